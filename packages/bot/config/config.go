@@ -22,8 +22,9 @@ type StorageConfig struct {
 }
 
 type ServiceConfig struct {
-	Api       string `yaml:"api"`
-	Websocket string `yaml:"websocket"`
+	Api             string `yaml:"api"`
+	Websocket       string `yaml:"websocket"`
+	EnrollmentToken string `yaml:"enrollment_token"`
 }
 
 type ConfigCtxKey struct{}
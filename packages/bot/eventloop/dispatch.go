@@ -29,6 +29,12 @@ func (s *SessionHub) startDispatch() {
 			err := sonic.Unmarshal(msg, &event)
 			if err != nil {
 				logger.Logger().Error("Failed to unmarshal incoming message", zap.Error(err))
+				// Reply to the session the message names, if it can be read.
+				var header struct {
+					SessionID uuid.UUID `json:"session_id"`
+				}
+				_ = sonic.Unmarshal(msg, &header)
+				s.sendError(header.SessionID, share.ErrorMalformedMessage, err.Error())
 				continue
 			}
 			s.dispatchEvent(event)
@@ -38,12 +44,14 @@ func (s *SessionHub) startDispatch() {
 
 func (s *SessionHub) dispatchEvent(event share.Message) {
 	payload := event.Payload
+	sessionId := event.SessionID
+
 	if payload.IsUnknown() {
 		logger.Logger().Warn("Received unknown message type", zap.String("type", event.Payload.Type))
+		s.sendError(sessionId, share.ErrorUnknownMessageType, "unknown payload type "+event.Payload.Type)
 		return
 	}
 
-	sessionId := event.SessionID
 	logger.Logger().Debug("Dispatching event", zap.String("session_id", sessionId.String()))
 
 	if payload.IsInstruction() {
@@ -51,12 +59,37 @@ func (s *SessionHub) dispatchEvent(event share.Message) {
 		return
 	}
 
+	if payload.IsEvent() {
+		logger.Logger().Warn("Impossible for client to receive event messages, ignoring")
+		s.sendError(sessionId, share.ErrorUnexpectedMessage, "events are only sent by the robot")
+		return
+	}
+
 	s.lock.RLock()
 	session, exists := s.sessions[sessionId]
 	s.lock.RUnlock()
 
+	if payload.IsError() {
+		// Errors are never answered with errors, so that both sides cannot
+		// keep reporting each other's replies.
+		var content share.ErrorContent
+		_ = sonic.Unmarshal(payload.Content, &content)
+		logger.Logger().Warn("Remote reported an error",
+			zap.String("session_id", sessionId.String()),
+			zap.String("code", content.Code),
+			zap.String("message", content.Message))
+		if exists {
+			close(session.readCh)
+			s.DeleteSession(sessionId)
+		}
+		return
+	}
+
 	if !exists {
 		logger.Logger().Warn("No session found for event", zap.String("session_id", sessionId.String()))
+		if payload.IsResponse() {
+			s.sendError(sessionId, share.ErrorUnknownSession, "no session is open with this ID")
+		}
 		return
 	}
 
@@ -66,8 +99,16 @@ func (s *SessionHub) dispatchEvent(event share.Message) {
 		s.DeleteSession(sessionId)
 	} else if payload.IsResponse() {
 		session.readCh <- payload.Content
-	} else if payload.IsEvent() {
-		logger.Logger().Warn("Impossible for client to receive event messages, ignoring")
+	}
+}
+
+// sendError reports a message that could not be handled back to the
+// service.
+func (s *SessionHub) sendError(sessionId uuid.UUID, code string, message string) {
+	writer := s.ctx.Value(lib.WsWriterCtxKey{}).(chan any)
+	select {
+	case <-s.ctx.Done():
+	case writer <- share.NewMessageWithId(sessionId, share.NewError(code, message)):
 	}
 }
 
@@ -82,12 +123,14 @@ func (s *SessionHub) StartInstructionHandler(sessionId uuid.UUID, content sonic.
 	err := sonic.Unmarshal(content, &instr)
 	if err != nil {
 		logger.Logger().Error("Failed to unmarshal instruction message", zap.Error(err))
+		s.sendError(sessionId, share.ErrorInvalidContent, err.Error())
 		return
 	}
 
 	handler, exists := instructions.InstructionHandlers[instr.Instruction]
 	if !exists {
 		logger.Logger().Warn("No handler found for instruction", zap.String("instruction", instr.Instruction))
+		s.sendError(sessionId, share.ErrorUnknownInstruction, "unknown instruction "+instr.Instruction)
 		return
 	}
 
@@ -2,6 +2,7 @@ package instructions
 
 import (
 	"context"
+	"errors"
 	"fmt"
 	"io"
 	"net/http"
@@ -31,10 +32,11 @@ type UpdateBinaryResponse struct {
 }
 
 // UpdateBinaryHandler registers the update_binary instruction using the
-// ResponseAction pattern.
+// FallibleResponseAction pattern, so failures reach the service as "error"
+// payloads.
 var UpdateBinaryHandler = InstructionHandler{
 	Instruction: InstructionUpdateBinary,
-	Action:      share.WrapResponseAction(UpdateBinaryAction),
+	Action:      share.WrapFallibleResponseAction(UpdateBinaryAction),
 }
 
 // elfMagic is the first 4 bytes of any valid ELF binary.
@@ -53,16 +55,16 @@ func sanitizeURL(raw string) string {
 	return u.Host + u.Path
 }
 
-func UpdateBinaryAction(ctx context.Context, req UpdateBinaryRequest) UpdateBinaryResponse {
+func UpdateBinaryAction(ctx context.Context, req UpdateBinaryRequest) (UpdateBinaryResponse, error) {
 	logger.Logger().Info("UpdateBinaryAction called", zap.String("artifact_url", sanitizeURL(req.ArtifactUrl)))
 
 	execPath, err := os.Executable()
 	if err != nil {
-		return UpdateBinaryResponse{Status: "error", Message: fmt.Sprintf("failed to get executable path: %v", err)}
+		return UpdateBinaryResponse{}, fmt.Errorf("failed to get executable path: %w", err)
 	}
 	execPath, err = filepath.EvalSymlinks(execPath)
 	if err != nil {
-		return UpdateBinaryResponse{Status: "error", Message: fmt.Sprintf("failed to resolve symlinks: %v", err)}
+		return UpdateBinaryResponse{}, fmt.Errorf("failed to resolve symlinks: %w", err)
 	}
 
 	execDir := filepath.Dir(execPath)
@@ -71,7 +73,7 @@ func UpdateBinaryAction(ctx context.Context, req UpdateBinaryRequest) UpdateBina
 	// atomic rename.
 	tmpFile, err := os.CreateTemp(execDir, ".update_binary_*")
 	if err != nil {
-		return UpdateBinaryResponse{Status: "error", Message: fmt.Sprintf("failed to create temp file: %v", err)}
+		return UpdateBinaryResponse{}, fmt.Errorf("failed to create temp file: %w", err)
 	}
 	tmpPath := tmpFile.Name()
 
@@ -86,24 +88,24 @@ func UpdateBinaryAction(ctx context.Context, req UpdateBinaryRequest) UpdateBina
 	httpReq, err := http.NewRequestWithContext(ctx, http.MethodGet, req.ArtifactUrl, nil)
 	if err != nil {
 		cleanup()
-		return UpdateBinaryResponse{Status: "error", Message: fmt.Sprintf("failed to create request: %v", err)}
+		return UpdateBinaryResponse{}, fmt.Errorf("failed to create request: %w", err)
 	}
 	resp, err := httpClient.Do(httpReq)
 	if err != nil {
 		cleanup()
-		return UpdateBinaryResponse{Status: "error", Message: fmt.Sprintf("failed to download binary: %v", err)}
+		return UpdateBinaryResponse{}, fmt.Errorf("failed to download binary: %w", err)
 	}
 	defer resp.Body.Close()
 
 	if resp.StatusCode != http.StatusOK {
 		cleanup()
-		return UpdateBinaryResponse{Status: "error", Message: fmt.Sprintf("download returned status %d", resp.StatusCode)}
+		return UpdateBinaryResponse{}, fmt.Errorf("download returned status %d", resp.StatusCode)
 	}
 
 	_, err = io.Copy(tmpFile, resp.Body)
 	if err != nil {
 		cleanup()
-		return UpdateBinaryResponse{Status: "error", Message: fmt.Sprintf("failed to write binary: %v", err)}
+		return UpdateBinaryResponse{}, fmt.Errorf("failed to write binary: %w", err)
 	}
 	tmpFile.Close()
 
@@ -112,31 +114,31 @@ func UpdateBinaryAction(ctx context.Context, req UpdateBinaryRequest) UpdateBina
 	f, err := os.Open(tmpPath)
 	if err != nil {
 		os.Remove(tmpPath)
-		return UpdateBinaryResponse{Status: "error", Message: fmt.Sprintf("failed to open temp file for validation: %v", err)}
+		return UpdateBinaryResponse{}, fmt.Errorf("failed to open temp file for validation: %w", err)
 	}
 	_, err = io.ReadFull(f, header)
 	f.Close()
 	if err != nil {
 		os.Remove(tmpPath)
-		return UpdateBinaryResponse{Status: "error", Message: fmt.Sprintf("failed to read header: %v", err)}
+		return UpdateBinaryResponse{}, fmt.Errorf("failed to read header: %w", err)
 	}
 	for i := 0; i < 4; i++ {
 		if header[i] != elfMagic[i] {
 			os.Remove(tmpPath)
-			return UpdateBinaryResponse{Status: "error", Message: "downloaded file is not a valid ELF binary"}
+			return UpdateBinaryResponse{}, errors.New("downloaded file is not a valid ELF binary")
 		}
 	}
 
 	// Set executable permissions.
 	if err := os.Chmod(tmpPath, 0755); err != nil {
 		os.Remove(tmpPath)
-		return UpdateBinaryResponse{Status: "error", Message: fmt.Sprintf("failed to chmod: %v", err)}
+		return UpdateBinaryResponse{}, fmt.Errorf("failed to chmod: %w", err)
 	}
 
 	// Atomic replace via same-filesystem rename.
 	if err := os.Rename(tmpPath, execPath); err != nil {
 		os.Remove(tmpPath)
-		return UpdateBinaryResponse{Status: "error", Message: fmt.Sprintf("failed to replace binary: %v", err)}
+		return UpdateBinaryResponse{}, fmt.Errorf("failed to replace binary: %w", err)
 	}
 
 	logger.Logger().Info("Binary replaced successfully, scheduling restart", zap.String("path", execPath))
@@ -155,5 +157,5 @@ func UpdateBinaryAction(ctx context.Context, req UpdateBinaryRequest) UpdateBina
 		}
 	}()
 
-	return UpdateBinaryResponse{Status: "post_update", Message: "success, restarting..."}
+	return UpdateBinaryResponse{Status: "post_update", Message: "success, restarting..."}, nil
 }
@@ -10,16 +10,28 @@ import (
 	"github.com/Alliance-Algorithm/rmcs-actions/packages/bot/logger"
 )
 
+// Actions read their request from the session's reader. The reader is
+// closed without a request when the remote closes the session or reports
+// an error for it first; the action then ends without answering.
 type OneShotAction[T any] func(context context.Context, request T)
 type ResponseAction[T any, O any] func(context context.Context, request T) O
 
+// FallibleResponseAction answers with an "error" payload instead of a
+// response when it returns an error.
+type FallibleResponseAction[T any, O any] func(context context.Context, request T) (O, error)
+
 func WrapOneShotAction[T any](action OneShotAction[T]) lib.SessionAction {
 	return func(ctx context.Context) {
-		request := (<-ctx.Value(lib.ResponseReaderCtxKey{}).(chan sonic.NoCopyRawMessage))
+		request, ok := <-ctx.Value(lib.ResponseReaderCtxKey{}).(chan sonic.NoCopyRawMessage)
+		if !ok {
+			logger.Logger().Info("Session closed before its request arrived")
+			return
+		}
 		var req T
 		err := sonic.Unmarshal(request, &req)
 		if err != nil {
 			logger.Logger().Error("Failed to unmarshal request in OneShotAction", zap.Error(err))
+			SendError(ctx, ErrorInvalidContent, err.Error())
 			return
 		}
 		(action)(ctx, req)
@@ -27,8 +39,18 @@ func WrapOneShotAction[T any](action OneShotAction[T]) lib.SessionAction {
 }
 
 func WrapResponseAction[T any, O any](action ResponseAction[T, O]) lib.SessionAction {
+	return WrapFallibleResponseAction(func(ctx context.Context, request T) (O, error) {
+		return action(ctx, request), nil
+	})
+}
+
+func WrapFallibleResponseAction[T any, O any](action FallibleResponseAction[T, O]) lib.SessionAction {
 	return func(ctx context.Context) {
-		request := (<-ctx.Value(lib.ResponseReaderCtxKey{}).(chan sonic.NoCopyRawMessage))
+		request, ok := <-ctx.Value(lib.ResponseReaderCtxKey{}).(chan sonic.NoCopyRawMessage)
+		if !ok {
+			logger.Logger().Info("Session closed before its request arrived")
+			return
+		}
 		var req T
 		if len(request) == 0 {
 			req = *new(T)
@@ -36,6 +58,7 @@ func WrapResponseAction[T any, O any](action ResponseAction[T, O]) lib.SessionAc
 			err := sonic.Unmarshal(request, &req)
 			if err != nil {
 				logger.Logger().Error("Failed to unmarshal request in ResponseAction", zap.Error(err))
+				SendError(ctx, ErrorInvalidContent, err.Error())
 				return
 			}
 		}
@@ -43,8 +66,15 @@ func WrapResponseAction[T any, O any](action ResponseAction[T, O]) lib.SessionAc
 		done := make(chan struct{})
 		actionCtx := context.WithValue(ctx, lib.WsSendDoneCtxKey{}, done)
 
-		response := (action)(actionCtx, req)
-		wrapped := NewMessage(ctx, NewResponse(response))
+		var payload any
+		response, err := (action)(actionCtx, req)
+		if err != nil {
+			logger.Logger().Error("Action failed", zap.Error(err))
+			payload = NewError(ErrorInstructionFailed, err.Error())
+		} else {
+			payload = NewResponse(response)
+		}
+		wrapped := NewMessage(ctx, payload)
 		ctx.Value(lib.WsWriterCtxKey{}).(chan any) <- lib.SendEnvelope{
 			Payload: wrapped,
 			Done:    done,
@@ -0,0 +1,49 @@
+package share
+
+import (
+	"context"
+
+	"github.com/Alliance-Algorithm/rmcs-actions/packages/bot/lib"
+)
+
+// Error codes of the "error" payload, shared with the service.
+const (
+	ErrorMalformedMessage   = "malformed_message"
+	ErrorUnknownMessageType = "unknown_message_type"
+	ErrorUnexpectedMessage  = "unexpected_message"
+	ErrorUnknownSession     = "unknown_session"
+	ErrorUnknownInstruction = "unknown_instruction"
+	ErrorUnknownEvent       = "unknown_event"
+	ErrorInvalidContent     = "invalid_content"
+	ErrorInstructionFailed  = "instruction_failed"
+)
+
+type Error struct {
+	// This will always be "error"
+	TypeName string       `json:"type"`
+	Content  ErrorContent `json:"content"`
+}
+
+type ErrorContent struct {
+	Code    string `json:"code"`
+	Message string `json:"message"`
+}
+
+func NewError(code string, message string) *Error {
+	return &Error{
+		TypeName: "error",
+		Content: ErrorContent{
+			Code:    code,
+			Message: message,
+		},
+	}
+}
+
+// SendError reports an error for the session carried by ctx.
+func SendError(ctx context.Context, code string, message string) {
+	writer := ctx.Value(lib.WsWriterCtxKey{}).(chan any)
+	select {
+	case <-ctx.Done():
+	case writer <- NewMessage(ctx, NewError(code, message)):
+	}
+}
@@ -21,6 +21,7 @@ const (
 	messageTypeInstruction = "instruction"
 	messageTypeEvent       = "event"
 	messageTypeResponse    = "response"
+	messageTypeError       = "error"
 	messageTypeClose       = "close"
 )
 
@@ -36,6 +37,10 @@ func (m *MessagePayload) IsResponse() bool {
 	return m.Type == messageTypeResponse
 }
 
+func (m *MessagePayload) IsError() bool {
+	return m.Type == messageTypeError
+}
+
 func (m *MessagePayload) IsClose() bool {
 	return m.Type == messageTypeClose
 }
@@ -44,5 +49,6 @@ func (m *MessagePayload) IsUnknown() bool {
 	return m.Type != messageTypeInstruction &&
 		m.Type != messageTypeEvent &&
 		m.Type != messageTypeResponse &&
+		m.Type != messageTypeError &&
 		m.Type != messageTypeClose
 }
@@ -50,6 +50,10 @@ func NewEvent(eventName string, detail any) *Event {
 
 func NewMessage(ctx context.Context, payload any) *SenderMessage {
 	sessionId := ctx.Value(lib.SessionIdCtxKey{}).(uuid.UUID)
+	return NewMessageWithId(sessionId, payload)
+}
+
+func NewMessageWithId(sessionId uuid.UUID, payload any) *SenderMessage {
 	return &SenderMessage{
 		SessionID:      sessionId,
 		LocalTimestamp: time.Now().UnixMilli(),
@@ -15,9 +15,10 @@ import (
 )
 
 type robotInfo struct {
-	Mac  string    `json:"mac"`
-	Name string    `json:"name"`
-	Uuid uuid.UUID `json:"uuid"`
+	Mac    string    `json:"mac"`
+	Name   string    `json:"name"`
+	Uuid   uuid.UUID `json:"uuid"`
+	Secret string    `json:"secret,omitempty"`
 }
 
 func getRobotIdFromLocalStorage(ctx context.Context) (*robotInfo, error) {
@@ -52,7 +53,8 @@ func saveRobotIdToLocalStorage(ctx context.Context, info *robotInfo) error {
 	if err != nil {
 		return err
 	}
-	err = os.WriteFile(robotIdFile, data, 0755)
+	// The file holds the robot secret, so only the bot may read it.
+	err = os.WriteFile(robotIdFile, data, 0600)
 	if err != nil {
 		return err
 	}
@@ -132,30 +134,56 @@ func getRobotAuthInfo(ctx context.Context) (*robotInfo, error) {
 	}, nil
 }
 
-func AuthenticateRobot(ctx context.Context) (uuid.UUID, error) {
+// AuthenticateRobot registers the robot with the service and returns its ID
+// together with the secret to connect with, which is empty until the robot
+// is approved.
+func AuthenticateRobot(ctx context.Context) (uuid.UUID, string, error) {
+	cfg, ok := config.GetConfigFromCtx(ctx)
+	if !ok {
+		return uuid.Nil, "", errors.New("no config is provided within context")
+	}
+
 	info, err := getRobotAuthInfo(ctx)
 	if err != nil {
-		return uuid.Nil, err
+		return uuid.Nil, "", err
 	}
 
 	err = saveRobotIdToLocalStorage(ctx, &robotInfo{
-		Mac:  info.Mac,
-		Name: info.Name,
-		Uuid: info.Uuid,
+		Mac:    info.Mac,
+		Name:   info.Name,
+		Uuid:   info.Uuid,
+		Secret: info.Secret,
 	})
 	if err != nil {
-		return uuid.Nil, err
+		return uuid.Nil, "", err
 	}
 
 	syncReq := ident.NewSyncRequest(ident.SyncRequestBody{
-		Mac:  info.Mac,
-		Name: info.Name,
-		Uuid: info.Uuid,
+		Mac:             info.Mac,
+		Name:            info.Name,
+		Uuid:            info.Uuid,
+		EnrollmentToken: cfg.Service.EnrollmentToken,
+		RobotSecret:     info.Secret,
 	})
-	_, err = syncReq.Send(ctx)
+	resp, err := syncReq.Send(ctx)
 	if err != nil {
-		return uuid.Nil, err
+		return uuid.Nil, "", err
+	}
+
+	// The service sends the secret only once, right after the robot is
+	// approved.
+	if resp.RobotSecret != "" {
+		info.Secret = resp.RobotSecret
+		err = saveRobotIdToLocalStorage(ctx, info)
+		if err != nil {
+			logger.Logger().Error("Failed to save robot secret, the robot has to be approved again", zap.Error(err))
+			return uuid.Nil, "", err
+		}
+		logger.Logger().Info("Received robot secret")
+	}
+	if resp.Enrollment != "" && resp.Enrollment != "approved" {
+		logger.Logger().Info("Robot is not approved yet", zap.String("enrollment", resp.Enrollment))
 	}
 
-	return info.Uuid, nil
+	return info.Uuid, info.Secret, nil
 }
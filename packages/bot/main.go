@@ -4,6 +4,8 @@ import (
 	"context"
 	"flag"
 	"fmt"
+	"net/http"
+	"net/url"
 	"os"
 	"os/signal"
 	"time"
@@ -53,6 +55,7 @@ func main() {
 
 	for {
 		var robotId uuid.UUID
+		var robotSecret string
 		for {
 			select {
 			case <-rootCtx.Done():
@@ -61,7 +64,7 @@ func main() {
 			default:
 			}
 
-			rid, err := lib.AuthenticateRobot(baseCtx)
+			rid, secret, err := lib.AuthenticateRobot(baseCtx)
 			if err != nil {
 				logger.Logger().Warn("Failed to authenticate robot, retrying", zap.Error(err), zap.Duration("retry_in", authRetryDelay))
 				if !waitForRetry(rootCtx, authRetryDelay) {
@@ -72,12 +75,13 @@ func main() {
 			}
 
 			robotId = rid
+			robotSecret = secret
 			logger.Logger().Info("Robot authenticated successfully", zap.String("robot_id", robotId.String()))
 			break
 		}
 
 		runCtx := context.WithValue(baseCtx, lib.RobotIdCtxKey{}, robotId)
-		wsUrl := cfg.Service.Websocket + "/" + robotId.String()
+		wsUrl := cfg.Service.Websocket + "/" + robotId.String() + "?version=" + url.QueryEscape(Version)
 
 		select {
 		case <-rootCtx.Done():
@@ -86,8 +90,14 @@ func main() {
 		default:
 		}
 
+		// Approved robots prove their identity with the secret they were issued.
+		dialOpts := &websocket.DialOptions{}
+		if robotSecret != "" {
+			dialOpts.HTTPHeader = http.Header{"Authorization": []string{"Bearer " + robotSecret}}
+		}
+
 		dialCtx, cancelDial := context.WithTimeout(runCtx, dialTimeout)
-		c, _, err := websocket.Dial(dialCtx, wsUrl, nil)
+		c, _, err := websocket.Dial(dialCtx, wsUrl, dialOpts)
 		cancelDial()
 		if err != nil {
 			logger.Logger().Warn("Failed to connect to websocket, restarting from authentication", zap.Error(err), zap.Duration("retry_in", reconnectDelay))
@@ -6,13 +6,17 @@ import (
 )
 
 type SyncRequestBody struct {
-	Mac  string    `json:"mac"`
-	Name string    `json:"name"`
-	Uuid uuid.UUID `json:"uuid"`
+	Mac             string    `json:"mac"`
+	Name            string    `json:"name"`
+	Uuid            uuid.UUID `json:"uuid"`
+	EnrollmentToken string    `json:"enrollment_token,omitempty"`
+	RobotSecret     string    `json:"robot_secret,omitempty"`
 }
 
 type SyncResponse struct {
-	Success bool `json:"success"`
+	Success     bool   `json:"success"`
+	Enrollment  string `json:"enrollment"`
+	RobotSecret string `json:"robot_secret"`
 }
 
 type SyncRequest = requests.BaseRequest[SyncRequestBody, SyncResponse]
@@ -16,7 +16,32 @@
           export DATABASE_URL="sqlite://$PWD/runtime/storage/rmcs-actions.db"
           db_path="$PWD/runtime/storage/rmcs-actions.db"
           mkdir -p "$(dirname "$db_path")"
-          sqlite3 "$db_path" < ${./schema.sql}
+          # Apply pending migrations and record them the way the service
+          # does, so that the database serves both sqlx query checking and
+          # local runs.
+          sqlite3 "$db_path" "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version     INTEGER PRIMARY KEY NOT NULL,
+            description TEXT NOT NULL,
+            checksum    BLOB NOT NULL,
+            applied_at  TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+          )"
+          for migration in ${./migrations}/*.sql; do
+            name=$(basename "$migration" .sql)
+            version=$((10#''${name%%_*}))
+            description=$(echo "''${name#*_}" | tr _ ' ')
+            applied=$(sqlite3 "$db_path" \
+              "SELECT COUNT(*) FROM schema_migrations WHERE version = $version")
+            if [ "$applied" = 0 ]; then
+              checksum=$(sha384sum "$migration" | cut -d ' ' -f 1)
+              {
+                echo "BEGIN;"
+                cat "$migration"
+                echo "INSERT INTO schema_migrations (version, description, checksum)"
+                echo "VALUES ($version, '$description', X'$checksum');"
+                echo "COMMIT;"
+              } | sqlite3 -bail "$db_path"
+            fi
+          done
         '';
       };
     };
@@ -1,6 +1,5 @@
--- Schema for rmcs-actions SQLite database.
--- Used to initialize an empty database so that sqlx compile-time query
--- checking can validate SQL expressions.
+-- Schema of the last release without migrations. Databases created by it
+-- already have these tables.
 
 CREATE TABLE IF NOT EXISTS robots (
     uuid TEXT PRIMARY KEY NOT NULL,
@@ -0,0 +1,140 @@
+-- Fleet metadata, jobs, connection history, operator accounts, the audit
+-- log and robot enrollment.
+
+ALTER TABLE robots ADD COLUMN role TEXT;
+ALTER TABLE robots ADD COLUMN team_color TEXT;
+ALTER TABLE robots ADD COLUMN version TEXT;
+ALTER TABLE robots ADD COLUMN last_seen TIMESTAMP;
+ALTER TABLE robots ADD COLUMN enrollment TEXT NOT NULL DEFAULT 'pending';
+ALTER TABLE robots ADD COLUMN machine_id TEXT;
+ALTER TABLE robots ADD COLUMN archived_at TIMESTAMP;
+
+-- Robots registered before enrollment existed stay usable.
+UPDATE robots SET enrollment = 'approved';
+
+CREATE TABLE robot_tags (
+    robot_uuid TEXT NOT NULL,
+    tag        TEXT NOT NULL,
+    PRIMARY KEY (robot_uuid, tag),
+    FOREIGN KEY (robot_uuid) REFERENCES robots(uuid) ON DELETE CASCADE
+);
+
+-- UUIDs of robots merged into another one, which their bots may still use.
+CREATE TABLE robot_aliases (
+    alias_uuid TEXT PRIMARY KEY NOT NULL,
+    robot_uuid TEXT NOT NULL,
+    FOREIGN KEY (robot_uuid) REFERENCES robots(uuid) ON DELETE CASCADE
+);
+
+CREATE TABLE network_interfaces (
+    robot_uuid    TEXT NOT NULL,
+    name          TEXT NOT NULL,
+    hardware_addr TEXT NOT NULL,
+    PRIMARY KEY (robot_uuid, name),
+    FOREIGN KEY (robot_uuid) REFERENCES robots(uuid) ON DELETE CASCADE
+);
+
+CREATE TABLE network_addresses (
+    robot_uuid TEXT NOT NULL,
+    interface  TEXT NOT NULL,
+    ip         TEXT NOT NULL,
+    prefix_len INTEGER,
+    family     TEXT NOT NULL,
+    FOREIGN KEY (robot_uuid) REFERENCES robots(uuid) ON DELETE CASCADE
+);
+
+CREATE INDEX network_interfaces_hardware_addr
+    ON network_interfaces (hardware_addr);
+CREATE INDEX network_addresses_ip ON network_addresses (ip);
+
+CREATE TABLE jobs (
+    id          TEXT PRIMARY KEY NOT NULL,
+    kind        TEXT NOT NULL,
+    status      TEXT NOT NULL,
+    outcome     TEXT,
+    created_at  TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    started_at  TIMESTAMP,
+    finished_at TIMESTAMP
+);
+
+CREATE TABLE job_robots (
+    job_id     TEXT NOT NULL,
+    robot_uuid TEXT NOT NULL,
+    status     TEXT NOT NULL,
+    message    TEXT,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (job_id, robot_uuid),
+    FOREIGN KEY (job_id) REFERENCES jobs(id) ON DELETE CASCADE
+);
+
+CREATE TABLE connection_sessions (
+    id                INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    robot_uuid        TEXT NOT NULL,
+    connected_at      TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    disconnected_at   TIMESTAMP,
+    remote_addr       TEXT,
+    disconnect_reason TEXT
+);
+
+CREATE INDEX connection_sessions_robot_uuid
+    ON connection_sessions (robot_uuid, connected_at);
+
+CREATE TABLE users (
+    id            INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    username      TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    password_hash TEXT NOT NULL,
+    role          TEXT NOT NULL,
+    created_at    TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE TABLE user_scope_tags (
+    user_id INTEGER NOT NULL,
+    tag     TEXT NOT NULL,
+    PRIMARY KEY (user_id, tag),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE auth_sessions (
+    token_hash TEXT PRIMARY KEY NOT NULL,
+    user_id    INTEGER NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE api_tokens (
+    id           INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id      INTEGER NOT NULL,
+    name         TEXT NOT NULL,
+    token_hash   TEXT NOT NULL UNIQUE,
+    created_at   TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_used_at TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE audit_log (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    created_at  TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    category    TEXT NOT NULL,
+    actor       TEXT NOT NULL,
+    actor_id    INTEGER,
+    action      TEXT NOT NULL,
+    robot_uuid  TEXT,
+    parameters  TEXT,
+    outcome     TEXT NOT NULL,
+    message     TEXT,
+    duration_ms INTEGER NOT NULL
+);
+
+CREATE INDEX audit_log_robot_uuid ON audit_log (robot_uuid);
+
+-- Entries are never changed or removed once written.
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'audit log is append-only');
+END;
+
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'audit log is append-only');
+END;
@@ -0,0 +1,23 @@
+-- History tables are pruned by the retention task. Audit entries stay
+-- append-only until they are older than the retention floor, which the
+-- service also reads from `audit_log_retention` to check its configuration.
+
+CREATE TABLE audit_log_retention (
+    floor_days INTEGER NOT NULL CHECK (floor_days > 0)
+);
+
+INSERT INTO audit_log_retention (floor_days) VALUES (30);
+
+DROP TRIGGER audit_log_no_delete;
+
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+WHEN julianday(OLD.created_at) > julianday(
+    'now',
+    '-' || (SELECT floor_days FROM audit_log_retention) || ' days'
+)
+BEGIN
+    SELECT RAISE(ABORT, 'audit log entries younger than the retention floor are kept');
+END;
+
+CREATE INDEX audit_log_created_at ON audit_log (created_at);
+CREATE INDEX jobs_finished_at ON jobs (finished_at);
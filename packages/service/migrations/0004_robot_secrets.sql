@@ -0,0 +1,6 @@
+-- Secrets robots present when connecting to `/ws`. A secret is issued when
+-- a robot is approved and kept in `undelivered_secret` until the robot
+-- picks it up through `/ident/sync`; afterwards only its digest remains.
+
+ALTER TABLE robots ADD COLUMN secret_hash TEXT;
+ALTER TABLE robots ADD COLUMN undelivered_secret TEXT;
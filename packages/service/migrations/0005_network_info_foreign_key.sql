@@ -0,0 +1,20 @@
+-- Releases predating migrations created `network_info` without its foreign
+-- key to `robots`. SQLite cannot add a constraint to an existing table, so
+-- the table is rebuilt; info of robots that no longer exist is dropped.
+-- Databases whose table already has the key record this migration without
+-- running it.
+
+ALTER TABLE network_info RENAME TO network_info_legacy;
+
+CREATE TABLE network_info (
+    robot_uuid   TEXT PRIMARY KEY NOT NULL,
+    info         TEXT NOT NULL,
+    last_updated TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (robot_uuid) REFERENCES robots(uuid) ON DELETE CASCADE
+);
+
+INSERT INTO network_info (robot_uuid, info, last_updated)
+SELECT robot_uuid, info, last_updated FROM network_info_legacy
+WHERE robot_uuid IN (SELECT uuid FROM robots);
+
+DROP TABLE network_info_legacy;
@@ -0,0 +1,6 @@
+-- Secrets of robots merged into another one, which their bots keep
+-- presenting when they connect with the merged robot's UUID. Cleared when
+-- the remaining robot is issued a new secret.
+
+ALTER TABLE robot_aliases ADD COLUMN secret_hash TEXT;
+ALTER TABLE robot_aliases ADD COLUMN undelivered_secret TEXT;
@@ -1,40 +1,197 @@
 pub mod action;
+pub mod audit;
+pub mod auth;
+pub mod backup;
+pub mod enrollment;
+pub mod error;
+pub mod export;
+pub mod health;
 pub mod ident;
+pub mod jobs;
 pub mod meta;
+pub mod registry;
+pub mod retention;
+pub mod sessions;
 pub mod stats;
 
-use poem::Error;
+use std::{fmt, time::Duration};
+
+use poem::{Error, IntoResponse, Response, error::NotFoundError};
 use poem_openapi::{
     ApiResponse, OpenApi,
+    error::{
+        AuthorizationError, ParseMultipartError, ParseParamError,
+        ParsePathError, ParseRequestPayloadError,
+    },
     payload::{Json, PlainText},
 };
 use serde::Deserialize;
 
+use crate::api::error::{ErrorCode, Problem};
+
+/// Error response of every endpoint, as problem details whose `code` tells
+/// clients what went wrong.
 #[derive(Debug, Clone, ApiResponse)]
-#[oai(bad_request_handler = "bad_request")]
+#[oai(display)]
 pub enum GenericResponse {
-    #[oai(status = 400)]
-    BadRequest(PlainText<String>),
-    #[oai(status = 404)]
-    NotFound(PlainText<String>),
-    #[oai(status = 500)]
-    InternalError(PlainText<String>),
+    /// `validation_failed`
+    #[oai(status = 400, content_type = "application/problem+json")]
+    BadRequest(Json<Problem>),
+    /// `unauthorized`
+    #[oai(status = 401, content_type = "application/problem+json")]
+    Unauthorized(Json<Problem>),
+    /// `forbidden`
+    #[oai(status = 403, content_type = "application/problem+json")]
+    Forbidden(Json<Problem>),
+    /// `not_found`
+    #[oai(status = 404, content_type = "application/problem+json")]
+    NotFound(Json<Problem>),
+    /// `conflict`, `robot_offline`, `instruction_cancelled` or
+    /// `robot_locked`
+    #[oai(status = 409, content_type = "application/problem+json")]
+    Conflict(Json<Problem>),
+    /// `too_many_requests`
+    #[oai(status = 429, content_type = "application/problem+json")]
+    TooManyRequests(Json<Problem>),
+    /// `internal_error`
+    #[oai(status = 500, content_type = "application/problem+json")]
+    InternalError(Json<Problem>),
+    /// `bot_error`
+    #[oai(status = 502, content_type = "application/problem+json")]
+    BadGateway(Json<Problem>),
+    /// `instruction_timeout`
+    #[oai(status = 504, content_type = "application/problem+json")]
+    GatewayTimeout(Json<Problem>),
+}
+
+impl GenericResponse {
+    pub fn new(code: ErrorCode, detail: impl Into<String>) -> Self {
+        Self::from_problem(Problem::new(code, detail))
+    }
+
+    pub fn validation_failed(detail: impl Into<String>) -> Self {
+        Self::new(ErrorCode::ValidationFailed, detail)
+    }
+
+    pub fn forbidden(detail: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Forbidden, detail)
+    }
+
+    pub fn not_found(detail: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, detail)
+    }
+
+    pub fn conflict(detail: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Conflict, detail)
+    }
+
+    pub fn robot_offline(robot_uuid: &str) -> Self {
+        Self::from_problem(
+            Problem::new(
+                ErrorCode::RobotOffline,
+                format!("Robot {robot_uuid} is not connected"),
+            )
+            .with_robot(robot_uuid),
+        )
+    }
+
+    pub fn bot_error(robot_uuid: &str, detail: impl Into<String>) -> Self {
+        Self::from_problem(
+            Problem::new(ErrorCode::BotError, detail).with_robot(robot_uuid),
+        )
+    }
+
+    pub fn instruction_timeout(robot_uuid: &str, time_limit: Duration) -> Self {
+        Self::from_problem(
+            Problem::new(
+                ErrorCode::InstructionTimeout,
+                format!(
+                    "Robot {robot_uuid} did not answer within {} seconds",
+                    time_limit.as_secs()
+                ),
+            )
+            .with_robot(robot_uuid),
+        )
+    }
+
+    pub fn problem(&self) -> &Problem {
+        match self {
+            GenericResponse::BadRequest(Json(problem))
+            | GenericResponse::Unauthorized(Json(problem))
+            | GenericResponse::Forbidden(Json(problem))
+            | GenericResponse::NotFound(Json(problem))
+            | GenericResponse::Conflict(Json(problem))
+            | GenericResponse::TooManyRequests(Json(problem))
+            | GenericResponse::InternalError(Json(problem))
+            | GenericResponse::BadGateway(Json(problem))
+            | GenericResponse::GatewayTimeout(Json(problem)) => problem,
+        }
+    }
+
+    pub fn from_problem(problem: Problem) -> Self {
+        let problem = Json(problem);
+        match problem.code {
+            ErrorCode::ValidationFailed => GenericResponse::BadRequest(problem),
+            ErrorCode::Unauthorized => GenericResponse::Unauthorized(problem),
+            ErrorCode::Forbidden => GenericResponse::Forbidden(problem),
+            ErrorCode::NotFound => GenericResponse::NotFound(problem),
+            ErrorCode::Conflict
+            | ErrorCode::RobotOffline
+            | ErrorCode::InstructionCancelled
+            | ErrorCode::RobotLocked => GenericResponse::Conflict(problem),
+            ErrorCode::TooManyRequests => {
+                GenericResponse::TooManyRequests(problem)
+            }
+            ErrorCode::InternalError => GenericResponse::InternalError(problem),
+            ErrorCode::BotError => GenericResponse::BadGateway(problem),
+            ErrorCode::InstructionTimeout => {
+                GenericResponse::GatewayTimeout(problem)
+            }
+        }
+    }
 }
 
-#[allow(clippy::needless_pass_by_value)]
-fn bad_request(err: Error) -> GenericResponse {
-    GenericResponse::BadRequest(PlainText(format!("Bad request: {err}")))
+/// Shows the detail, which is what the audit log records of failed
+/// requests.
+impl fmt::Display for GenericResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.problem().detail)
+    }
 }
 
 impl<T: Into<anyhow::Error>> From<T> for GenericResponse {
     fn from(err: T) -> Self {
-        GenericResponse::InternalError(PlainText(format!(
-            "Internal error: {}",
-            err.into()
-        )))
+        GenericResponse::new(
+            ErrorCode::InternalError,
+            format!("Internal error: {:#}", err.into()),
+        )
     }
 }
 
+/// Answers requests rejected before reaching an endpoint: those without
+/// valid credentials, for unknown paths, or whose parameters or payload do
+/// not parse. Other errors pass through unchanged.
+#[allow(clippy::unused_async)]
+pub async fn rejected_request(err: Error) -> Response {
+    let response = if err.is::<AuthorizationError>() {
+        GenericResponse::new(
+            ErrorCode::Unauthorized,
+            "A session cookie or API token is required",
+        )
+    } else if err.is::<NotFoundError>() {
+        GenericResponse::not_found("No endpoint at this path")
+    } else if err.is::<ParseParamError>()
+        || err.is::<ParsePathError>()
+        || err.is::<ParseRequestPayloadError>()
+        || err.is::<ParseMultipartError>()
+    {
+        GenericResponse::validation_failed(err.to_string())
+    } else {
+        return err.into_response();
+    };
+    response.into_response()
+}
+
 pub type ApiResult<T> = Result<Json<T>, GenericResponse>;
 pub type RawApiResult<T> = Result<T, GenericResponse>;
 
@@ -1,23 +1,42 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
-use futures_util::future::join_all;
 use poem_openapi::{
-    OpenApi,
-    payload::{Json, PlainText},
+    ApiResponse, OpenApi,
+    param::Query,
+    payload::Json,
+    types::{ToJSON, Type},
 };
 use tokio::time::timeout;
 
 use crate::{
-    api::{AnyDeserialize, ApiResult, GenericResponse},
-    database::with_database,
-    service::{CONNECTIONS, instructions::Instruction},
+    api::{
+        AnyDeserialize, ApiResult, GenericResponse,
+        auth::{Auth, scope::check_robot_scope},
+        error::{ErrorCode, Problem},
+        jobs::JobAccepted,
+    },
+    database::{
+        audit::Actor,
+        robot::{RobotFilter, RobotRole, TeamColor},
+        user::UserRole,
+        with_database,
+    },
+    service::{
+        CONNECTIONS,
+        connection::{Connection, InstructionError},
+        instructions::Instruction,
+    },
 };
 
+pub mod bulk;
+pub mod fan_out;
 pub mod fetch_network;
 pub mod set_robot_name;
 pub mod update_binary;
 
 const UPDATE_BINARY_TIMEOUT: Duration = Duration::from_secs(60);
+/// Status the robot reports once the new binary is in place.
+pub const UPDATE_BINARY_SUCCESS: &str = "post_update";
 
 fn parse_update_binary_response(
     info: &serde_json::Value,
@@ -36,28 +55,67 @@ fn parse_update_binary_response(
     update_binary::UpdateBinaryResponse { status, message }
 }
 
-fn update_binary_error_response(
-    message: impl Into<String>,
-) -> update_binary::UpdateBinaryResponse {
-    update_binary::UpdateBinaryResponse {
-        status: "error".to_string(),
-        message: message.into(),
+/// Live connection of a robot that can be instructed.
+fn connection_of(robot_uuid: &str) -> Result<Arc<Connection>, GenericResponse> {
+    let Some(conn) = CONNECTIONS.get(robot_uuid) else {
+        log::info!("No connection found for robot_id: {robot_uuid}");
+        return Err(GenericResponse::robot_offline(robot_uuid));
+    };
+    if !conn.value().is_approved() {
+        return Err(GenericResponse::conflict(format!(
+            "Robot {robot_uuid} is not approved"
+        )));
     }
+    Ok(conn.value().clone())
 }
 
-fn update_binary_instruction_failure_response(
-    err: impl std::fmt::Display,
-) -> update_binary::UpdateBinaryResponse {
-    update_binary_error_response(format!("instruction failed: {err}"))
+fn instruction_failed(
+    robot_uuid: &str,
+    err: InstructionError,
+) -> GenericResponse {
+    match err {
+        InstructionError::NotApproved => GenericResponse::conflict(format!(
+            "Robot {robot_uuid} is not approved"
+        )),
+        InstructionError::Cancelled => GenericResponse::from_problem(
+            Problem::new(
+                ErrorCode::InstructionCancelled,
+                format!("The instruction to robot {robot_uuid} was cancelled"),
+            )
+            .with_robot(robot_uuid),
+        ),
+        InstructionError::Locked(lock) => GenericResponse::from_problem(
+            Problem::new(
+                ErrorCode::RobotLocked,
+                format!("Robot {robot_uuid} is busy: {lock}"),
+            )
+            .with_robot(robot_uuid),
+        ),
+        InstructionError::Robot(err) => GenericResponse::bot_error(
+            robot_uuid,
+            format!("robot reported {err}"),
+        ),
+        err => GenericResponse::bot_error(
+            robot_uuid,
+            format!("instruction failed: {err}"),
+        ),
+    }
 }
 
-fn update_binary_timeout_response() -> update_binary::UpdateBinaryResponse {
-    update_binary_error_response(format!(
-        "instruction timed out after {} seconds",
-        UPDATE_BINARY_TIMEOUT.as_secs()
-    ))
+/// Result of an action that can optionally run as a job.
+#[derive(ApiResponse)]
+pub enum ActionResponse<T: ToJSON + Type> {
+    /// The action completed.
+    #[oai(status = 200)]
+    Completed(Json<T>),
+    /// The action was started as a job whose progress is available under
+    /// `/jobs/:id`.
+    #[oai(status = 202)]
+    Accepted(Json<JobAccepted>),
 }
 
+type ActionResult<T> = Result<ActionResponse<T>, GenericResponse>;
+
 pub struct ActionApi;
 
 #[OpenApi]
@@ -65,229 +123,291 @@ impl ActionApi {
     #[oai(path = "/action/set_robot_name", method = "post")]
     async fn set_robot_name(
         &self,
+        auth: Auth,
         request: Json<set_robot_name::SetRobotNameRequest>,
     ) -> ApiResult<set_robot_name::SetRobotNameResponse> {
-        if let Some(conn) = CONNECTIONS.get(&request.robot_uuid) {
-            let _ = conn
-                .value()
-                .send_instruction::<AnyDeserialize>(
-                    Instruction::SyncRobotName {
-                        robot_name: request.new_robot_name.clone(),
-                    },
-                )
+        let user = auth.require(UserRole::Operator)?;
+        check_robot_scope(user, &request.robot_uuid).await?;
+        let conn = connection_of(&request.robot_uuid)?;
+        // The name is stored while the rename still holds the robot's
+        // operation lock, so that concurrent renames fail with `Locked`
+        // instead of leaving the robot and the registry disagreeing.
+        conn.send_instruction_then(
+            Instruction::SyncRobotName {
+                robot_name: request.new_robot_name.clone(),
+            },
+            &Actor::from(user),
+            async |_: AnyDeserialize| {
+                with_database(|db| {
+                    db.set_robot_name(
+                        &request.robot_uuid,
+                        &request.new_robot_name,
+                    )
+                })?
                 .await?;
-            with_database(|db| {
-                db.set_robot_name(&request.robot_uuid, &request.new_robot_name)
-            })?
-            .await?;
-            Ok(Json(set_robot_name::SetRobotNameResponse))
-        } else {
-            log::info!(
-                "No connection found for robot_id: {}",
-                request.robot_uuid
+                anyhow::Ok(())
+            },
+        )
+        .await
+        .map_err(|err| {
+            log::error!(
+                "Failed to set name of robot {}: {:?}",
+                request.robot_uuid,
+                err
             );
-            Err(GenericResponse::BadRequest(PlainText(
-                "robot not connected".to_string(),
-            )))
-        }
+            instruction_failed(&request.robot_uuid, err)
+        })??;
+        Ok(Json(set_robot_name::SetRobotNameResponse))
     }
 
     #[oai(path = "/action/refresh_network", method = "post")]
     async fn refresh_network(
         &self,
+        auth: Auth,
         request: Json<fetch_network::FetchNetworkRequest>,
     ) -> ApiResult<fetch_network::FetchNetworkResponse> {
-        if let Some(conn) = CONNECTIONS.get(&request.robot_id) {
-            let net_info = conn
-                .value()
-                .send_instruction(Instruction::FetchNetwork {})
-                .await;
-            match net_info {
-                Ok(info) => {
-                    with_database(|db| {
-                        db.write_network_info(&request.robot_id, &info)
-                    })?
-                    .await
-                    .map_err(|err| {
-                        GenericResponse::InternalError(PlainText(format!(
-                            "Failed to write network info: {err}"
-                        )))
-                    })?;
-                    Ok(Json(fetch_network::FetchNetworkResponse {}))
-                }
-                Err(err) => {
-                    log::error!(
-                        "Failed to fetch network info from robot {}: {:?}",
-                        request.robot_id,
-                        err
-                    );
-                    Err(GenericResponse::BadRequest(PlainText(
-                        "failed to fetch network info".to_string(),
-                    )))
-                }
-            }
-        } else {
-            log::info!(
-                "No connection found for robot_id: {}",
-                request.robot_id
-            );
-            Err(GenericResponse::BadRequest(PlainText(
-                "robot not connected".to_string(),
-            )))
-        }
+        let user = auth.require(UserRole::Operator)?;
+        check_robot_scope(user, &request.robot_id).await?;
+        let conn = connection_of(&request.robot_id)?;
+        let info = conn
+            .send_instruction(Instruction::FetchNetwork {}, &Actor::from(user))
+            .await
+            .map_err(|err| {
+                log::error!(
+                    "Failed to fetch network info from robot {}: {:?}",
+                    request.robot_id,
+                    err
+                );
+                instruction_failed(&request.robot_id, err)
+            })?;
+        with_database(|db| db.write_network_info(&request.robot_id, &info))?
+            .await
+            .map_err(|err| {
+                GenericResponse::new(
+                    ErrorCode::InternalError,
+                    format!("Failed to write network info: {err}"),
+                )
+            })?;
+        Ok(Json(fetch_network::FetchNetworkResponse {}))
     }
 
+    /// Refreshes the network info of every matching robot and reports the
+    /// outcome per robot, including failures to store the info.
     #[oai(path = "/action/refresh_network_all", method = "post")]
     async fn refresh_network_all(
         &self,
-    ) -> ApiResult<fetch_network::FetchNetworkResponse> {
-        for conn in CONNECTIONS.iter() {
-            let robot_id = conn.key().clone();
-            let net_info = conn
-                .value()
-                .send_instruction(Instruction::FetchNetwork {})
-                .await;
-            match net_info {
-                Ok(info) => {
-                    with_database(|db| {
-                        db.write_network_info(&robot_id, &info)
-                    })?
-                    .await
-                    .map_err(|err| {
-                        GenericResponse::InternalError(PlainText(format!(
-                            "Failed to write network info for robot {robot_id}: {err}"
-                        )))
-                    })?;
-                }
-                Err(err) => {
-                    log::error!(
-                        "Failed to fetch network info from robot {robot_id}: {err:?}"
-                    );
-                }
-            }
-        }
-        Ok(Json(fetch_network::FetchNetworkResponse {}))
+        auth: Auth,
+        Query(role): Query<Vec<RobotRole>>,
+        Query(team_color): Query<Option<TeamColor>>,
+        Query(tag): Query<Vec<String>>,
+    ) -> ApiResult<bulk::BulkActionResponse> {
+        let user = auth.require(UserRole::Operator)?;
+        let filter = RobotFilter {
+            roles: role,
+            team_color,
+            tags: tag,
+        };
+        let instruction = bulk::BulkInstruction::FetchNetwork(
+            bulk::FetchNetworkInstruction {},
+        );
+        let targets = fan_out::resolve_targets(
+            &bulk::RobotSelector::from_filter(filter),
+            user,
+        )
+        .await?;
+        Ok(Json(
+            fan_out::fan_out(
+                targets,
+                &instruction,
+                fan_out::DEFAULT_CONCURRENCY,
+                instruction.default_timeout(),
+                &Actor::from(user),
+                None,
+            )
+            .await,
+        ))
     }
 
     #[oai(path = "/action/update_binary", method = "post")]
     async fn update_binary(
         &self,
+        auth: Auth,
         request: Json<update_binary::UpdateBinaryRequest>,
-    ) -> ApiResult<update_binary::UpdateBinaryResponse> {
-        if let Some(conn) = CONNECTIONS.get(&request.robot_id) {
-            let result = timeout(
-                UPDATE_BINARY_TIMEOUT,
-                conn.value().send_instruction::<serde_json::Value>(
-                    Instruction::UpdateBinary {
+        /// Return a job ID immediately instead of waiting for the robot.
+        #[oai(name = "async")]
+        Query(run_async): Query<Option<bool>>,
+    ) -> ActionResult<update_binary::UpdateBinaryResponse> {
+        let user = auth.require(UserRole::Operator)?;
+        check_robot_scope(user, &request.robot_id).await?;
+        let conn = connection_of(&request.robot_id)?;
+        if run_async.unwrap_or(false) {
+            let job_id = fan_out::spawn_job(
+                vec![fan_out::Target {
+                    robot_id: request.robot_id.clone(),
+                    connection: Some(conn),
+                }],
+                bulk::BulkInstruction::UpdateBinary(
+                    bulk::UpdateBinaryInstruction {
                         artifact_url: request.artifact_url.clone(),
                     },
                 ),
+                1,
+                UPDATE_BINARY_TIMEOUT,
+                Actor::from(user),
             )
-            .await;
-            match result {
-                Ok(Ok(info)) => Ok(Json(parse_update_binary_response(&info))),
-                Ok(Err(err)) => {
-                    log::error!(
-                        "Failed to update binary on robot {}: {:?}",
-                        request.robot_id,
-                        err
-                    );
-                    Ok(Json(update_binary_instruction_failure_response(err)))
-                }
-                Err(_) => {
-                    log::error!(
-                        "Timed out updating binary on robot {} after {} seconds",
-                        request.robot_id,
-                        UPDATE_BINARY_TIMEOUT.as_secs()
-                    );
-                    Ok(Json(update_binary_timeout_response()))
+            .await?;
+            return Ok(ActionResponse::Accepted(Json(JobAccepted { job_id })));
+        }
+        let result = timeout(
+            UPDATE_BINARY_TIMEOUT,
+            conn.send_instruction::<serde_json::Value>(
+                Instruction::UpdateBinary {
+                    artifact_url: request.artifact_url.clone(),
+                },
+                &Actor::from(user),
+            ),
+        )
+        .await;
+        match result {
+            Ok(Ok(info)) => {
+                let response = parse_update_binary_response(&info);
+                if response.status == UPDATE_BINARY_SUCCESS {
+                    Ok(ActionResponse::Completed(Json(response)))
+                } else {
+                    Err(GenericResponse::bot_error(
+                        &request.robot_id,
+                        response.message,
+                    ))
                 }
             }
-        } else {
-            log::info!(
-                "No connection found for robot_id: {}",
-                request.robot_id
-            );
-            Err(GenericResponse::BadRequest(PlainText(
-                "robot not connected".to_string(),
-            )))
+            Ok(Err(err)) => {
+                log::error!(
+                    "Failed to update binary on robot {}: {:?}",
+                    request.robot_id,
+                    err
+                );
+                Err(instruction_failed(&request.robot_id, err))
+            }
+            Err(_) => {
+                log::error!(
+                    "Timed out updating binary on robot {} after {} seconds",
+                    request.robot_id,
+                    UPDATE_BINARY_TIMEOUT.as_secs()
+                );
+                Err(GenericResponse::instruction_timeout(
+                    &request.robot_id,
+                    UPDATE_BINARY_TIMEOUT,
+                ))
+            }
         }
     }
 
     #[oai(path = "/action/update_binary_all", method = "post")]
     async fn update_binary_all(
         &self,
+        auth: Auth,
         request: Json<update_binary::UpdateBinaryAllRequest>,
-    ) -> ApiResult<update_binary::UpdateBinaryAllResponse> {
-        let update_futures = CONNECTIONS.iter().map(|conn| {
-            let robot_id = conn.key().clone();
-            let connection = conn.value().clone();
-            let artifact_url = request.artifact_url.clone();
-
-            async move {
-                let result = timeout(
-                    UPDATE_BINARY_TIMEOUT,
-                    connection.send_instruction::<serde_json::Value>(
-                        Instruction::UpdateBinary { artifact_url },
-                    ),
-                )
-                .await;
-
-                (robot_id, result)
-            }
-        });
-
-        let mut results = Vec::new();
-        let mut has_failure = false;
-
-        for (robot_id, result) in join_all(update_futures).await {
-            match result {
-                Ok(Ok(info)) => {
-                    let response = parse_update_binary_response(&info);
-                    if response.status != "post_update" {
-                        has_failure = true;
-                    }
-                    results.push(update_binary::RobotUpdateResult {
-                        robot_id,
-                        status: response.status,
-                        message: response.message,
-                    });
-                }
-                Ok(Err(err)) => {
-                    has_failure = true;
-                    log::error!(
-                        "Failed to update binary on robot {robot_id}: {err:?}"
-                    );
-                    let response =
-                        update_binary_instruction_failure_response(err);
-                    results.push(update_binary::RobotUpdateResult {
-                        robot_id,
-                        status: response.status,
-                        message: response.message,
-                    });
-                }
-                Err(_) => {
-                    has_failure = true;
-                    log::error!(
-                        "Timed out updating binary on robot {} after {} seconds",
-                        robot_id,
-                        UPDATE_BINARY_TIMEOUT.as_secs()
-                    );
-                    let response = update_binary_timeout_response();
-                    results.push(update_binary::RobotUpdateResult {
-                        robot_id,
-                        status: response.status,
-                        message: response.message,
-                    });
-                }
-            }
+        Query(role): Query<Vec<RobotRole>>,
+        Query(team_color): Query<Option<TeamColor>>,
+        Query(tag): Query<Vec<String>>,
+        /// Return a job ID immediately instead of waiting for the robots.
+        #[oai(name = "async")]
+        Query(run_async): Query<Option<bool>>,
+    ) -> ActionResult<update_binary::UpdateBinaryAllResponse> {
+        let user = auth.require(UserRole::Operator)?;
+        let filter = RobotFilter {
+            roles: role,
+            team_color,
+            tags: tag,
+        };
+        let instruction = bulk::BulkInstruction::UpdateBinary(
+            bulk::UpdateBinaryInstruction {
+                artifact_url: request.artifact_url.clone(),
+            },
+        );
+        let targets = fan_out::resolve_targets(
+            &bulk::RobotSelector::from_filter(filter),
+            user,
+        )
+        .await?;
+        let time_limit = instruction.default_timeout();
+        if run_async.unwrap_or(false) {
+            let job_id = fan_out::spawn_job(
+                targets,
+                instruction,
+                fan_out::DEFAULT_CONCURRENCY,
+                time_limit,
+                Actor::from(user),
+            )
+            .await?;
+            return Ok(ActionResponse::Accepted(Json(JobAccepted { job_id })));
         }
+        let response = fan_out::fan_out(
+            targets,
+            &instruction,
+            fan_out::DEFAULT_CONCURRENCY,
+            time_limit,
+            &Actor::from(user),
+            None,
+        )
+        .await;
 
-        let overall_status = if has_failure { "partial_failure" } else { "ok" };
+        Ok(ActionResponse::Completed(Json(
+            update_binary::UpdateBinaryAllResponse {
+                status: response.status,
+                results: response.results,
+            },
+        )))
+    }
 
-        Ok(Json(update_binary::UpdateBinaryAllResponse {
-            status: overall_status.to_string(),
-            results,
-        }))
+    /// Runs an instruction on every robot matched by a selector and
+    /// reports the outcome per robot.
+    #[oai(path = "/action/bulk", method = "post")]
+    async fn bulk(
+        &self,
+        auth: Auth,
+        request: Json<bulk::BulkActionRequest>,
+        /// Return a job ID immediately instead of waiting for the robots.
+        #[oai(name = "async")]
+        Query(run_async): Query<Option<bool>>,
+    ) -> ActionResult<bulk::BulkActionResponse> {
+        let user = auth.require(UserRole::Operator)?;
+        let targets = fan_out::resolve_targets(&request.selector, user).await?;
+        let concurrency = request
+            .concurrency
+            .map_or(fan_out::DEFAULT_CONCURRENCY, |concurrency| {
+                concurrency as usize
+            });
+        if request.timeout_secs == Some(0) {
+            return Err(GenericResponse::validation_failed(
+                "timeout_secs must be at least 1",
+            ));
+        }
+        let time_limit = request.timeout_secs.map_or_else(
+            || request.instruction.default_timeout(),
+            Duration::from_secs,
+        );
+        if run_async.unwrap_or(false) {
+            let job_id = fan_out::spawn_job(
+                targets,
+                request.0.instruction,
+                concurrency,
+                time_limit,
+                Actor::from(user),
+            )
+            .await?;
+            return Ok(ActionResponse::Accepted(Json(JobAccepted { job_id })));
+        }
+        Ok(ActionResponse::Completed(Json(
+            fan_out::fan_out(
+                targets,
+                &request.instruction,
+                concurrency,
+                time_limit,
+                &Actor::from(user),
+                None,
+            )
+            .await,
+        )))
     }
 }
@@ -0,0 +1,105 @@
+use poem_openapi::{Object, Union};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::action::update_binary::RobotUpdateResult,
+    database::robot::{RobotFilter, RobotRole, TeamColor},
+};
+
+fn default_online_only() -> bool {
+    true
+}
+
+/// Selects the robots a bulk action runs on. All criteria must match;
+/// omitted criteria match every robot.
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct RobotSelector {
+    /// Explicit robots to target. When omitted, every approved robot is a
+    /// candidate.
+    pub robot_uuids: Option<Vec<String>>,
+    /// Robots with any of these roles.
+    #[oai(default)]
+    #[serde(default)]
+    pub roles: Vec<RobotRole>,
+    pub team_color: Option<TeamColor>,
+    /// Robots carrying all of these tags.
+    #[oai(default)]
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Skip robots that are not connected instead of reporting them as
+    /// failed. Defaults to `true`.
+    #[oai(default = "default_online_only")]
+    #[serde(default = "default_online_only")]
+    pub online_only: bool,
+}
+
+impl Default for RobotSelector {
+    fn default() -> Self {
+        Self {
+            robot_uuids: None,
+            roles: Vec::new(),
+            team_color: None,
+            tags: Vec::new(),
+            online_only: default_online_only(),
+        }
+    }
+}
+
+impl RobotSelector {
+    pub fn from_filter(filter: RobotFilter) -> Self {
+        Self {
+            roles: filter.roles,
+            team_color: filter.team_color,
+            tags: filter.tags,
+            ..Self::default()
+        }
+    }
+
+    pub fn filter(&self) -> RobotFilter {
+        RobotFilter {
+            roles: self.roles.clone(),
+            team_color: self.team_color,
+            tags: self.tags.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct FetchNetworkInstruction {}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct UpdateBinaryInstruction {
+    pub artifact_url: String,
+}
+
+/// Instructions that can be sent to many robots at once.
+#[derive(Debug, Clone, Serialize, Deserialize, Union)]
+#[serde(tag = "instruction", rename_all = "snake_case")]
+#[oai(discriminator_name = "instruction")]
+pub enum BulkInstruction {
+    #[oai(mapping = "fetch_network")]
+    FetchNetwork(FetchNetworkInstruction),
+    #[oai(mapping = "update_binary")]
+    UpdateBinary(UpdateBinaryInstruction),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct BulkActionRequest {
+    #[oai(default)]
+    #[serde(default)]
+    pub selector: RobotSelector,
+    pub instruction: BulkInstruction,
+    /// Maximum number of robots instructed at the same time.
+    pub concurrency: Option<u32>,
+    /// Time each robot has to complete the instruction, at least one
+    /// second. Defaults to a per-instruction value.
+    pub timeout_secs: Option<u64>,
+}
+
+/// Per-robot results of a bulk action. `status` is `ok` if the instruction
+/// succeeded on every targeted robot and `partial_failure` otherwise.
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct BulkActionResponse {
+    pub status: String,
+    pub results: Vec<RobotUpdateResult>,
+}
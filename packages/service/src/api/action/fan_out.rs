@@ -0,0 +1,428 @@
+use std::{collections::BTreeSet, sync::Arc, time::Duration};
+
+use futures_util::{StreamExt, stream};
+use tokio::{select, time::timeout};
+use uuid::Uuid;
+
+use crate::{
+    api::{
+        GenericResponse,
+        action::{
+            UPDATE_BINARY_SUCCESS, UPDATE_BINARY_TIMEOUT,
+            bulk::{BulkActionResponse, BulkInstruction, RobotSelector},
+            parse_update_binary_response,
+            update_binary::RobotUpdateResult,
+        },
+        auth::scope::out_of_scope,
+    },
+    database::{
+        Database,
+        audit::Actor,
+        job::{
+            ROBOT_STATUS_CANCELLED, ROBOT_STATUS_LOCKED, ROBOT_STATUS_RUNNING,
+        },
+        network::NetworkInfo,
+        robot::EnrollmentStatus,
+        user::User,
+        with_database,
+    },
+    service::{
+        CONNECTIONS,
+        connection::{Connection, InstructionError},
+        instructions::Instruction,
+        jobs::JobContext,
+    },
+};
+
+pub const DEFAULT_CONCURRENCY: usize = 8;
+const FETCH_NETWORK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A robot selected for a bulk action, with its live connection if it is
+/// online.
+pub struct Target {
+    pub robot_id: String,
+    pub connection: Option<Arc<Connection>>,
+}
+
+struct Outcome {
+    success: bool,
+    status: String,
+    message: String,
+}
+
+impl Outcome {
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            status: "error".to_string(),
+            message: message.into(),
+        }
+    }
+
+    fn cancelled(message: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            status: ROBOT_STATUS_CANCELLED.to_string(),
+            message: message.into(),
+        }
+    }
+
+    fn locked(message: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            status: ROBOT_STATUS_LOCKED.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl BulkInstruction {
+    /// Name recorded as the kind of jobs running this instruction.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BulkInstruction::FetchNetwork(_) => "fetch_network",
+            BulkInstruction::UpdateBinary(_) => "update_binary",
+        }
+    }
+
+    pub fn default_timeout(&self) -> Duration {
+        match self {
+            BulkInstruction::FetchNetwork(_) => FETCH_NETWORK_TIMEOUT,
+            BulkInstruction::UpdateBinary(_) => UPDATE_BINARY_TIMEOUT,
+        }
+    }
+
+    async fn run(
+        &self,
+        connection: &Connection,
+        actor: &Actor,
+    ) -> anyhow::Result<Outcome> {
+        match self {
+            BulkInstruction::FetchNetwork(_) => {
+                let info: NetworkInfo = connection
+                    .send_instruction(Instruction::FetchNetwork {}, actor)
+                    .await?;
+                let stored: anyhow::Result<()> = async {
+                    with_database(|db| {
+                        db.write_network_info(&connection.robot_id, &info)
+                    })?
+                    .await?;
+                    Ok(())
+                }
+                .await;
+                if let Err(err) = stored {
+                    log::error!(
+                        "Failed to store network info of robot {}: {err:?}",
+                        connection.robot_id
+                    );
+                    return Ok(Outcome::error(format!(
+                        "failed to store network info: {err:#}"
+                    )));
+                }
+                Ok(Outcome {
+                    success: true,
+                    status: "ok".to_string(),
+                    message: "network info refreshed".to_string(),
+                })
+            }
+            BulkInstruction::UpdateBinary(instruction) => {
+                let info = match connection
+                    .send_instruction::<serde_json::Value>(
+                        Instruction::UpdateBinary {
+                            artifact_url: instruction.artifact_url.clone(),
+                        },
+                        actor,
+                    )
+                    .await
+                {
+                    Ok(info) => info,
+                    Err(InstructionError::Robot(err)) => {
+                        return Ok(Outcome::error(err.message));
+                    }
+                    Err(err) => return Err(err.into()),
+                };
+                let response = parse_update_binary_response(&info);
+                Ok(Outcome {
+                    success: response.status == UPDATE_BINARY_SUCCESS,
+                    status: response.status,
+                    message: response.message,
+                })
+            }
+        }
+    }
+}
+
+/// Resolves a selector into the robots to instruct, ordered by UUID unless
+/// explicit UUIDs are given. Robots outside the scope of `user` are left
+/// out, or rejected when they were requested explicitly.
+pub async fn resolve_targets(
+    selector: &RobotSelector,
+    user: &User,
+) -> Result<Vec<Target>, GenericResponse> {
+    let filter = selector.filter();
+    let profiles = with_database(|db| db.filter_robots(&filter))?.await?;
+    let matching: BTreeSet<String> = profiles
+        .iter()
+        .map(|profile| profile.ident.uuid.clone())
+        .collect();
+    let approved: BTreeSet<String> = profiles
+        .iter()
+        .filter(|profile| profile.enrollment == EnrollmentStatus::Approved)
+        .map(|profile| profile.ident.uuid.clone())
+        .collect();
+    let in_scope: BTreeSet<String> = profiles
+        .into_iter()
+        .filter(|profile| user.can_act_on(&profile.tags))
+        .map(|profile| profile.ident.uuid)
+        .collect();
+
+    let candidates: Vec<String> =
+        if let Some(robot_uuids) = &selector.robot_uuids {
+            let archived =
+                with_database(Database::get_archived_robot_profiles)?.await?;
+            if let Some(robot) = archived
+                .iter()
+                .find(|robot| robot_uuids.contains(&robot.ident.uuid))
+            {
+                return Err(GenericResponse::conflict(format!(
+                    "Robot {} is archived",
+                    robot.ident.uuid
+                )));
+            }
+            let mut seen = BTreeSet::new();
+            let candidates: Vec<String> = robot_uuids
+                .iter()
+                .filter(|uuid| seen.insert(uuid.as_str()))
+                .filter(|uuid| filter.is_empty() || matching.contains(*uuid))
+                .cloned()
+                .collect();
+            if user.is_scoped()
+                && let Some(uuid) =
+                    candidates.iter().find(|uuid| !in_scope.contains(*uuid))
+            {
+                return Err(out_of_scope(uuid));
+            }
+            candidates
+        } else {
+            // Only approved robots can be instructed, so the others are only
+            // reported when requested explicitly.
+            let candidates = if user.is_scoped() { in_scope } else { matching };
+            candidates
+                .into_iter()
+                .filter(|uuid| approved.contains(uuid))
+                .collect()
+        };
+
+    Ok(candidates
+        .into_iter()
+        .map(|robot_id| Target {
+            connection: CONNECTIONS
+                .get(&robot_id)
+                .map(|conn| conn.value().clone()),
+            robot_id,
+        })
+        .filter(|target| !selector.online_only || target.connection.is_some())
+        .collect())
+}
+
+/// Runs `instruction` on every target on behalf of `actor`, at most
+/// `concurrency` at a time, allowing each robot `time_limit` to complete
+/// it. When running as part of `job`, per-robot progress is recorded and
+/// robots still in flight are stopped once the job is cancelled.
+pub async fn fan_out(
+    targets: Vec<Target>,
+    instruction: &BulkInstruction,
+    concurrency: usize,
+    time_limit: Duration,
+    actor: &Actor,
+    job: Option<&JobContext>,
+) -> BulkActionResponse {
+    let outcomes: Vec<(String, Outcome)> = stream::iter(targets)
+        .map(|target| async move {
+            let outcome = match job {
+                Some(job) => {
+                    run_as_job(&target, instruction, time_limit, actor, job)
+                        .await
+                }
+                None => {
+                    run_on_target(&target, instruction, time_limit, actor).await
+                }
+            };
+            (target.robot_id, outcome)
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+
+    let has_failure = outcomes.iter().any(|(_, outcome)| !outcome.success);
+    BulkActionResponse {
+        status: if has_failure { "partial_failure" } else { "ok" }.to_string(),
+        results: outcomes
+            .into_iter()
+            .map(|(robot_id, outcome)| RobotUpdateResult {
+                robot_id,
+                status: outcome.status,
+                message: outcome.message,
+            })
+            .collect(),
+    }
+}
+
+/// Records a job running `instruction` on `targets` and executes it in the
+/// background. Returns the job ID.
+pub async fn spawn_job(
+    targets: Vec<Target>,
+    instruction: BulkInstruction,
+    concurrency: usize,
+    time_limit: Duration,
+    actor: Actor,
+) -> anyhow::Result<String> {
+    let id = Uuid::new_v4().to_string();
+    let robot_uuids: Vec<String> = targets
+        .iter()
+        .map(|target| target.robot_id.clone())
+        .collect();
+    with_database(|db| db.create_job(&id, instruction.kind(), &robot_uuids))?
+        .await?;
+    let job = JobContext::register(id.clone());
+
+    tokio::spawn(async move {
+        let started = async {
+            with_database(|db| db.start_job(&job.id))?.await?;
+            anyhow::Ok(())
+        };
+        if let Err(err) = started.await {
+            log::error!("Failed to start job {}: {err:?}", job.id);
+            return;
+        }
+        let response = fan_out(
+            targets,
+            &instruction,
+            concurrency,
+            time_limit,
+            &actor,
+            Some(&job),
+        )
+        .await;
+        if job.is_cancelled() {
+            return;
+        }
+        let finished = async {
+            with_database(|db| db.finish_job(&job.id, &response.status))?
+                .await?;
+            anyhow::Ok(())
+        };
+        if let Err(err) = finished.await {
+            log::error!(
+                "Failed to record completion of job {}: {err:?}",
+                job.id
+            );
+        }
+    });
+    Ok(id)
+}
+
+async fn run_on_target(
+    target: &Target,
+    instruction: &BulkInstruction,
+    time_limit: Duration,
+    actor: &Actor,
+) -> Outcome {
+    let Some(connection) = &target.connection else {
+        return Outcome::error("robot not connected");
+    };
+    match timeout(time_limit, instruction.run(connection, actor)).await {
+        Ok(Ok(outcome)) => outcome,
+        Ok(Err(err))
+            if matches!(
+                err.downcast_ref(),
+                Some(InstructionError::Cancelled)
+            ) =>
+        {
+            Outcome::cancelled("session cancelled")
+        }
+        // Another operator's instruction is running on the robot; that is
+        // not a failure of the service.
+        Ok(Err(err))
+            if matches!(
+                err.downcast_ref(),
+                Some(InstructionError::Locked(_))
+            ) =>
+        {
+            log::info!(
+                "Skipped bulk instruction on robot {}: {err}",
+                target.robot_id
+            );
+            Outcome::locked(err.to_string())
+        }
+        Ok(Err(err)) => {
+            log::error!(
+                "Bulk instruction failed on robot {}: {err:?}",
+                target.robot_id
+            );
+            Outcome::error(format!("instruction failed: {err}"))
+        }
+        Err(_) => {
+            log::error!(
+                "Bulk instruction timed out on robot {} after {} seconds",
+                target.robot_id,
+                time_limit.as_secs()
+            );
+            Outcome::error(format!(
+                "instruction timed out after {} seconds",
+                time_limit.as_secs()
+            ))
+        }
+    }
+}
+
+async fn run_as_job(
+    target: &Target,
+    instruction: &BulkInstruction,
+    time_limit: Duration,
+    actor: &Actor,
+    job: &JobContext,
+) -> Outcome {
+    // Cancelling the job already marked robots not yet instructed.
+    if job.is_cancelled() {
+        return Outcome::cancelled("job cancelled");
+    }
+    record_robot_status(job, &target.robot_id, ROBOT_STATUS_RUNNING, None)
+        .await;
+    // Dropping the instruction on cancellation closes its session.
+    let outcome = select! {
+        outcome = run_on_target(target, instruction, time_limit, actor) => {
+            outcome
+        }
+        () = job.cancelled() => return Outcome::cancelled("job cancelled"),
+    };
+    record_robot_status(
+        job,
+        &target.robot_id,
+        &outcome.status,
+        Some(&outcome.message),
+    )
+    .await;
+    outcome
+}
+
+async fn record_robot_status(
+    job: &JobContext,
+    robot_id: &str,
+    status: &str,
+    message: Option<&str>,
+) {
+    let recorded = async {
+        with_database(|db| {
+            db.set_job_robot_status(&job.id, robot_id, status, message)
+        })?
+        .await?;
+        anyhow::Ok(())
+    };
+    if let Err(err) = recorded.await {
+        log::warn!(
+            "Failed to record status of robot {robot_id} in job {}: {err:?}",
+            job.id
+        );
+    }
+}
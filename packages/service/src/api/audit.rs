@@ -0,0 +1,120 @@
+use chrono::{DateTime, Utc};
+use poem_openapi::{
+    ApiResponse, Object, OpenApi,
+    param::Query,
+    payload::{Json, PlainText},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{ApiResult, GenericResponse, auth::Auth},
+    database::{
+        audit::{AuditCategory, AuditEntry, AuditFilter, AuditOutcome},
+        user::UserRole,
+        with_database,
+    },
+};
+
+pub mod middleware;
+
+pub const DEFAULT_LIMIT: u32 = 100;
+pub const MAX_LIMIT: u32 = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct AuditLogResponse {
+    /// Number of entries matching the filters, before pagination.
+    pub total: i64,
+    pub offset: u32,
+    pub limit: u32,
+    /// Newest first.
+    pub entries: Vec<AuditEntry>,
+}
+
+#[derive(ApiResponse)]
+pub enum AuditExportResponse {
+    /// One JSON object per line, newest first.
+    #[oai(status = 200, content_type = "application/x-ndjson")]
+    Ok(PlainText<String>),
+}
+
+/// Read access to the record of mutating API calls and instructions sent
+/// to robots.
+pub struct AuditApi;
+
+#[OpenApi]
+impl AuditApi {
+    #[oai(path = "/audit", method = "get")]
+    #[allow(clippy::too_many_arguments)]
+    async fn list_audit_entries(
+        &self,
+        auth: Auth,
+        Query(category): Query<Option<AuditCategory>>,
+        Query(actor): Query<Option<String>>,
+        Query(robot_uuid): Query<Option<String>>,
+        Query(action): Query<Option<String>>,
+        Query(outcome): Query<Option<AuditOutcome>>,
+        Query(from): Query<Option<DateTime<Utc>>>,
+        Query(to): Query<Option<DateTime<Utc>>>,
+        Query(offset): Query<Option<u32>>,
+        Query(limit): Query<Option<u32>>,
+    ) -> ApiResult<AuditLogResponse> {
+        auth.require(UserRole::Admin)?;
+        let filter = AuditFilter {
+            category,
+            actor,
+            robot_uuid,
+            action,
+            outcome,
+            from,
+            to,
+        };
+        let offset = offset.unwrap_or(0);
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+        let total =
+            with_database(|db| db.count_audit_entries(&filter))?.await?;
+        let entries = with_database(|db| {
+            db.list_audit_entries(&filter, i64::from(limit), i64::from(offset))
+        })?
+        .await?;
+        Ok(Json(AuditLogResponse {
+            total,
+            offset,
+            limit,
+            entries,
+        }))
+    }
+
+    /// Every entry matching the filters as JSON Lines.
+    #[oai(path = "/audit/export", method = "get")]
+    #[allow(clippy::too_many_arguments)]
+    async fn export_audit_entries(
+        &self,
+        auth: Auth,
+        Query(category): Query<Option<AuditCategory>>,
+        Query(actor): Query<Option<String>>,
+        Query(robot_uuid): Query<Option<String>>,
+        Query(action): Query<Option<String>>,
+        Query(outcome): Query<Option<AuditOutcome>>,
+        Query(from): Query<Option<DateTime<Utc>>>,
+        Query(to): Query<Option<DateTime<Utc>>>,
+    ) -> Result<AuditExportResponse, GenericResponse> {
+        auth.require(UserRole::Admin)?;
+        let filter = AuditFilter {
+            category,
+            actor,
+            robot_uuid,
+            action,
+            outcome,
+            from,
+            to,
+        };
+        let entries =
+            with_database(|db| db.list_audit_entries(&filter, -1, 0))?.await?;
+        let mut lines = String::new();
+        for entry in &entries {
+            lines.push_str(&serde_json::to_string(entry)?);
+            lines.push('\n');
+        }
+        Ok(AuditExportResponse::Ok(PlainText(lines)))
+    }
+}
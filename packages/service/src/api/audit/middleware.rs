@@ -0,0 +1,172 @@
+use std::{
+    sync::{Arc, OnceLock},
+    time::Instant,
+};
+
+use poem::{
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+    http::{Method, StatusCode},
+};
+use serde_json::Value;
+
+use crate::{
+    api::error::Problem,
+    database::{
+        audit::{Actor, AuditCategory, AuditOutcome, NewAuditEntry},
+        user::User,
+    },
+    service::audit,
+};
+
+/// Longest error message kept from a failed response.
+const MAX_MESSAGE_LEN: usize = 500;
+
+/// Slot through which the authentication checkers tell [`AuditLog`] who
+/// is making a request.
+#[derive(Debug, Clone, Default)]
+pub struct RequestActor(Arc<OnceLock<Actor>>);
+
+impl RequestActor {
+    pub fn set(req: &Request, user: &User) {
+        if let Some(slot) = req.extensions().get::<RequestActor>() {
+            let _ = slot.0.set(Actor::from(user));
+        }
+    }
+}
+
+/// Records every request that is not a plain read in the audit log.
+pub struct AuditLog;
+
+impl<E: Endpoint> Middleware<E> for AuditLog {
+    type Output = AuditLogEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        AuditLogEndpoint(ep)
+    }
+}
+
+pub struct AuditLogEndpoint<E>(E);
+
+fn outcome_of(status: StatusCode) -> AuditOutcome {
+    if status.is_success() {
+        AuditOutcome::Success
+    } else if status == StatusCode::UNAUTHORIZED
+        || status == StatusCode::FORBIDDEN
+    {
+        AuditOutcome::Denied
+    } else {
+        AuditOutcome::Failure
+    }
+}
+
+/// Returns the robot named by a `/robots/:uuid/...` path, for requests
+/// that do not name it in their body.
+fn robot_uuid_from_path(path: &str) -> Option<String> {
+    let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+    segments.find(|segment| *segment == "robots")?;
+    segments.next().map(ToString::to_string)
+}
+
+fn truncate_message(message: &str) -> String {
+    match message.char_indices().nth(MAX_MESSAGE_LEN) {
+        Some((end, _)) => format!("{}...", &message[..end]),
+        None => message.to_string(),
+    }
+}
+
+impl<E: Endpoint> Endpoint for AuditLogEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS)
+        {
+            return self.0.call(req).await.map(IntoResponse::into_response);
+        }
+
+        let action = format!("{} {}", req.method(), req.uri().path());
+        let body = req.take_body().into_bytes().await?;
+        let parameters = serde_json::from_slice::<Value>(&body)
+            .ok()
+            .map(audit::redact);
+        req.set_body(body);
+        let robot_uuid = parameters
+            .as_ref()
+            .and_then(|parameters| {
+                ["robot_uuid", "robot_id", "uuid"].iter().find_map(|key| {
+                    parameters.get(key)?.as_str().map(ToString::to_string)
+                })
+            })
+            .or_else(|| robot_uuid_from_path(req.uri().path()));
+        let parameters = parameters.map(audit::limit_size);
+        let actor = RequestActor::default();
+        req.extensions_mut().insert(actor.clone());
+
+        let started = Instant::now();
+        let result = self.0.call(req).await.map(IntoResponse::into_response);
+        let duration = started.elapsed();
+
+        let (outcome, message, result) = match result {
+            Ok(mut resp) if !resp.status().is_success() => {
+                let body = resp.take_body().into_bytes().await?;
+                // Problem details carry the message in `detail`.
+                let message = serde_json::from_slice::<Problem>(&body)
+                    .map_or_else(
+                        |_| String::from_utf8_lossy(&body).into_owned(),
+                        |problem| problem.detail,
+                    );
+                let message = truncate_message(&message);
+                resp.set_body(body);
+                (outcome_of(resp.status()), Some(message), Ok(resp))
+            }
+            Ok(resp) => (AuditOutcome::Success, None, Ok(resp)),
+            Err(err) => (
+                outcome_of(err.status()),
+                Some(truncate_message(&err.to_string())),
+                Err(err),
+            ),
+        };
+        audit::record(NewAuditEntry {
+            category: AuditCategory::Api,
+            actor: actor.0.get().cloned().unwrap_or_else(Actor::anonymous),
+            action,
+            robot_uuid,
+            parameters,
+            outcome,
+            message,
+            duration,
+        });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_robot_in_path() {
+        assert_eq!(
+            robot_uuid_from_path("/enrollment/robots/abc/approve").as_deref(),
+            Some("abc")
+        );
+        assert_eq!(
+            robot_uuid_from_path("/robots/abc/sessions/1/cancel").as_deref(),
+            Some("abc")
+        );
+    }
+
+    #[test]
+    fn ignores_paths_without_robot() {
+        assert_eq!(robot_uuid_from_path("/jobs/abc/cancel"), None);
+        assert_eq!(robot_uuid_from_path("/enrollment/robots"), None);
+    }
+
+    #[test]
+    fn truncates_long_messages() {
+        assert_eq!(truncate_message("short"), "short");
+        let message = "é".repeat(MAX_MESSAGE_LEN + 1);
+        let truncated = truncate_message(&message);
+        assert!(truncated.ends_with("..."));
+        assert_eq!(truncated.chars().count(), MAX_MESSAGE_LEN + 3);
+    }
+}
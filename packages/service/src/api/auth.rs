@@ -0,0 +1,465 @@
+//! Operator authentication for the HTTP API.
+//!
+//! Operators log in with a password to obtain a session cookie, or use
+//! bearer API tokens from scripts. Every endpoint except those used by
+//! robots themselves requires one of the two through [`Auth`].
+
+use std::net::SocketAddr;
+
+use chrono::{TimeDelta, Utc};
+use poem::Request;
+use poem_openapi::{
+    ApiResponse, OpenApi, SecurityScheme,
+    auth::{ApiKey, Bearer},
+    param::Path,
+    payload::Json,
+};
+
+use crate::{
+    api::{
+        ApiResult, GenericResponse,
+        audit::middleware::RequestActor,
+        error::{ErrorCode, Problem},
+    },
+    constant::env::{
+        DEFAULT_AUTH_COOKIE_SECURE, DEFAULT_AUTH_SESSION_TTL_SECS,
+        ENV_NAME_AUTH_BOOTSTRAP_PASSWORD, ENV_NAME_AUTH_BOOTSTRAP_USERNAME,
+        ENV_NAME_AUTH_COOKIE_SECURE, ENV_NAME_AUTH_SESSION_TTL_SECS,
+    },
+    database::{
+        Database,
+        robot::normalize_tags,
+        user::{ApiTokenInfo, User, UserRole},
+        with_database,
+    },
+    env::parse_env_or,
+    utils::credentials::{
+        MIN_PASSWORD_LEN, generate_token, hash_password, hash_token,
+        verify_password,
+    },
+};
+
+pub mod objects;
+pub mod scope;
+pub mod throttle;
+
+pub const SESSION_COOKIE: &str = "rmcs_session";
+const SESSION_TOKEN_PREFIX: &str = "rmcss_";
+const API_TOKEN_PREFIX: &str = "rmcst_";
+
+async fn check_session(req: &Request, key: ApiKey) -> Option<User> {
+    let token_hash = hash_token(&key.key);
+    let user = match with_database(|db| db.get_session_user(&token_hash)) {
+        Ok(lookup) => lookup.await.unwrap_or_else(|err| {
+            log::error!("Failed to look up login session: {err:?}");
+            None
+        }),
+        Err(err) => {
+            log::error!("Failed to look up login session: {err:?}");
+            None
+        }
+    };
+    if let Some(user) = &user {
+        RequestActor::set(req, user);
+    }
+    user
+}
+
+async fn check_token(req: &Request, bearer: Bearer) -> Option<User> {
+    let token_hash = hash_token(&bearer.token);
+    let user = match with_database(|db| db.use_api_token(&token_hash)) {
+        Ok(lookup) => lookup.await.unwrap_or_else(|err| {
+            log::error!("Failed to look up API token: {err:?}");
+            None
+        }),
+        Err(err) => {
+            log::error!("Failed to look up API token: {err:?}");
+            None
+        }
+    };
+    if let Some(user) = &user {
+        RequestActor::set(req, user);
+    }
+    user
+}
+
+/// Session cookie set by `/auth/login`.
+#[derive(SecurityScheme)]
+#[oai(
+    ty = "api_key",
+    key_name = "rmcs_session",
+    key_in = "cookie",
+    checker = "check_session"
+)]
+pub struct SessionAuth(User);
+
+/// API token created through `/auth/tokens`.
+#[derive(SecurityScheme)]
+#[oai(ty = "bearer", checker = "check_token")]
+pub struct TokenAuth(User);
+
+/// An authenticated operator.
+#[derive(SecurityScheme)]
+pub enum Auth {
+    Session(SessionAuth),
+    Token(TokenAuth),
+}
+
+impl Auth {
+    pub fn user(&self) -> &User {
+        match self {
+            Auth::Session(SessionAuth(user)) | Auth::Token(TokenAuth(user)) => {
+                user
+            }
+        }
+    }
+
+    /// Returns the user if their role is at least `role`.
+    pub fn require(&self, role: UserRole) -> Result<&User, GenericResponse> {
+        let user = self.user();
+        if user.role < role {
+            return Err(GenericResponse::forbidden(format!(
+                "This action requires the `{}` role",
+                role.as_str()
+            )));
+        }
+        Ok(user)
+    }
+}
+
+fn session_cookie(token: &str, max_age_secs: i64) -> anyhow::Result<String> {
+    let secure =
+        parse_env_or(ENV_NAME_AUTH_COOKIE_SECURE, DEFAULT_AUTH_COOKIE_SECURE)?;
+    Ok(format!(
+        "{SESSION_COOKIE}={token}; Path=/; Max-Age={max_age_secs}; HttpOnly; \
+         SameSite=Lax{}",
+        if secure { "; Secure" } else { "" }
+    ))
+}
+
+fn validate_password(password: &str) -> Result<(), GenericResponse> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(GenericResponse::validation_failed(format!(
+            "Password must be at least {MIN_PASSWORD_LEN} characters long"
+        )));
+    }
+    Ok(())
+}
+
+async fn hash_password_blocking(password: String) -> anyhow::Result<String> {
+    tokio::task::spawn_blocking(move || hash_password(&password)).await?
+}
+
+async fn verify_password_blocking(password: String, hash: String) -> bool {
+    tokio::task::spawn_blocking(move || verify_password(&password, &hash))
+        .await
+        .unwrap_or(false)
+}
+
+/// Creates the initial user from the environment when no user exists, so
+/// that a fresh deployment can be logged into.
+pub async fn bootstrap_user() -> anyhow::Result<()> {
+    if with_database(Database::count_users)?.await? > 0 {
+        return Ok(());
+    }
+    let (Ok(username), Ok(password)) = (
+        std::env::var(ENV_NAME_AUTH_BOOTSTRAP_USERNAME),
+        std::env::var(ENV_NAME_AUTH_BOOTSTRAP_PASSWORD),
+    ) else {
+        log::warn!(
+            "No users exist; set {ENV_NAME_AUTH_BOOTSTRAP_USERNAME} and \
+             {ENV_NAME_AUTH_BOOTSTRAP_PASSWORD} to create the first one"
+        );
+        return Ok(());
+    };
+    if let Err(err) = validate_password(&password) {
+        anyhow::bail!("{ENV_NAME_AUTH_BOOTSTRAP_PASSWORD} is invalid: {err}");
+    }
+    let password_hash = hash_password_blocking(password).await?;
+    with_database(|db| {
+        db.create_user(username.trim(), &password_hash, UserRole::Admin, &[])
+    })?
+    .await?;
+    log::info!("Created initial user `{}`", username.trim());
+    Ok(())
+}
+
+#[derive(ApiResponse)]
+pub enum LoginResponse {
+    /// Logged in; the session cookie is set.
+    #[oai(status = 200)]
+    Ok(Json<User>, #[oai(header = "Set-Cookie")] String),
+    /// `unauthorized`
+    #[oai(status = 401, content_type = "application/problem+json")]
+    Unauthorized(Json<Problem>),
+    /// `too_many_requests`; the header tells after how many seconds the
+    /// next attempt is allowed.
+    #[oai(status = 429, content_type = "application/problem+json")]
+    TooManyRequests(Json<Problem>, #[oai(header = "Retry-After")] u64),
+}
+
+#[derive(ApiResponse)]
+pub enum LogoutResponse {
+    /// Logged out; the session cookie is cleared.
+    #[oai(status = 200)]
+    Ok(
+        Json<objects::AuthOkResponse>,
+        #[oai(header = "Set-Cookie")] String,
+    ),
+}
+
+pub struct AuthApi;
+
+#[OpenApi]
+impl AuthApi {
+    /// Logs in with a password. After too many attempts for a username
+    /// from one client address, that client's logins as it are refused
+    /// for a while.
+    #[oai(path = "/auth/login", method = "post")]
+    async fn login(
+        &self,
+        req: &Request,
+        request: Json<objects::LoginRequest>,
+    ) -> Result<LoginResponse, GenericResponse> {
+        // The socket address is used rather than forwarding headers, which
+        // a client could rotate to get past the limit.
+        let client = req.remote_addr().as_socket_addr().map(SocketAddr::ip);
+        if let Err(retry_after) =
+            throttle::begin_attempt(client, &request.username)
+        {
+            let retry_after_secs = retry_after.as_secs() + 1;
+            return Ok(LoginResponse::TooManyRequests(
+                Json(Problem::new(
+                    ErrorCode::TooManyRequests,
+                    format!(
+                        "Too many login attempts, try again in \
+                         {retry_after_secs} seconds"
+                    ),
+                )),
+                retry_after_secs,
+            ));
+        }
+        let credentials = with_database(|db| {
+            db.get_user_credentials(request.username.trim())
+        })?
+        .await?;
+        let verified = match &credentials {
+            Some(credentials) => {
+                verify_password_blocking(
+                    request.password.clone(),
+                    credentials.password_hash.clone(),
+                )
+                .await
+            }
+            None => false,
+        };
+        let Some(user) = (match credentials.filter(|_| verified) {
+            Some(credentials) => {
+                with_database(|db| db.get_user(credentials.id))?.await?
+            }
+            None => None,
+        }) else {
+            return Ok(LoginResponse::Unauthorized(Json(Problem::new(
+                ErrorCode::Unauthorized,
+                "Invalid username or password",
+            ))));
+        };
+
+        throttle::clear_attempts(client, &request.username);
+        RequestActor::set(req, &user);
+
+        let ttl_secs = parse_env_or(
+            ENV_NAME_AUTH_SESSION_TTL_SECS,
+            DEFAULT_AUTH_SESSION_TTL_SECS,
+        )?;
+        let token = generate_token(SESSION_TOKEN_PREFIX);
+        let token_hash = hash_token(&token);
+        with_database(|db| {
+            db.create_auth_session(
+                &token_hash,
+                user.id,
+                Utc::now() + TimeDelta::seconds(ttl_secs),
+            )
+        })?
+        .await?;
+        Ok(LoginResponse::Ok(
+            Json(user),
+            session_cookie(&token, ttl_secs)?,
+        ))
+    }
+
+    /// Ends the current login session, if any. API tokens are not
+    /// affected.
+    #[oai(path = "/auth/logout", method = "post")]
+    async fn logout(
+        &self,
+        req: &Request,
+    ) -> Result<LogoutResponse, GenericResponse> {
+        if let Some(cookie) = req.cookie().get(SESSION_COOKIE) {
+            let token_hash = hash_token(cookie.value_str());
+            with_database(|db| db.delete_auth_session(&token_hash))?.await?;
+        }
+        Ok(LogoutResponse::Ok(
+            Json(objects::AuthOkResponse),
+            session_cookie("", 0)?,
+        ))
+    }
+
+    #[oai(path = "/auth/me", method = "get")]
+    #[allow(clippy::unused_async)]
+    async fn me(&self, auth: Auth) -> ApiResult<User> {
+        Ok(Json(auth.user().clone()))
+    }
+
+    /// Changes the caller's password and ends all of their login sessions.
+    #[oai(path = "/auth/change_password", method = "post")]
+    async fn change_password(
+        &self,
+        auth: Auth,
+        request: Json<objects::ChangePasswordRequest>,
+    ) -> ApiResult<objects::AuthOkResponse> {
+        validate_password(&request.new_password)?;
+        let user = auth.user();
+        let Some(credentials) =
+            with_database(|db| db.get_user_credentials(&user.username))?
+                .await?
+        else {
+            return Err(GenericResponse::not_found("User no longer exists"));
+        };
+        if !verify_password_blocking(
+            request.current_password.clone(),
+            credentials.password_hash,
+        )
+        .await
+        {
+            return Err(GenericResponse::validation_failed(
+                "Current password is incorrect",
+            ));
+        }
+        let password_hash =
+            hash_password_blocking(request.new_password.clone()).await?;
+        with_database(|db| db.set_user_password(user.id, &password_hash))?
+            .await?;
+        Ok(Json(objects::AuthOkResponse))
+    }
+
+    #[oai(path = "/auth/users", method = "get")]
+    async fn list_users(&self, auth: Auth) -> ApiResult<Vec<User>> {
+        auth.require(UserRole::Admin)?;
+        Ok(Json(with_database(Database::list_users)?.await?))
+    }
+
+    #[oai(path = "/auth/users", method = "post")]
+    async fn create_user(
+        &self,
+        auth: Auth,
+        request: Json<objects::CreateUserRequest>,
+    ) -> ApiResult<User> {
+        auth.require(UserRole::Admin)?;
+        let username = request.username.trim();
+        if username.is_empty() {
+            return Err(GenericResponse::validation_failed(
+                "Username must not be empty",
+            ));
+        }
+        validate_password(&request.password)?;
+        let password_hash =
+            hash_password_blocking(request.password.clone()).await?;
+        let scope_tags = normalize_tags(&request.scope_tags);
+        with_database(|db| {
+            db.create_user(username, &password_hash, request.role, &scope_tags)
+        })?
+        .await?
+        .map(Json)
+        .ok_or_else(|| {
+            GenericResponse::conflict(format!(
+                "User `{username}` already exists"
+            ))
+        })
+    }
+
+    /// Changes a user's role and the robot tags an operator is scoped to.
+    #[oai(path = "/auth/users/:id/role", method = "post")]
+    async fn set_user_role(
+        &self,
+        auth: Auth,
+        Path(id): Path<i64>,
+        request: Json<objects::SetUserRoleRequest>,
+    ) -> ApiResult<User> {
+        auth.require(UserRole::Admin)?;
+        let Some(user) = with_database(|db| db.get_user(id))?.await? else {
+            return Err(GenericResponse::not_found(format!(
+                "No user found with ID: {id}"
+            )));
+        };
+        if user.role == UserRole::Admin
+            && request.role != UserRole::Admin
+            && with_database(Database::count_admins)?.await? <= 1
+        {
+            return Err(GenericResponse::conflict(
+                "Cannot demote the last admin",
+            ));
+        }
+        let scope_tags = normalize_tags(&request.scope_tags);
+        with_database(|db| db.set_user_role(id, request.role, &scope_tags))?
+            .await?;
+        Ok(Json(User {
+            role: request.role,
+            scope_tags,
+            ..user
+        }))
+    }
+
+    /// Lists the caller's API tokens.
+    #[oai(path = "/auth/tokens", method = "get")]
+    async fn list_api_tokens(
+        &self,
+        auth: Auth,
+    ) -> ApiResult<Vec<ApiTokenInfo>> {
+        let user_id = auth.user().id;
+        Ok(Json(
+            with_database(|db| db.list_api_tokens(user_id))?.await?,
+        ))
+    }
+
+    /// Creates an API token acting as the caller. The token is only
+    /// returned by this request.
+    #[oai(path = "/auth/tokens", method = "post")]
+    async fn create_api_token(
+        &self,
+        auth: Auth,
+        request: Json<objects::CreateApiTokenRequest>,
+    ) -> ApiResult<objects::CreatedApiToken> {
+        let name = request.name.trim();
+        if name.is_empty() {
+            return Err(GenericResponse::validation_failed(
+                "Token name must not be empty",
+            ));
+        }
+        let token = generate_token(API_TOKEN_PREFIX);
+        let token_hash = hash_token(&token);
+        let user_id = auth.user().id;
+        let info = with_database(|db| {
+            db.create_api_token(user_id, name, &token_hash)
+        })?
+        .await?;
+        Ok(Json(objects::CreatedApiToken { info, token }))
+    }
+
+    #[oai(path = "/auth/tokens/:id/revoke", method = "post")]
+    async fn revoke_api_token(
+        &self,
+        auth: Auth,
+        Path(id): Path<i64>,
+    ) -> ApiResult<objects::AuthOkResponse> {
+        let user_id = auth.user().id;
+        let found =
+            with_database(|db| db.delete_api_token(user_id, id))?.await?;
+        if !found {
+            return Err(GenericResponse::not_found(format!(
+                "No API token found with ID: {id}"
+            )));
+        }
+        Ok(Json(objects::AuthOkResponse))
+    }
+}
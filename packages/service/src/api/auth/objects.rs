@@ -0,0 +1,65 @@
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+
+use crate::database::user::{ApiTokenInfo, UserRole};
+
+fn default_role() -> UserRole {
+    UserRole::Viewer
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct LoginRequest {
+    pub username: String,
+    #[oai(write_only)]
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct ChangePasswordRequest {
+    #[oai(write_only)]
+    pub current_password: String,
+    #[oai(write_only)]
+    pub new_password: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct CreateUserRequest {
+    pub username: String,
+    #[oai(write_only)]
+    pub password: String,
+    /// Defaults to `viewer`.
+    #[oai(default = "default_role")]
+    #[serde(default = "default_role")]
+    pub role: UserRole,
+    /// Robot tags an operator is restricted to; empty for no restriction.
+    #[oai(default)]
+    #[serde(default)]
+    pub scope_tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct SetUserRoleRequest {
+    pub role: UserRole,
+    /// Robot tags an operator is restricted to; empty for no restriction.
+    #[oai(default)]
+    #[serde(default)]
+    pub scope_tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct CreateApiTokenRequest {
+    /// Label telling the token's purpose, e.g. the script using it.
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct CreatedApiToken {
+    #[oai(flatten)]
+    #[serde(flatten)]
+    pub info: ApiTokenInfo,
+    /// The bearer token. It cannot be retrieved again.
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Object)]
+pub struct AuthOkResponse;
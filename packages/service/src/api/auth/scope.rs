@@ -0,0 +1,30 @@
+use crate::{
+    api::GenericResponse,
+    database::{user::User, with_database},
+};
+
+pub fn out_of_scope(robot_uuid: &str) -> GenericResponse {
+    GenericResponse::forbidden(format!(
+        "Robot {robot_uuid} is outside of your scope"
+    ))
+}
+
+/// Fails unless `user` may act on the robot with the given UUID. Robots
+/// that are not registered carry no tags, so only unscoped users may act
+/// on them.
+pub async fn check_robot_scope(
+    user: &User,
+    robot_uuid: &str,
+) -> Result<(), GenericResponse> {
+    if !user.is_scoped() {
+        return Ok(());
+    }
+    let tags = with_database(|db| db.get_robot_profile(robot_uuid))?
+        .await?
+        .map(|profile| profile.tags)
+        .unwrap_or_default();
+    if !user.can_act_on(&tags) {
+        return Err(out_of_scope(robot_uuid));
+    }
+    Ok(())
+}
@@ -0,0 +1,144 @@
+//! Limits login attempts so that passwords cannot be guessed quickly.
+//!
+//! Attempts are counted in memory per client address and username, so a
+//! client guessing a password cannot lock the user out for everyone else.
+//! Once a client has used [`MAX_LOGIN_ATTEMPTS`] for a username within
+//! [`LOGIN_ATTEMPT_WINDOW`], its further attempts are refused until the
+//! window has passed, even with the right password. A successful login
+//! clears the count.
+
+use std::{
+    net::IpAddr,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+
+pub const MAX_LOGIN_ATTEMPTS: u32 = 5;
+pub const LOGIN_ATTEMPT_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// Number of tracked clients and usernames above which expired windows are dropped.
+const PRUNE_THRESHOLD: usize = 1024;
+
+struct Attempts {
+    count: u32,
+    since: Instant,
+}
+
+type Key = (Option<IpAddr>, String);
+
+static LOGIN_ATTEMPTS: LazyLock<DashMap<Key, Attempts>> =
+    LazyLock::new(DashMap::new);
+
+fn key(client: Option<IpAddr>, username: &str) -> Key {
+    (client, username.trim().to_lowercase())
+}
+
+/// Counts an attempt by `client` to log in as `username`. Fails with the
+/// time left until the next attempt is allowed if too many were made
+/// recently.
+///
+/// Attempts are counted before the password is checked, so concurrent
+/// requests cannot get past the limit.
+pub fn begin_attempt(
+    client: Option<IpAddr>,
+    username: &str,
+) -> Result<(), Duration> {
+    if LOGIN_ATTEMPTS.len() > PRUNE_THRESHOLD {
+        LOGIN_ATTEMPTS.retain(|_, attempts| {
+            attempts.since.elapsed() < LOGIN_ATTEMPT_WINDOW
+        });
+    }
+
+    let now = Instant::now();
+    let mut attempts =
+        LOGIN_ATTEMPTS
+            .entry(key(client, username))
+            .or_insert(Attempts {
+                count: 0,
+                since: now,
+            });
+    let elapsed = now.duration_since(attempts.since);
+    if elapsed >= LOGIN_ATTEMPT_WINDOW {
+        *attempts = Attempts {
+            count: 0,
+            since: now,
+        };
+    } else if attempts.count >= MAX_LOGIN_ATTEMPTS {
+        return Err(LOGIN_ATTEMPT_WINDOW.saturating_sub(elapsed));
+    }
+    attempts.count += 1;
+    Ok(())
+}
+
+/// Forgets the attempts of `client` for `username` after it logged in
+/// successfully.
+pub fn clear_attempts(client: Option<IpAddr>, username: &str) {
+    LOGIN_ATTEMPTS.remove(&key(client, username));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    const CLIENT: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+    const OTHER_CLIENT: Option<IpAddr> =
+        Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)));
+
+    fn use_up_attempts(client: Option<IpAddr>, username: &str) {
+        for _ in 0..MAX_LOGIN_ATTEMPTS {
+            assert!(begin_attempt(client, username).is_ok());
+        }
+    }
+
+    #[test]
+    fn refuses_attempts_over_the_limit() {
+        use_up_attempts(CLIENT, "limit");
+        let retry_after = begin_attempt(CLIENT, "limit").unwrap_err();
+        assert!(retry_after <= LOGIN_ATTEMPT_WINDOW);
+        assert!(retry_after > Duration::ZERO);
+    }
+
+    #[test]
+    fn counts_usernames_case_insensitively() {
+        use_up_attempts(CLIENT, "Mixed");
+        assert!(begin_attempt(CLIENT, " mixed ").is_err());
+    }
+
+    #[test]
+    fn does_not_lock_out_other_clients() {
+        use_up_attempts(CLIENT, "shared");
+        assert!(begin_attempt(CLIENT, "shared").is_err());
+        assert!(begin_attempt(OTHER_CLIENT, "shared").is_ok());
+        assert!(begin_attempt(None, "shared").is_ok());
+    }
+
+    #[test]
+    fn does_not_lock_out_other_usernames() {
+        use_up_attempts(CLIENT, "first");
+        assert!(begin_attempt(CLIENT, "second").is_ok());
+    }
+
+    #[test]
+    fn clearing_resets_the_count() {
+        use_up_attempts(CLIENT, "cleared");
+        clear_attempts(CLIENT, "cleared");
+        assert!(begin_attempt(CLIENT, "cleared").is_ok());
+    }
+
+    #[test]
+    fn allows_attempts_again_after_the_window() {
+        use_up_attempts(CLIENT, "expired");
+        if let Some(mut attempts) =
+            LOGIN_ATTEMPTS.get_mut(&key(CLIENT, "expired"))
+        {
+            attempts.since = Instant::now()
+                .checked_sub(LOGIN_ATTEMPT_WINDOW)
+                .expect("monotonic clock is past the window");
+        }
+        assert!(begin_attempt(CLIENT, "expired").is_ok());
+    }
+}
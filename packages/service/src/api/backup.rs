@@ -0,0 +1,157 @@
+use std::{collections::HashSet, time::Instant};
+
+use poem_openapi::{
+    ApiResponse, OpenApi,
+    payload::{Binary, Json},
+};
+
+use crate::{
+    api::{ApiResult, GenericResponse, auth::Auth},
+    database::{
+        Database,
+        audit::{Actor, AuditCategory, AuditOutcome, NewAuditEntry},
+        registry::{
+            REGISTRY_FORMAT_VERSION, RegistryExport, RegistryImportSummary,
+        },
+        robot::EnrollmentStatus,
+        user::UserRole,
+        with_database,
+    },
+    service::{CONNECTIONS, audit, backup::BACKUP_SERVICE},
+};
+
+#[derive(ApiResponse)]
+pub enum SnapshotResponse {
+    /// A copy of the database file.
+    #[oai(status = 200, content_type = "application/vnd.sqlite3")]
+    Ok(
+        Binary<Vec<u8>>,
+        #[oai(header = "Content-Disposition")] String,
+    ),
+}
+
+/// Checks that an export can be imported as a whole before anything is
+/// written.
+async fn validate_import(
+    export: &RegistryExport,
+) -> Result<(), GenericResponse> {
+    if export.format_version != REGISTRY_FORMAT_VERSION {
+        return Err(GenericResponse::validation_failed(format!(
+            "Unsupported registry format version {}; expected {}",
+            export.format_version, REGISTRY_FORMAT_VERSION
+        )));
+    }
+    let mut uuids = HashSet::new();
+    for robot in &export.robots {
+        if !uuids.insert(robot.ident.uuid.as_str()) {
+            return Err(GenericResponse::validation_failed(format!(
+                "Robot {} appears more than once",
+                robot.ident.uuid
+            )));
+        }
+    }
+    for alias in &export.aliases {
+        if uuids.contains(alias.alias_uuid.as_str()) {
+            return Err(GenericResponse::validation_failed(format!(
+                "Alias {} is also a robot",
+                alias.alias_uuid
+            )));
+        }
+        if !uuids.contains(alias.robot_uuid.as_str())
+            && with_database(|db| db.get_robot_enrollment(&alias.robot_uuid))?
+                .await?
+                .is_none()
+        {
+            return Err(GenericResponse::validation_failed(format!(
+                "Alias {} refers to unknown robot {}",
+                alias.alias_uuid, alias.robot_uuid
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Copies of the service database and its robot registry.
+pub struct BackupApi;
+
+#[OpenApi]
+impl BackupApi {
+    /// Downloads a consistent copy of the whole database, which can
+    /// replace the database file of a stopped service.
+    #[oai(path = "/backup/snapshot", method = "get")]
+    async fn snapshot(
+        &self,
+        auth: Auth,
+    ) -> Result<SnapshotResponse, GenericResponse> {
+        let user = auth.require(UserRole::Admin)?;
+        let started = Instant::now();
+        let service = BACKUP_SERVICE.get().ok_or_else(|| {
+            anyhow::anyhow!("Backup service is not initialized")
+        })?;
+        let snapshot = service.snapshot().await;
+        // Reads are not audited otherwise, but a snapshot hands out every
+        // credential hash and the whole fleet.
+        audit::record(NewAuditEntry {
+            category: AuditCategory::Api,
+            actor: Actor::from(user),
+            action: "GET /backup/snapshot".to_string(),
+            robot_uuid: None,
+            parameters: None,
+            outcome: if snapshot.is_ok() {
+                AuditOutcome::Success
+            } else {
+                AuditOutcome::Failure
+            },
+            message: snapshot.as_ref().err().map(ToString::to_string),
+            duration: started.elapsed(),
+        });
+        let filename = format!(
+            "rmcs-actions-{}.db",
+            chrono::Utc::now().format("%Y%m%dT%H%M%SZ")
+        );
+        Ok(SnapshotResponse::Ok(
+            Binary(snapshot?),
+            format!("attachment; filename=\"{filename}\""),
+        ))
+    }
+
+    /// Exports every robot, including archived ones, with its tags and
+    /// fleet metadata, along with the UUIDs of merged robots.
+    #[oai(path = "/backup/registry", method = "get")]
+    async fn export_registry(&self, auth: Auth) -> ApiResult<RegistryExport> {
+        auth.require(UserRole::Admin)?;
+        Ok(Json(with_database(Database::export_registry)?.await?))
+    }
+
+    /// Imports a registry export. Robots already registered under the same
+    /// UUID are replaced by the imported ones; other robots are kept.
+    #[oai(path = "/backup/registry", method = "post")]
+    async fn import_registry(
+        &self,
+        auth: Auth,
+        export: Json<RegistryExport>,
+    ) -> ApiResult<RegistryImportSummary> {
+        auth.require(UserRole::Admin)?;
+        validate_import(&export).await?;
+        let summary = with_database(|db| db.import_registry(&export))?.await?;
+        // Connected robots follow their imported enrollment right away.
+        // Newly approved ones reconnect, presenting their secret.
+        for robot in &export.robots {
+            if let Some(conn) = CONNECTIONS.get(&robot.ident.uuid) {
+                let conn = conn.value();
+                let approved = robot.enrollment == EnrollmentStatus::Approved
+                    && robot.archived_at.is_none();
+                if !approved {
+                    conn.set_approved(false);
+                }
+                if robot.enrollment.is_banned()
+                    || robot.archived_at.is_some()
+                    || (approved && !conn.is_approved())
+                {
+                    conn.disconnect();
+                }
+            }
+        }
+        Ok(Json(summary))
+    }
+}
@@ -0,0 +1,146 @@
+use poem_openapi::{
+    OpenApi,
+    param::{Path, Query},
+    payload::Json,
+};
+
+use crate::{
+    api::{ApiResult, GenericResponse, auth::Auth},
+    database::{
+        Database,
+        robot::{EnrollmentStatus, RobotProfile},
+        user::UserRole,
+        with_database,
+    },
+    service::CONNECTIONS,
+};
+
+/// Moves a robot between enrollment states and returns its profile, or
+/// explains why the transition is not possible.
+async fn transition(
+    uuid: &str,
+    from: &[EnrollmentStatus],
+    to: EnrollmentStatus,
+) -> ApiResult<RobotProfile> {
+    if with_database(|db| db.get_robot_profile(uuid))?
+        .await?
+        .is_some_and(|profile| profile.archived_at.is_some())
+    {
+        return Err(GenericResponse::conflict(format!(
+            "Robot {uuid} is archived"
+        )));
+    }
+    if !with_database(|db| db.transition_robot_enrollment(uuid, from, to))?
+        .await?
+    {
+        return Err(
+            match with_database(|db| db.get_robot_enrollment(uuid))?.await? {
+                Some(current) => GenericResponse::conflict(format!(
+                    "Robot {uuid} is {} and cannot become {}",
+                    current.as_str(),
+                    to.as_str()
+                )),
+                None => GenericResponse::not_found(format!(
+                    "No robot found with UUID: {uuid}"
+                )),
+            },
+        );
+    }
+    with_database(|db| db.get_robot_profile(uuid))?
+        .await?
+        .map(Json)
+        .ok_or_else(|| {
+            GenericResponse::not_found(format!(
+                "No robot found with UUID: {uuid}"
+            ))
+        })
+}
+
+/// Admission of robots registered through `/ident/sync`.
+pub struct EnrollmentApi;
+
+#[OpenApi]
+impl EnrollmentApi {
+    /// Lists registered robots, optionally only those in one enrollment
+    /// state.
+    #[oai(path = "/enrollment/robots", method = "get")]
+    async fn list_robots(
+        &self,
+        _auth: Auth,
+        Query(status): Query<Option<EnrollmentStatus>>,
+    ) -> ApiResult<Vec<RobotProfile>> {
+        let mut profiles = with_database(Database::get_robot_profiles)?.await?;
+        if let Some(status) = status {
+            profiles.retain(|profile| profile.enrollment == status);
+        }
+        Ok(Json(profiles))
+    }
+
+    /// Admits a pending, rejected or revoked robot and issues it a new
+    /// connection secret. A connected robot is disconnected, so that it
+    /// picks the secret up and reconnects with it.
+    #[oai(path = "/enrollment/robots/:uuid/approve", method = "post")]
+    async fn approve(
+        &self,
+        auth: Auth,
+        Path(uuid): Path<String>,
+    ) -> ApiResult<RobotProfile> {
+        auth.require(UserRole::Admin)?;
+        let profile = transition(
+            &uuid,
+            &[
+                EnrollmentStatus::Pending,
+                EnrollmentStatus::Rejected,
+                EnrollmentStatus::Revoked,
+            ],
+            EnrollmentStatus::Approved,
+        )
+        .await?;
+        with_database(|db| db.issue_robot_secret(&uuid))?.await?;
+        if let Some(conn) = CONNECTIONS.get(&uuid) {
+            conn.value().disconnect();
+        }
+        Ok(profile)
+    }
+
+    /// Turns down a pending robot and closes its connection.
+    #[oai(path = "/enrollment/robots/:uuid/reject", method = "post")]
+    async fn reject(
+        &self,
+        auth: Auth,
+        Path(uuid): Path<String>,
+    ) -> ApiResult<RobotProfile> {
+        auth.require(UserRole::Admin)?;
+        let profile = transition(
+            &uuid,
+            &[EnrollmentStatus::Pending],
+            EnrollmentStatus::Rejected,
+        )
+        .await?;
+        if let Some(conn) = CONNECTIONS.get(&uuid) {
+            conn.value().disconnect();
+        }
+        Ok(profile)
+    }
+
+    /// Removes an approved robot from the fleet and closes its connection.
+    #[oai(path = "/enrollment/robots/:uuid/revoke", method = "post")]
+    async fn revoke(
+        &self,
+        auth: Auth,
+        Path(uuid): Path<String>,
+    ) -> ApiResult<RobotProfile> {
+        auth.require(UserRole::Admin)?;
+        let profile = transition(
+            &uuid,
+            &[EnrollmentStatus::Approved],
+            EnrollmentStatus::Revoked,
+        )
+        .await?;
+        if let Some(conn) = CONNECTIONS.get(&uuid) {
+            conn.value().set_approved(false);
+            conn.value().disconnect();
+        }
+        Ok(profile)
+    }
+}
@@ -0,0 +1,92 @@
+//! Problem details ([RFC 9457]) returned by every failing endpoint.
+//!
+//! [RFC 9457]: https://www.rfc-editor.org/rfc/rfc9457
+
+use poem::http::StatusCode;
+use poem_openapi::{Enum, Object};
+use serde::{Deserialize, Serialize};
+
+/// Machine-readable reason of an error. Codes are stable; the accompanying
+/// `detail` text is not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The request is malformed or its values are invalid.
+    ValidationFailed,
+    /// No valid session cookie or API token was presented.
+    Unauthorized,
+    /// The caller's role or scope does not allow the request.
+    Forbidden,
+    NotFound,
+    /// The request conflicts with the current state of a resource, such as
+    /// an archived robot or a job that has ended.
+    Conflict,
+    /// The robot is not connected.
+    RobotOffline,
+    /// The robot failed to carry out the instruction or answered with an
+    /// error.
+    BotError,
+    /// The robot did not answer the instruction in time.
+    InstructionTimeout,
+    /// The instruction's session was cancelled before the robot answered.
+    InstructionCancelled,
+    /// Another mutating instruction is running on the robot.
+    RobotLocked,
+    /// Too many attempts were made recently, such as failed logins.
+    TooManyRequests,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::ValidationFailed => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::Conflict
+            | ErrorCode::RobotOffline
+            | ErrorCode::InstructionCancelled
+            | ErrorCode::RobotLocked => StatusCode::CONFLICT,
+            ErrorCode::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::BotError => StatusCode::BAD_GATEWAY,
+            ErrorCode::InstructionTimeout => StatusCode::GATEWAY_TIMEOUT,
+            ErrorCode::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct Problem {
+    /// Reason phrase of the HTTP status.
+    pub title: String,
+    /// HTTP status code.
+    pub status: u16,
+    /// Human-readable explanation of this occurrence.
+    pub detail: String,
+    pub code: ErrorCode,
+    /// Robot the error concerns.
+    #[oai(skip_serializing_if_is_none)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub robot_uuid: Option<String>,
+}
+
+impl Problem {
+    pub fn new(code: ErrorCode, detail: impl Into<String>) -> Self {
+        let status = code.status();
+        Self {
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            status: status.as_u16(),
+            detail: detail.into(),
+            code,
+            robot_uuid: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_robot(mut self, robot_uuid: impl Into<String>) -> Self {
+        self.robot_uuid = Some(robot_uuid.into());
+        self
+    }
+}
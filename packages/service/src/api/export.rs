@@ -0,0 +1,110 @@
+use poem_openapi::{Enum, OpenApi, param::Query, payload::PlainText};
+
+use crate::{
+    api::{GenericResponse, RawApiResult, auth::Auth},
+    constant::env::{DEFAULT_DNS_ZONE, ENV_NAME_DNS_ZONE},
+    database::{
+        Database,
+        robot::{RobotFilter, RobotRole, TeamColor},
+        with_database,
+    },
+    env::parse_env_or,
+    utils::network::IgnoredInterfaces,
+};
+
+pub mod inventory;
+
+#[derive(Debug, Clone, Copy, Default, Enum)]
+#[oai(rename_all = "lowercase")]
+pub enum AnsibleFormat {
+    #[default]
+    Yaml,
+    Ini,
+}
+
+async fn load_inventory(
+    filter: &RobotFilter,
+) -> anyhow::Result<inventory::Inventory> {
+    let robots = with_database(Database::get_robot_profiles)?.await?;
+    let addresses = with_database(Database::get_all_robot_addresses)?.await?;
+    Ok(inventory::Inventory::new(
+        robots,
+        addresses,
+        &IgnoredInterfaces::from_env()?,
+        filter,
+    ))
+}
+
+pub struct ExportApi;
+
+#[OpenApi]
+impl ExportApi {
+    /// Ansible inventory of every robot with a reachable address.
+    #[oai(path = "/export/ansible", method = "get")]
+    async fn export_ansible(
+        &self,
+        _auth: Auth,
+        Query(format): Query<Option<AnsibleFormat>>,
+        Query(role): Query<Vec<RobotRole>>,
+        Query(team_color): Query<Option<TeamColor>>,
+        Query(tag): Query<Vec<String>>,
+    ) -> RawApiResult<PlainText<String>> {
+        let inventory = load_inventory(&RobotFilter {
+            roles: role,
+            team_color,
+            tags: tag,
+        })
+        .await?;
+        Ok(PlainText(match format.unwrap_or_default() {
+            AnsibleFormat::Yaml => inventory.to_ansible_yaml(),
+            AnsibleFormat::Ini => inventory.to_ansible_ini(),
+        }))
+    }
+
+    /// OpenSSH client configuration with one `Host` entry per robot.
+    #[oai(path = "/export/ssh_config", method = "get")]
+    async fn export_ssh_config(
+        &self,
+        _auth: Auth,
+        Query(user): Query<Option<String>>,
+        Query(role): Query<Vec<RobotRole>>,
+        Query(team_color): Query<Option<TeamColor>>,
+        Query(tag): Query<Vec<String>>,
+    ) -> RawApiResult<PlainText<String>> {
+        if let Some(user) = &user
+            && !inventory::is_plain_username(user)
+        {
+            return Err(GenericResponse::validation_failed(
+                "user must be a plain user name of letters, digits, `_`, `-` \
+                 and `.`",
+            ));
+        }
+        let inventory = load_inventory(&RobotFilter {
+            roles: role,
+            team_color,
+            tags: tag,
+        })
+        .await?;
+        Ok(PlainText(inventory.to_ssh_config(user.as_deref())))
+    }
+
+    /// `/etc/hosts` fragment mapping robot names to their addresses.
+    #[oai(path = "/export/hosts", method = "get")]
+    async fn export_hosts(
+        &self,
+        _auth: Auth,
+        Query(role): Query<Vec<RobotRole>>,
+        Query(team_color): Query<Option<TeamColor>>,
+        Query(tag): Query<Vec<String>>,
+    ) -> RawApiResult<PlainText<String>> {
+        let inventory = load_inventory(&RobotFilter {
+            roles: role,
+            team_color,
+            tags: tag,
+        })
+        .await?;
+        let zone =
+            parse_env_or(ENV_NAME_DNS_ZONE, DEFAULT_DNS_ZONE.to_string())?;
+        Ok(PlainText(inventory.to_hosts(zone.trim_matches('.'))))
+    }
+}
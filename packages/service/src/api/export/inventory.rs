@@ -0,0 +1,503 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write,
+    net::IpAddr,
+};
+
+use crate::{
+    database::{
+        network::RobotAddressRow,
+        robot::{RobotFilter, RobotProfile},
+    },
+    utils::network::IgnoredInterfaces,
+};
+
+/// Group every exported host belongs to.
+pub const ALL_ROBOTS_GROUP: &str = "robots";
+
+/// Length of the UUID prefix appended to aliases that collide.
+const ALIAS_SUFFIX_LEN: usize = 8;
+
+/// A registered robot together with the address used to reach it.
+pub struct InventoryHost {
+    /// Robot name made safe for use as a host alias, unique within the
+    /// inventory.
+    pub alias: String,
+    pub uuid: String,
+    pub address: IpAddr,
+}
+
+pub struct Inventory {
+    pub hosts: Vec<InventoryHost>,
+    /// Group name and the UUIDs of its members, in output order.
+    pub groups: Vec<(String, Vec<String>)>,
+}
+
+/// Replaces characters that are not valid in host aliases and group names.
+fn host_alias(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Makes aliases unique: hosts sharing an alias, compared
+/// case-insensitively like host names, get a short UUID suffix, and the full
+/// UUID if that still collides with another alias.
+fn dedup_aliases(hosts: &mut [InventoryHost]) {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for host in hosts.iter() {
+        *counts.entry(host.alias.to_lowercase()).or_default() += 1;
+    }
+    let mut seen = HashSet::new();
+    for host in hosts.iter_mut() {
+        if counts[&host.alias.to_lowercase()] > 1 {
+            let short = &host.uuid[..host.uuid.len().min(ALIAS_SUFFIX_LEN)];
+            let candidate = format!("{}-{short}", host.alias);
+            host.alias = if counts.contains_key(&candidate.to_lowercase())
+                || seen.contains(&candidate.to_lowercase())
+            {
+                format!("{}-{}", host.alias, host.uuid)
+            } else {
+                candidate
+            };
+        }
+        seen.insert(host.alias.to_lowercase());
+    }
+}
+
+/// Longest user name accepted for SSH exports, as on Linux.
+const MAX_USERNAME_LEN: usize = 32;
+
+/// Whether `user` is a plain user name that can be written into an SSH
+/// config without changing its meaning: ASCII letters, digits, `_`, `-` and
+/// `.`, not starting with `-`.
+pub fn is_plain_username(user: &str) -> bool {
+    !user.is_empty()
+        && user.len() <= MAX_USERNAME_LEN
+        && !user.starts_with('-')
+        && user
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Picks the address used to reach a robot, preferring IPv4 over IPv6.
+fn preferred_address(
+    addresses: &[RobotAddressRow],
+    ignored: &IgnoredInterfaces,
+) -> Option<IpAddr> {
+    let reachable: Vec<IpAddr> = addresses
+        .iter()
+        .filter_map(|row| row.reachable_ip(ignored))
+        .collect();
+    reachable
+        .iter()
+        .find(|ip| ip.is_ipv4())
+        .or_else(|| reachable.first())
+        .copied()
+}
+
+impl Inventory {
+    /// Builds the inventory from every registered robot and their indexed
+    /// addresses, keeping the robots matching `filter`. Robots without a
+    /// reachable address are left out.
+    ///
+    /// Aliases are made unique among all robots before filtering, so that a
+    /// robot has the same alias in every export and in DNS.
+    ///
+    /// Besides the group of all robots, robots are grouped by role
+    /// (`role_<role>`), team colour (`team_<colour>`) and tag
+    /// (`tag_<tag>`).
+    pub fn new(
+        robots: Vec<RobotProfile>,
+        addresses: Vec<(String, RobotAddressRow)>,
+        ignored: &IgnoredInterfaces,
+        filter: &RobotFilter,
+    ) -> Self {
+        let mut addresses_by_robot: HashMap<String, Vec<RobotAddressRow>> =
+            HashMap::new();
+        for (uuid, row) in addresses {
+            addresses_by_robot.entry(uuid).or_default().push(row);
+        }
+
+        let mut hosts = Vec::new();
+        let mut selected_uuids = HashSet::new();
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for robot in robots {
+            let Some(address) = addresses_by_robot
+                .get(&robot.ident.uuid)
+                .and_then(|addresses| preferred_address(addresses, ignored))
+            else {
+                continue;
+            };
+            let alias = host_alias(&robot.ident.name);
+            let selected = filter.matches(&robot);
+            hosts.push(InventoryHost {
+                alias,
+                uuid: robot.ident.uuid.clone(),
+                address,
+            });
+            if !selected {
+                continue;
+            }
+            selected_uuids.insert(robot.ident.uuid.clone());
+
+            let mut member_of = Vec::new();
+            if let Some(role) = robot.role {
+                member_of.push(format!("role_{}", role.as_str()));
+            }
+            if let Some(team_color) = robot.team_color {
+                member_of.push(format!("team_{}", team_color.as_str()));
+            }
+            for tag in &robot.tags {
+                member_of.push(format!("tag_{}", host_alias(tag)));
+            }
+            for group in member_of {
+                groups
+                    .entry(group)
+                    .or_default()
+                    .push(robot.ident.uuid.clone());
+            }
+        }
+
+        dedup_aliases(&mut hosts);
+        hosts.retain(|host| selected_uuids.contains(&host.uuid));
+
+        let mut groups: Vec<(String, Vec<String>)> =
+            groups.into_iter().collect();
+        groups.insert(
+            0,
+            (
+                ALL_ROBOTS_GROUP.to_string(),
+                hosts.iter().map(|host| host.uuid.clone()).collect(),
+            ),
+        );
+
+        Self { hosts, groups }
+    }
+
+    fn host(&self, uuid: &str) -> Option<&InventoryHost> {
+        self.hosts.iter().find(|host| host.uuid == uuid)
+    }
+
+    /// Finds the host with `alias`, compared case-insensitively like host
+    /// names.
+    pub fn find_alias(&self, alias: &str) -> Option<&InventoryHost> {
+        self.hosts
+            .iter()
+            .find(|host| host.alias.eq_ignore_ascii_case(alias))
+    }
+
+    /// Renders an Ansible inventory in YAML format. Host variables are
+    /// listed once, in the group of all robots.
+    pub fn to_ansible_yaml(&self) -> String {
+        let mut out = String::from("all:\n  children:\n");
+        for (group, members) in &self.groups {
+            let _ = writeln!(out, "    {group}:");
+            if members.is_empty() {
+                out.push_str("      hosts: {}\n");
+                continue;
+            }
+            out.push_str("      hosts:\n");
+            for host in members.iter().filter_map(|uuid| self.host(uuid)) {
+                if group == ALL_ROBOTS_GROUP {
+                    let _ = writeln!(out, "        {}:", host.alias);
+                    let _ = writeln!(
+                        out,
+                        "          ansible_host: {}",
+                        host.address
+                    );
+                    let _ = writeln!(
+                        out,
+                        "          robot_uuid: \"{}\"",
+                        host.uuid
+                    );
+                } else {
+                    let _ = writeln!(out, "        {}: {{}}", host.alias);
+                }
+            }
+        }
+        out
+    }
+
+    /// Renders an Ansible inventory in INI format. Host variables are
+    /// listed once, in the group of all robots.
+    pub fn to_ansible_ini(&self) -> String {
+        let mut out = String::new();
+        for (group, members) in &self.groups {
+            let _ = writeln!(out, "[{group}]");
+            for host in members.iter().filter_map(|uuid| self.host(uuid)) {
+                if group == ALL_ROBOTS_GROUP {
+                    let _ = writeln!(
+                        out,
+                        "{} ansible_host={} robot_uuid={}",
+                        host.alias, host.address, host.uuid
+                    );
+                } else {
+                    let _ = writeln!(out, "{}", host.alias);
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Renders `Host` entries for `~/.ssh/config`. `user` must be checked
+    /// with [`is_plain_username`] first.
+    pub fn to_ssh_config(&self, user: Option<&str>) -> String {
+        let mut out = String::new();
+        for host in &self.hosts {
+            let _ = writeln!(out, "Host {}", host.alias);
+            let _ = writeln!(out, "    HostName {}", host.address);
+            if let Some(user) = user {
+                let _ = writeln!(out, "    User {user}");
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Renders an `/etc/hosts` fragment, listing each robot under its alias
+    /// and under its alias within the DNS `zone`, which the DNS responder
+    /// resolves.
+    pub fn to_hosts(&self, zone: &str) -> String {
+        let mut out = String::new();
+        for host in &self.hosts {
+            let _ = writeln!(
+                out,
+                "{} {} {}.{zone}",
+                host.address, host.alias, host.alias
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::robot::{
+        EnrollmentStatus, RobotIdent, RobotRole, TeamColor,
+    };
+
+    fn robot(uuid: &str, name: &str) -> RobotProfile {
+        RobotProfile {
+            ident: RobotIdent {
+                mac: String::new(),
+                name: name.to_string(),
+                uuid: uuid.to_string(),
+            },
+            role: None,
+            team_color: None,
+            tags: Vec::new(),
+            enrollment: EnrollmentStatus::Approved,
+            archived_at: None,
+        }
+    }
+
+    fn address(
+        uuid: &str,
+        interface: &str,
+        ip: &str,
+    ) -> (String, RobotAddressRow) {
+        (
+            uuid.to_string(),
+            RobotAddressRow {
+                interface: interface.to_string(),
+                ip: ip.to_string(),
+            },
+        )
+    }
+
+    fn host(alias: &str, uuid: &str) -> InventoryHost {
+        InventoryHost {
+            alias: alias.to_string(),
+            uuid: uuid.to_string(),
+            address: IpAddr::from([10, 0, 0, 1]),
+        }
+    }
+
+    fn aliases(hosts: &[InventoryHost]) -> Vec<&str> {
+        hosts.iter().map(|host| host.alias.as_str()).collect()
+    }
+
+    #[test]
+    fn sanitizes_aliases() {
+        assert_eq!(host_alias("sentry 1.red"), "sentry_1_red");
+        assert_eq!(host_alias("hero-2_b"), "hero-2_b");
+    }
+
+    #[test]
+    fn keeps_unique_aliases() {
+        let mut hosts = [host("hero", "aaaa"), host("sentry", "bbbb")];
+        dedup_aliases(&mut hosts);
+        assert_eq!(aliases(&hosts), ["hero", "sentry"]);
+    }
+
+    #[test]
+    fn suffixes_colliding_aliases_case_insensitively() {
+        let mut hosts = [
+            host("sentry", "11111111-aaaa"),
+            host("Sentry", "22222222-bbbb"),
+        ];
+        dedup_aliases(&mut hosts);
+        assert_eq!(aliases(&hosts), ["sentry-11111111", "Sentry-22222222"]);
+    }
+
+    #[test]
+    fn falls_back_to_the_full_uuid() {
+        let mut hosts = [
+            host("sentry", "11111111-aaaa"),
+            host("sentry", "11111111-bbbb"),
+            host("sentry-11111111", "33333333-cccc"),
+        ];
+        dedup_aliases(&mut hosts);
+        assert_eq!(
+            aliases(&hosts),
+            [
+                "sentry-11111111-aaaa",
+                "sentry-11111111-bbbb",
+                "sentry-11111111"
+            ]
+        );
+    }
+
+    #[test]
+    fn prefers_reachable_ipv4_addresses() {
+        let ignored = IgnoredInterfaces::parse("docker");
+        let rows: Vec<RobotAddressRow> = [
+            address("a", "lo", "127.0.0.1"),
+            address("a", "docker0", "172.17.0.1"),
+            address("a", "eth0", "fd00::1"),
+            address("a", "eth0", "192.168.1.10"),
+        ]
+        .into_iter()
+        .map(|(_, row)| row)
+        .collect();
+        assert_eq!(
+            preferred_address(&rows, &ignored),
+            Some(IpAddr::from([192, 168, 1, 10]))
+        );
+        assert_eq!(
+            preferred_address(&rows[..3], &ignored),
+            Some("fd00::1".parse().unwrap())
+        );
+        assert_eq!(preferred_address(&rows[..2], &ignored), None);
+    }
+
+    #[test]
+    fn accepts_only_plain_usernames() {
+        assert!(is_plain_username("rmcs"));
+        assert!(is_plain_username("first.last_2-x"));
+        assert!(!is_plain_username(""));
+        assert!(!is_plain_username("-oProxyCommand"));
+        assert!(!is_plain_username("root\nProxyCommand sh"));
+        assert!(!is_plain_username("root user"));
+        assert!(!is_plain_username(&"a".repeat(MAX_USERNAME_LEN + 1)));
+    }
+
+    fn fleet() -> Inventory {
+        let mut sentry = robot("11111111-aaaa", "sentry");
+        sentry.role = Some(RobotRole::Sentry);
+        sentry.team_color = Some(TeamColor::Red);
+        sentry.tags = vec!["lab a".to_string()];
+        let mut other = robot("22222222-bbbb", "sentry");
+        other.team_color = Some(TeamColor::Blue);
+        let offline = robot("33333333-cccc", "hero");
+        Inventory::new(
+            vec![sentry, other, offline],
+            vec![
+                address("11111111-aaaa", "eth0", "192.168.1.10"),
+                address("22222222-bbbb", "eth0", "192.168.1.11"),
+            ],
+            &IgnoredInterfaces::parse(""),
+            &RobotFilter::default(),
+        )
+    }
+
+    #[test]
+    fn groups_robots_with_an_address() {
+        let inventory = fleet();
+        assert_eq!(
+            aliases(&inventory.hosts),
+            ["sentry-11111111", "sentry-22222222"]
+        );
+        let groups: Vec<&str> = inventory
+            .groups
+            .iter()
+            .map(|(group, _)| group.as_str())
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                ALL_ROBOTS_GROUP,
+                "role_sentry",
+                "tag_lab_a",
+                "team_blue",
+                "team_red"
+            ]
+        );
+        assert_eq!(
+            inventory
+                .find_alias("SENTRY-22222222")
+                .map(|h| h.uuid.as_str()),
+            Some("22222222-bbbb")
+        );
+        assert!(inventory.find_alias("hero").is_none());
+    }
+
+    #[test]
+    fn keeps_aliases_when_filtered() {
+        let mut sentry = robot("11111111-aaaa", "sentry");
+        sentry.team_color = Some(TeamColor::Red);
+        let inventory = Inventory::new(
+            vec![sentry, robot("22222222-bbbb", "sentry")],
+            vec![
+                address("11111111-aaaa", "eth0", "192.168.1.10"),
+                address("22222222-bbbb", "eth0", "192.168.1.11"),
+            ],
+            &IgnoredInterfaces::parse(""),
+            &RobotFilter {
+                team_color: Some(TeamColor::Red),
+                ..RobotFilter::default()
+            },
+        );
+        assert_eq!(aliases(&inventory.hosts), ["sentry-11111111"]);
+        assert_eq!(inventory.groups[0].1, ["11111111-aaaa"]);
+    }
+
+    #[test]
+    fn renders_every_format() {
+        let inventory = fleet();
+        assert_eq!(
+            inventory.to_hosts("rmcs.lan"),
+            "192.168.1.10 sentry-11111111 sentry-11111111.rmcs.lan\n\
+             192.168.1.11 sentry-22222222 sentry-22222222.rmcs.lan\n"
+        );
+        assert_eq!(
+            inventory.to_ssh_config(Some("rmcs")),
+            "Host sentry-11111111\n    HostName 192.168.1.10\n    User rmcs\n\n\
+             Host sentry-22222222\n    HostName 192.168.1.11\n    User rmcs\n\n"
+        );
+        let ini = inventory.to_ansible_ini();
+        assert!(ini.starts_with(
+            "[robots]\n\
+             sentry-11111111 ansible_host=192.168.1.10 robot_uuid=11111111-aaaa\n"
+        ));
+        assert!(ini.contains("[team_blue]\nsentry-22222222\n\n"));
+        let yaml = inventory.to_ansible_yaml();
+        assert!(yaml.starts_with(
+            "all:\n  children:\n    robots:\n      hosts:\n        \
+             sentry-11111111:\n          ansible_host: 192.168.1.10\n          \
+             robot_uuid: \"11111111-aaaa\"\n"
+        ));
+        assert!(yaml.contains(
+            "    team_red:\n      hosts:\n        sentry-11111111: {}\n"
+        ));
+    }
+}
@@ -0,0 +1,44 @@
+use poem_openapi::{ApiResponse, OpenApi, payload::Json};
+
+use crate::service::health::{self, HealthReport};
+
+#[derive(ApiResponse)]
+pub enum HealthResponse {
+    /// Every check passed.
+    #[oai(status = 200)]
+    Ok(Json<HealthReport>),
+    /// At least one check is failing.
+    #[oai(status = 503)]
+    Unavailable(Json<HealthReport>),
+}
+
+impl From<HealthReport> for HealthResponse {
+    fn from(report: HealthReport) -> Self {
+        if report.is_ok() {
+            HealthResponse::Ok(Json(report))
+        } else {
+            HealthResponse::Unavailable(Json(report))
+        }
+    }
+}
+
+/// Health checks for supervisors. Unlike `/ping`, both endpoints check the
+/// service's dependencies and need no authentication.
+pub struct HealthApi;
+
+#[OpenApi]
+impl HealthApi {
+    /// Fails when a restart may help: the database does not answer or a
+    /// background task has stopped.
+    #[oai(path = "/health/live", method = "get")]
+    async fn live(&self) -> HealthResponse {
+        health::liveness().await.into()
+    }
+
+    /// Fails when the service cannot serve requests, which additionally
+    /// covers `STORAGE_DIR` or `LOG_DIR` not being writable.
+    #[oai(path = "/health/ready", method = "get")]
+    async fn ready(&self) -> HealthResponse {
+        health::readiness().await.into()
+    }
+}
@@ -2,31 +2,65 @@ use poem_openapi::{OpenApi, param::Query, payload::Json};
 use uuid::Uuid;
 
 use crate::{
-    api::ApiResult,
-    database::{self},
+    api::{ApiResult, GenericResponse},
+    constant::env::ENV_NAME_ENROLLMENT_TOKEN,
+    database::{robot::EnrollmentStatus, with_database},
+    utils::credentials::hash_token,
 };
 
 pub mod sync;
 pub mod whoami;
 
+/// Whether `token` is the configured enrollment token. Without a
+/// configured token every robot has to be approved by an admin.
+fn enrollment_token_matches(token: Option<&str>) -> bool {
+    let Ok(expected) = std::env::var(ENV_NAME_ENROLLMENT_TOKEN) else {
+        return false;
+    };
+    // Comparing digests keeps the comparison time independent of how much
+    // of the token is right.
+    !expected.is_empty()
+        && token.is_some_and(|token| hash_token(token) == hash_token(&expected))
+}
+
 pub struct IdentApi;
 
 #[OpenApi]
 impl IdentApi {
     /// `whoami` is used to get a valid robot ID based on the provided MAC address.
+    /// A device that has registered before, identified by its machine ID or
+    /// MAC address, gets its existing ID back.
     /// The validation is not done here.
     #[oai(path = "/ident/whoami", method = "post")]
-    #[allow(clippy::unused_async)]
     async fn whoami(
         &self,
         info: Json<whoami::WhoAmI>,
-    ) -> Json<whoami::WhoAmIResponse> {
+    ) -> ApiResult<whoami::WhoAmIResponse> {
+        if let Some(robot) = with_database(|db| {
+            db.find_robot_by_identity(
+                &info.mac,
+                info.machine_id.as_deref(),
+                Some(&info.username),
+            )
+        })?
+        .await?
+        {
+            log::info!(
+                "Robot with MAC {} is already registered as {}",
+                info.mac,
+                robot.uuid
+            );
+            return Ok(Json(whoami::WhoAmIResponse {
+                robot_uuid: robot.uuid,
+                robot_name: robot.name,
+            }));
+        }
         let uuid = Uuid::new_v4().to_string();
         let robot_name = format!("robot_{}_{}", info.username, info.mac);
-        Json(whoami::WhoAmIResponse {
+        Ok(Json(whoami::WhoAmIResponse {
             robot_uuid: uuid,
             robot_name,
-        })
+        }))
     }
 
     /// The `sync` endpoint allows a robot to register itself with the server.
@@ -37,26 +71,109 @@ impl IdentApi {
         &self,
         info: Json<sync::Sync>,
     ) -> ApiResult<sync::SyncResponse> {
-        let db = database::get_database()?;
-        if let Err(e) =
-            db.register_robot(&info.mac, &info.name, &info.uuid).await
+        let approve =
+            enrollment_token_matches(info.enrollment_token.as_deref());
+        // A robot merged into another one still syncs with its old UUID.
+        let uuid =
+            with_database(|db| db.resolve_robot_uuid(&info.uuid))?.await?;
+        // A merged robot's bot keeps the secret it held before the merge.
+        let alias_secret =
+            with_database(|db| db.get_alias_secret(&info.uuid))?.await?;
+        let secret = match &alias_secret {
+            Some(secret) => Some(secret.clone()),
+            None => with_database(|db| db.get_robot_secret(&uuid))?.await?,
+        };
+        // Once a robot holds a secret, knowing its UUID is not enough to
+        // sync as it.
+        if let Some(secret) = secret
+            && secret.delivered
+            && info
+                .robot_secret
+                .as_deref()
+                .is_none_or(|presented| hash_token(presented) != secret.hash)
+        {
+            return Err(GenericResponse::forbidden(format!(
+                "Robot {uuid} has to present its robot secret"
+            )));
+        }
+        match with_database(|db| {
+            db.register_robot(
+                &info.mac,
+                &info.name,
+                &uuid,
+                info.machine_id.as_deref(),
+                approve,
+            )
+        })?
+        .await
         {
-            log::error!("Failed to register robot: {e}");
-            return Ok(Json(sync::SyncResponse { success: false }));
+            Ok(enrollment) => {
+                if enrollment == EnrollmentStatus::Pending {
+                    log::info!(
+                        "Robot {uuid} ({}) is awaiting enrollment approval",
+                        info.name
+                    );
+                }
+                // Robots approved by the enrollment token, or before
+                // secrets existed, get theirs now.
+                if enrollment == EnrollmentStatus::Approved
+                    && with_database(|db| db.get_robot_secret(&uuid))?
+                        .await?
+                        .is_none()
+                {
+                    with_database(|db| db.issue_robot_secret(&uuid))?.await?;
+                }
+                Ok(Json(sync::SyncResponse {
+                    success: true,
+                    enrollment: Some(enrollment),
+                    robot_secret: if alias_secret.is_some() {
+                        with_database(|db| db.take_alias_secret(&info.uuid))?
+                            .await?
+                    } else {
+                        with_database(|db| db.take_robot_secret(&uuid))?.await?
+                    },
+                }))
+            }
+            Err(e) => {
+                log::error!("Failed to register robot: {e}");
+                Ok(Json(sync::SyncResponse {
+                    success: false,
+                    enrollment: None,
+                    robot_secret: None,
+                }))
+            }
         }
-        Ok(Json(sync::SyncResponse { success: true }))
     }
 
     /// The `retrieve` endpoint allows fetching robot information by robot ID.
     /// This is used for robots to verify their registration status.
+    /// Robots are matched by machine ID or MAC address, preferring those
+    /// whose name contains `username`, and otherwise by name alone.
     #[oai(path = "/ident/retrieve", method = "get")]
     async fn retrieve(
         &self,
         Query(username): Query<String>,
         Query(mac_address): Query<String>,
+        Query(machine_id): Query<Option<String>>,
     ) -> ApiResult<Option<sync::RetrieveResponse>> {
-        let db = database::get_database()?;
-        match db.fuzz_search_by_name(&username, &mac_address).await {
+        let found = async {
+            let robot = with_database(|db| {
+                db.find_robot_by_identity(
+                    &mac_address,
+                    machine_id.as_deref(),
+                    Some(&username),
+                )
+            })?
+            .await?;
+            if robot.is_some() {
+                return anyhow::Ok(robot);
+            }
+            Ok(with_database(|db| {
+                db.find_robot_by_name(&username, machine_id.as_deref())
+            })?
+            .await?)
+        };
+        match found.await {
             Ok(Some(robot)) => Ok(Json(Some(sync::RetrieveResponse {
                 mac: robot.mac,
                 name: robot.name,
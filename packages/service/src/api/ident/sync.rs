@@ -1,16 +1,35 @@
 use poem_openapi::Object;
 use serde::{Deserialize, Serialize};
 
+use crate::database::robot::EnrollmentStatus;
+
 #[derive(Serialize, Deserialize, Object, Debug, Clone)]
 pub struct Sync {
     pub mac: String,
     pub name: String,
     pub uuid: String,
+    /// Stable identifier of the installation, such as `/etc/machine-id`.
+    pub machine_id: Option<String>,
+    /// Pre-shared enrollment token that approves the robot without waiting
+    /// for an admin.
+    #[oai(write_only)]
+    pub enrollment_token: Option<String>,
+    /// Secret handed out in an earlier response. Required once the robot
+    /// has picked one up.
+    #[oai(write_only)]
+    pub robot_secret: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Object, Debug, Clone)]
 pub struct SyncResponse {
     pub success: bool,
+    /// Unset when the registration failed.
+    pub enrollment: Option<EnrollmentStatus>,
+    /// Secret to present when connecting to `/ws`, sent once after the
+    /// robot is approved. The robot has to keep it.
+    #[oai(skip_serializing_if_is_none)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub robot_secret: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Object, Debug, Clone)]
@@ -5,6 +5,9 @@ use serde::{Deserialize, Serialize};
 pub struct WhoAmI {
     pub username: String,
     pub mac: String,
+    /// Stable identifier of the installation, such as `/etc/machine-id`.
+    /// Tells apart devices sharing a MAC address.
+    pub machine_id: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Object, Debug, Clone)]
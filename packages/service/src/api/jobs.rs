@@ -0,0 +1,88 @@
+use poem_openapi::{Object, OpenApi, param::Path, payload::Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        ApiResult, GenericResponse,
+        auth::{Auth, scope::check_robot_scope},
+    },
+    database::{
+        job::Job,
+        user::{User, UserRole},
+        with_database,
+    },
+    service::jobs,
+};
+
+/// Returned instead of the action result when an action is started as a
+/// job.
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct JobAccepted {
+    pub job_id: String,
+}
+
+fn job_not_found(id: &str) -> GenericResponse {
+    GenericResponse::not_found(format!("No job found with ID: {id}"))
+}
+
+/// Fails unless `user` may act on every robot of `job`.
+async fn check_job_scope(
+    user: &User,
+    job: &Job,
+) -> Result<(), GenericResponse> {
+    for robot in &job.robots {
+        check_robot_scope(user, &robot.robot_id).await?;
+    }
+    Ok(())
+}
+
+/// Progress and cancellation of actions started with `async=true`.
+pub struct JobsApi;
+
+#[OpenApi]
+impl JobsApi {
+    /// Scoped users may only read jobs whose robots are all in their
+    /// scope.
+    #[oai(path = "/jobs/:id", method = "get")]
+    async fn get_job(
+        &self,
+        auth: Auth,
+        Path(id): Path<String>,
+    ) -> ApiResult<Job> {
+        let job = with_database(|db| db.get_job(&id))?
+            .await?
+            .ok_or_else(|| job_not_found(&id))?;
+        check_job_scope(auth.user(), &job).await?;
+        Ok(Json(job))
+    }
+
+    /// Cancels a pending or running job, closing the sessions of robots
+    /// that are still executing its instruction.
+    #[oai(path = "/jobs/:id/cancel", method = "post")]
+    async fn cancel_job(
+        &self,
+        auth: Auth,
+        Path(id): Path<String>,
+    ) -> ApiResult<Job> {
+        let user = auth.require(UserRole::Operator)?;
+        if user.is_scoped() {
+            let job = with_database(|db| db.get_job(&id))?
+                .await?
+                .ok_or_else(|| job_not_found(&id))?;
+            check_job_scope(user, &job).await?;
+        }
+        let cancelled = with_database(|db| db.cancel_job(&id))?.await?;
+        if cancelled {
+            jobs::cancel(&id);
+        }
+        let job = with_database(|db| db.get_job(&id))?
+            .await?
+            .ok_or_else(|| job_not_found(&id))?;
+        if !cancelled {
+            return Err(GenericResponse::conflict(format!(
+                "Job {id} has already ended"
+            )));
+        }
+        Ok(Json(job))
+    }
+}
@@ -0,0 +1,247 @@
+use poem_openapi::{OpenApi, payload::Json};
+
+use crate::{
+    api::{
+        ApiResult, GenericResponse,
+        auth::{Auth, scope::check_robot_scope},
+    },
+    database::{
+        Database,
+        robot::{RobotProfile, normalize_tags},
+        user::UserRole,
+        with_database,
+    },
+    service::CONNECTIONS,
+};
+
+pub mod decommission;
+pub mod merge_robots;
+pub mod set_robot_metadata;
+
+fn robot_not_found(uuid: &str) -> GenericResponse {
+    GenericResponse::not_found(format!("No robot found with UUID: {uuid}"))
+}
+
+/// Closes the live connection of a robot that was archived or deleted.
+fn disconnect_robot(uuid: &str) {
+    if let Some(conn) = CONNECTIONS.get(uuid) {
+        conn.value().set_approved(false);
+        conn.value().disconnect();
+    }
+}
+
+/// Editing of fleet metadata kept by the service only, which unlike the
+/// robot name is never synchronised to the robot itself.
+pub struct RegistryApi;
+
+#[OpenApi]
+impl RegistryApi {
+    #[oai(path = "/registry/set_robot_role", method = "post")]
+    async fn set_robot_role(
+        &self,
+        auth: Auth,
+        request: Json<set_robot_metadata::SetRobotRoleRequest>,
+    ) -> ApiResult<set_robot_metadata::SetRobotMetadataResponse> {
+        let user = auth.require(UserRole::Operator)?;
+        check_robot_scope(user, &request.robot_uuid).await?;
+        let found = with_database(|db| {
+            db.set_robot_role(&request.robot_uuid, request.role)
+        })?
+        .await?;
+        if !found {
+            return Err(robot_not_found(&request.robot_uuid));
+        }
+        Ok(Json(set_robot_metadata::SetRobotMetadataResponse))
+    }
+
+    #[oai(path = "/registry/set_robot_team_color", method = "post")]
+    async fn set_robot_team_color(
+        &self,
+        auth: Auth,
+        request: Json<set_robot_metadata::SetRobotTeamColorRequest>,
+    ) -> ApiResult<set_robot_metadata::SetRobotMetadataResponse> {
+        let user = auth.require(UserRole::Operator)?;
+        check_robot_scope(user, &request.robot_uuid).await?;
+        let found = with_database(|db| {
+            db.set_robot_team_color(&request.robot_uuid, request.team_color)
+        })?
+        .await?;
+        if !found {
+            return Err(robot_not_found(&request.robot_uuid));
+        }
+        Ok(Json(set_robot_metadata::SetRobotMetadataResponse))
+    }
+
+    #[oai(path = "/registry/set_robot_tags", method = "post")]
+    async fn set_robot_tags(
+        &self,
+        auth: Auth,
+        request: Json<set_robot_metadata::SetRobotTagsRequest>,
+    ) -> ApiResult<set_robot_metadata::SetRobotTagsResponse> {
+        let user = auth.require(UserRole::Operator)?;
+        check_robot_scope(user, &request.robot_uuid).await?;
+        let tags = normalize_tags(&request.tags);
+        let found =
+            with_database(|db| db.set_robot_tags(&request.robot_uuid, &tags))?
+                .await?;
+        if !found {
+            return Err(robot_not_found(&request.robot_uuid));
+        }
+        Ok(Json(set_robot_metadata::SetRobotTagsResponse { tags }))
+    }
+
+    /// Merges duplicate robot records into one and deletes the
+    /// duplicates. Bots still using a duplicate's UUID are served as the
+    /// remaining robot.
+    #[oai(path = "/registry/merge_robots", method = "post")]
+    async fn merge_robots(
+        &self,
+        auth: Auth,
+        request: Json<merge_robots::MergeRobotsRequest>,
+    ) -> ApiResult<RobotProfile> {
+        auth.require(UserRole::Admin)?;
+        let mut duplicates = request.duplicate_uuids.clone();
+        duplicates.sort();
+        duplicates.dedup();
+        if duplicates.is_empty() {
+            return Err(GenericResponse::validation_failed(
+                "No duplicate robots given",
+            ));
+        }
+        if duplicates.contains(&request.robot_uuid) {
+            return Err(GenericResponse::validation_failed(format!(
+                "Robot {} cannot be merged into itself",
+                request.robot_uuid
+            )));
+        }
+        for uuid in duplicates.iter().chain([&request.robot_uuid]) {
+            if with_database(|db| db.get_robot_enrollment(uuid))?
+                .await?
+                .is_none()
+            {
+                return Err(robot_not_found(uuid));
+            }
+        }
+
+        with_database(|db| db.merge_robots(&request.robot_uuid, &duplicates))?
+            .await?;
+        log::info!(
+            "Merged robots {} into {}",
+            duplicates.join(", "),
+            request.robot_uuid
+        );
+        // Their bots reconnect under the remaining robot.
+        for uuid in &duplicates {
+            if let Some(conn) = CONNECTIONS.get(uuid) {
+                conn.value().disconnect();
+            }
+        }
+        with_database(|db| db.get_robot_profile(&request.robot_uuid))?
+            .await?
+            .map(Json)
+            .ok_or_else(|| robot_not_found(&request.robot_uuid))
+    }
+
+    #[oai(path = "/registry/archived_robots", method = "get")]
+    async fn archived_robots(
+        &self,
+        _auth: Auth,
+    ) -> ApiResult<Vec<RobotProfile>> {
+        Ok(Json(
+            with_database(Database::get_archived_robot_profiles)?.await?,
+        ))
+    }
+
+    /// Decommissions a robot while keeping its history. Its enrollment is
+    /// revoked and its connection closed.
+    #[oai(path = "/registry/archive_robot", method = "post")]
+    async fn archive_robot(
+        &self,
+        auth: Auth,
+        request: Json<decommission::ArchiveRobotRequest>,
+    ) -> ApiResult<RobotProfile> {
+        auth.require(UserRole::Admin)?;
+        let Some(profile) =
+            with_database(|db| db.get_robot_profile(&request.robot_uuid))?
+                .await?
+        else {
+            return Err(robot_not_found(&request.robot_uuid));
+        };
+        if profile.archived_at.is_some() {
+            return Err(GenericResponse::conflict(format!(
+                "Robot {} is already archived",
+                request.robot_uuid
+            )));
+        }
+        with_database(|db| db.archive_robot(&request.robot_uuid))?.await?;
+        disconnect_robot(&request.robot_uuid);
+        log::info!("Archived robot {}", request.robot_uuid);
+        with_database(|db| db.get_robot_profile(&request.robot_uuid))?
+            .await?
+            .map(Json)
+            .ok_or_else(|| robot_not_found(&request.robot_uuid))
+    }
+
+    /// Returns an archived robot to the fleet. It has to be approved again
+    /// before it may connect.
+    #[oai(path = "/registry/unarchive_robot", method = "post")]
+    async fn unarchive_robot(
+        &self,
+        auth: Auth,
+        request: Json<decommission::ArchiveRobotRequest>,
+    ) -> ApiResult<RobotProfile> {
+        auth.require(UserRole::Admin)?;
+        if !with_database(|db| db.unarchive_robot(&request.robot_uuid))?.await?
+        {
+            return Err(
+                match with_database(|db| {
+                    db.get_robot_profile(&request.robot_uuid)
+                })?
+                .await?
+                {
+                    Some(_) => GenericResponse::conflict(format!(
+                        "Robot {} is not archived",
+                        request.robot_uuid
+                    )),
+                    None => robot_not_found(&request.robot_uuid),
+                },
+            );
+        }
+        log::info!("Restored archived robot {}", request.robot_uuid);
+        with_database(|db| db.get_robot_profile(&request.robot_uuid))?
+            .await?
+            .map(Json)
+            .ok_or_else(|| robot_not_found(&request.robot_uuid))
+    }
+
+    /// Removes a robot from the registry and closes its connection. A
+    /// deleted robot that syncs again registers as a new, pending robot.
+    #[oai(path = "/registry/delete_robot", method = "post")]
+    async fn delete_robot(
+        &self,
+        auth: Auth,
+        request: Json<decommission::DeleteRobotRequest>,
+    ) -> ApiResult<RobotProfile> {
+        auth.require(UserRole::Admin)?;
+        let Some(profile) =
+            with_database(|db| db.get_robot_profile(&request.robot_uuid))?
+                .await?
+        else {
+            return Err(robot_not_found(&request.robot_uuid));
+        };
+        if !with_database(|db| {
+            db.delete_robot(&request.robot_uuid, request.history)
+        })?
+        .await?
+        {
+            return Err(robot_not_found(&request.robot_uuid));
+        }
+        disconnect_robot(&request.robot_uuid);
+        log::info!(
+            "Deleted robot {} ({:?} history)",
+            request.robot_uuid,
+            request.history
+        );
+        Ok(Json(profile))
+    }
+}
@@ -0,0 +1,20 @@
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+
+use crate::database::robot::RobotHistory;
+
+/// Archives or restores a robot.
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct ArchiveRobotRequest {
+    pub robot_uuid: String,
+}
+
+/// Deletes a robot, by default keeping its connection history and job
+/// results.
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct DeleteRobotRequest {
+    pub robot_uuid: String,
+    #[oai(default)]
+    #[serde(default)]
+    pub history: RobotHistory,
+}
@@ -0,0 +1,10 @@
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+
+/// Folds duplicate records of one robot, such as those left by
+/// reinstalling it, into the record at `robot_uuid`.
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct MergeRobotsRequest {
+    pub robot_uuid: String,
+    pub duplicate_uuids: Vec<String>,
+}
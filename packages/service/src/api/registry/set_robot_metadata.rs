@@ -0,0 +1,34 @@
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+
+use crate::database::robot::{RobotRole, TeamColor};
+
+/// Sets or, with a `null` role, clears the role of a robot.
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct SetRobotRoleRequest {
+    pub robot_uuid: String,
+    pub role: Option<RobotRole>,
+}
+
+/// Sets or, with a `null` colour, clears the team colour of a robot.
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct SetRobotTeamColorRequest {
+    pub robot_uuid: String,
+    pub team_color: Option<TeamColor>,
+}
+
+/// Replaces all tags of a robot.
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct SetRobotTagsRequest {
+    pub robot_uuid: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct SetRobotMetadataResponse;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct SetRobotTagsResponse {
+    /// The stored tags, trimmed and deduplicated.
+    pub tags: Vec<String>,
+}
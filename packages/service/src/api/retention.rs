@@ -0,0 +1,44 @@
+use poem_openapi::{OpenApi, payload::Json};
+
+use crate::{
+    api::{ApiResult, GenericResponse, auth::Auth},
+    database::user::UserRole,
+    service::retention::{
+        RETENTION_SERVICE, RetentionRun, RetentionService, RetentionStatus,
+    },
+};
+
+fn retention_service() -> anyhow::Result<&'static RetentionService> {
+    RETENTION_SERVICE
+        .get()
+        .ok_or_else(|| anyhow::anyhow!("Retention service is not initialized"))
+}
+
+/// Pruning of connection history, finished jobs and old audit entries.
+pub struct RetentionApi;
+
+#[OpenApi]
+impl RetentionApi {
+    /// Reports the retention policies and the outcome of the last run.
+    #[oai(path = "/retention/status", method = "get")]
+    async fn status(&self, auth: Auth) -> ApiResult<RetentionStatus> {
+        auth.require(UserRole::Admin)?;
+        Ok(Json(retention_service()?.status().await))
+    }
+
+    /// Prunes history right away and returns the outcome. Fails if a run
+    /// is already in progress.
+    #[oai(path = "/retention/run", method = "post")]
+    async fn run(&self, auth: Auth) -> ApiResult<RetentionRun> {
+        auth.require(UserRole::Admin)?;
+        retention_service()?
+            .try_run()
+            .await
+            .map(Json)
+            .ok_or_else(|| {
+                GenericResponse::conflict(
+                    "A retention run is already in progress",
+                )
+            })
+    }
+}
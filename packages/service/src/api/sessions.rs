@@ -0,0 +1,113 @@
+use chrono::{DateTime, Utc};
+use poem_openapi::{Object, OpenApi, param::Path, payload::Json};
+use uuid::Uuid;
+
+use crate::{
+    api::{
+        ApiResult, GenericResponse,
+        auth::{Auth, scope::check_robot_scope},
+    },
+    database::user::UserRole,
+    service::{
+        CONNECTIONS,
+        connection::{OperationLock, Session, SessionKind, SessionState},
+    },
+};
+
+#[derive(Debug, Clone, Object)]
+pub struct RobotSession {
+    pub session_id: Uuid,
+    pub kind: SessionKind,
+    /// Name of the instruction or event.
+    pub name: String,
+    pub state: SessionState,
+    pub started_at: DateTime<Utc>,
+    /// Time since the session was opened, in milliseconds.
+    pub age_ms: i64,
+}
+
+impl RobotSession {
+    fn new(session_id: Uuid, session: &Session) -> Self {
+        Self {
+            session_id,
+            kind: session.kind,
+            name: session.name.to_string(),
+            state: session.state(),
+            started_at: session.started_at,
+            age_ms: (Utc::now() - session.started_at).num_milliseconds(),
+        }
+    }
+}
+
+fn session_not_found(robot_uuid: &str, session_id: Uuid) -> GenericResponse {
+    GenericResponse::not_found(format!(
+        "No session {session_id} is open on robot {robot_uuid}"
+    ))
+}
+
+/// Sessions and operation locks of robot connections.
+pub struct SessionsApi;
+
+#[OpenApi]
+impl SessionsApi {
+    /// Lists the open sessions of a connected robot, oldest first.
+    #[oai(path = "/robots/:uuid/sessions", method = "get")]
+    #[allow(clippy::unused_async)]
+    async fn list_sessions(
+        &self,
+        _auth: Auth,
+        Path(uuid): Path<String>,
+    ) -> ApiResult<Vec<RobotSession>> {
+        let connection = CONNECTIONS
+            .get(&uuid)
+            .ok_or_else(|| GenericResponse::robot_offline(&uuid))?;
+        let mut sessions: Vec<_> = connection
+            .sessions
+            .iter()
+            .map(|entry| RobotSession::new(*entry.key(), entry.value()))
+            .collect();
+        sessions.sort_by_key(|session| session.started_at);
+        Ok(Json(sessions))
+    }
+
+    /// Returns the robot's operation lock, or `null` if no mutating
+    /// instruction is running on it.
+    #[oai(path = "/robots/:uuid/lock", method = "get")]
+    #[allow(clippy::unused_async)]
+    async fn get_lock(
+        &self,
+        _auth: Auth,
+        Path(uuid): Path<String>,
+    ) -> ApiResult<Option<OperationLock>> {
+        let connection = CONNECTIONS
+            .get(&uuid)
+            .ok_or_else(|| GenericResponse::robot_offline(&uuid))?;
+        Ok(Json(connection.operation_lock()))
+    }
+
+    /// Cancels an open session: the robot is told to close it and an
+    /// instruction waiting on it fails with `instruction_cancelled`.
+    #[oai(path = "/robots/:uuid/sessions/:session_id/cancel", method = "post")]
+    async fn cancel_session(
+        &self,
+        auth: Auth,
+        Path(uuid): Path<String>,
+        Path(session_id): Path<Uuid>,
+    ) -> ApiResult<RobotSession> {
+        let user = auth.require(UserRole::Operator)?;
+        check_robot_scope(user, &uuid).await?;
+        let connection = CONNECTIONS
+            .get(&uuid)
+            .map(|entry| entry.value().clone())
+            .ok_or_else(|| GenericResponse::robot_offline(&uuid))?;
+        let session = connection
+            .sessions
+            .get(&session_id)
+            .map(|session| RobotSession::new(session_id, &session))
+            .ok_or_else(|| session_not_found(&uuid, session_id))?;
+        if !connection.cancel_session(session_id) {
+            return Err(session_not_found(&uuid, session_id));
+        }
+        Ok(Json(session))
+    }
+}
@@ -1,65 +1,144 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+
+use futures_util::{
+    StreamExt,
+    stream::{self, BoxStream},
+};
 use poem_openapi::{
     OpenApi,
-    param::Path,
-    payload::{Json, PlainText},
+    param::{Path, Query},
+    payload::{EventStream, Json},
 };
+use tokio::sync::broadcast::error::RecvError;
 
 use crate::{
-    api::{ApiResult, GenericResponse},
-    database::{robot::RobotIdent, with_database},
+    api::{ApiResult, GenericResponse, auth::Auth},
+    database::{
+        Database,
+        robot::{RobotFilter, RobotProfile, RobotRole, TeamColor},
+        with_database,
+    },
+    service::fleet_events::{self, FleetEvent},
+    utils::network::IgnoredInterfaces,
 };
 
+pub mod address_conflicts;
 pub mod get_robot_network_stats;
+pub mod ip_lookup;
+pub mod robot_list;
+pub mod uptime;
 
 pub struct StatsApi;
 
 #[OpenApi]
 impl StatsApi {
     #[oai(path = "/stats/robots", method = "get")]
-    async fn get_registered_robots(&self) -> ApiResult<Vec<String>> {
-        let robots = crate::database::with_database(
-            crate::database::Database::get_robots,
-        )?
-        .await
-        .map_err(|e| {
-            poem::Error::from_string(
-                format!("Failed to fetch robots: {e}"),
-                poem::http::StatusCode::INTERNAL_SERVER_ERROR,
-            )
-        })?;
-        Ok(Json(robots))
+    async fn get_registered_robots(
+        &self,
+        _auth: Auth,
+        Query(role): Query<Vec<RobotRole>>,
+        Query(team_color): Query<Option<TeamColor>>,
+        Query(tag): Query<Vec<String>>,
+    ) -> ApiResult<Vec<String>> {
+        let filter = RobotFilter {
+            roles: role,
+            team_color,
+            tags: tag,
+        };
+        let robots = with_database(|db| db.filter_robots(&filter))?.await?;
+        Ok(Json(
+            robots.into_iter().map(|robot| robot.ident.uuid).collect(),
+        ))
+    }
+
+    /// Registered robots with their connection state, version and latest
+    /// network info, sorted and paginated.
+    #[oai(path = "/stats/robot_list", method = "get")]
+    #[allow(clippy::too_many_arguments)]
+    async fn get_robot_list(
+        &self,
+        _auth: Auth,
+        Query(role): Query<Vec<RobotRole>>,
+        Query(team_color): Query<Option<TeamColor>>,
+        Query(tag): Query<Vec<String>>,
+        Query(online): Query<Option<bool>>,
+        Query(search): Query<Option<String>>,
+        Query(sort): Query<Option<robot_list::RobotSortKey>>,
+        Query(order): Query<Option<robot_list::SortOrder>>,
+        Query(offset): Query<Option<u32>>,
+        Query(limit): Query<Option<u32>>,
+    ) -> ApiResult<robot_list::RobotListResponse> {
+        let query = robot_list::RobotListQuery {
+            filter: RobotFilter {
+                roles: role,
+                team_color,
+                tags: tag,
+            },
+            online,
+            search: search
+                .map(|search| search.trim().to_string())
+                .filter(|search| !search.is_empty()),
+            sort: sort.unwrap_or_default(),
+            order: order.unwrap_or_default(),
+            offset: offset.unwrap_or(0),
+            limit: limit
+                .unwrap_or(robot_list::DEFAULT_LIMIT)
+                .min(robot_list::MAX_LIMIT),
+        };
+        Ok(Json(robot_list::list_robots(&query).await?))
     }
 
     #[oai(path = "/stats/online_robots", method = "get")]
-    #[allow(clippy::unused_async)]
-    async fn get_online_robots(&self) -> ApiResult<Vec<String>> {
-        let online_robots: Vec<String> = crate::service::CONNECTIONS
+    async fn get_online_robots(
+        &self,
+        _auth: Auth,
+        Query(role): Query<Vec<RobotRole>>,
+        Query(team_color): Query<Option<TeamColor>>,
+        Query(tag): Query<Vec<String>>,
+    ) -> ApiResult<Vec<String>> {
+        let filter = RobotFilter {
+            roles: role,
+            team_color,
+            tags: tag,
+        };
+        let mut online_robots: Vec<String> = crate::service::CONNECTIONS
             .iter()
             .map(|entry| entry.key().clone())
             .collect();
+        if !filter.is_empty() {
+            let matching: HashSet<String> =
+                with_database(|db| db.filter_robots(&filter))?
+                    .await?
+                    .into_iter()
+                    .map(|robot| robot.ident.uuid)
+                    .collect();
+            online_robots.retain(|uuid| matching.contains(uuid));
+        }
         Ok(Json(online_robots))
     }
 
     #[oai(path = "/stats/robot/:uuid", method = "get")]
     async fn get_robot(
         &self,
+        _auth: Auth,
         Path(uuid): Path<String>,
-    ) -> ApiResult<Option<RobotIdent>> {
-        let robot =
-            crate::database::with_database(|db| db.get_robot_by_id(&uuid))?
-                .await
-                .map_err(|e| {
-                    poem::Error::from_string(
-                        format!("Failed to fetch robot: {e}"),
-                        poem::http::StatusCode::INTERNAL_SERVER_ERROR,
-                    )
-                })?;
+    ) -> ApiResult<RobotProfile> {
+        let robot = with_database(|db| db.get_robot_profile(&uuid))?
+            .await?
+            .ok_or_else(|| {
+                GenericResponse::not_found(format!(
+                    "No robot found with UUID: {uuid}"
+                ))
+            })?;
         Ok(Json(robot))
     }
 
     #[oai(path = "/stats/robot/:uuid/network", method = "get")]
     async fn get_robot_network_stats(
         &self,
+        _auth: Auth,
         Path(uuid): Path<String>,
     ) -> ApiResult<get_robot_network_stats::RobotNetworkStatsResponse> {
         let stats = with_database(|db| db.get_network_info(&uuid))?.await?;
@@ -70,9 +149,125 @@ impl StatsApi {
                 last_updated: network_info.last_updated,
             }))
         } else {
-            Err(GenericResponse::NotFound(PlainText(format!(
+            Err(GenericResponse::not_found(format!(
                 "No network info found for robot with UUID: {uuid}"
-            ))))
+            )))
         }
     }
+
+    /// Connections of a robot overlapping a time range, with when and why
+    /// each of them ended. Defaults to the last 24 hours.
+    #[oai(path = "/stats/robot/:uuid/connections", method = "get")]
+    async fn get_robot_connections(
+        &self,
+        _auth: Auth,
+        Path(uuid): Path<String>,
+        Query(from): Query<Option<DateTime<Utc>>>,
+        Query(to): Query<Option<DateTime<Utc>>>,
+    ) -> ApiResult<uptime::ConnectionTimelineResponse> {
+        let (from, to) = uptime::resolve_range(from, to, Utc::now())
+            .map_err(GenericResponse::validation_failed)?;
+        let sessions = with_database(|db| {
+            db.get_connection_sessions(Some(&uuid), from, to)
+        })?
+        .await?;
+        Ok(Json(uptime::ConnectionTimelineResponse {
+            robot_uuid: uuid,
+            from,
+            to,
+            sessions,
+        }))
+    }
+
+    /// Share of a time range each registered robot was connected, and how
+    /// often its connection dropped. Defaults to the last 24 hours.
+    #[oai(path = "/stats/uptime", method = "get")]
+    async fn get_uptime(
+        &self,
+        _auth: Auth,
+        Query(from): Query<Option<DateTime<Utc>>>,
+        Query(to): Query<Option<DateTime<Utc>>>,
+        Query(role): Query<Vec<RobotRole>>,
+        Query(team_color): Query<Option<TeamColor>>,
+        Query(tag): Query<Vec<String>>,
+    ) -> ApiResult<uptime::UptimeResponse> {
+        let (from, to) = uptime::resolve_range(from, to, Utc::now())
+            .map_err(GenericResponse::validation_failed)?;
+        let filter = RobotFilter {
+            roles: role,
+            team_color,
+            tags: tag,
+        };
+        let robots = with_database(|db| db.filter_robots(&filter))?.await?;
+        let sessions =
+            with_database(|db| db.get_connection_sessions(None, from, to))?
+                .await?;
+        Ok(Json(uptime::UptimeResponse {
+            from,
+            to,
+            robots: uptime::compute_uptime(robots, &sessions, from, to),
+        }))
+    }
+
+    /// Finds the robots whose latest network info holds the given IP
+    /// address. More than one match means the address is in conflict.
+    #[oai(path = "/stats/lookup", method = "get")]
+    async fn lookup_ip(
+        &self,
+        _auth: Auth,
+        Query(ip): Query<String>,
+    ) -> ApiResult<ip_lookup::IpLookupResponse> {
+        let Ok(ip) = ip.trim().parse::<std::net::IpAddr>() else {
+            return Err(GenericResponse::validation_failed(format!(
+                "Invalid IP address: {ip}"
+            )));
+        };
+        let ip = ip.to_string();
+        let rows = with_database(|db| db.lookup_ip(&ip))?.await?;
+        Ok(Json(ip_lookup::IpLookupResponse {
+            ip,
+            matches: rows.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    /// Lists IPv4 and MAC addresses reported by more than one robot.
+    #[oai(path = "/stats/conflicts", method = "get")]
+    async fn get_address_conflicts(
+        &self,
+        _auth: Auth,
+    ) -> ApiResult<address_conflicts::AddressConflictsResponse> {
+        let ipv4_rows =
+            with_database(Database::get_indexed_ipv4_addresses)?.await?;
+        let mac_rows =
+            with_database(Database::get_indexed_hardware_addresses)?.await?;
+        let ignored = IgnoredInterfaces::from_env()?;
+        Ok(Json(address_conflicts::AddressConflictsResponse::new(
+            ipv4_rows, mac_rows, &ignored,
+        )))
+    }
+
+    /// Server-sent stream of fleet events, such as robots reporting
+    /// network changes.
+    #[oai(path = "/stats/events", method = "get")]
+    #[allow(clippy::unused_async)]
+    async fn get_fleet_events(
+        &self,
+        _auth: Auth,
+    ) -> EventStream<BoxStream<'static, FleetEvent>> {
+        let receiver = fleet_events::subscribe();
+        EventStream::new(stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!(
+                            "Fleet event subscriber lagged, skipped {skipped} events"
+                        );
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .boxed())
+    }
 }
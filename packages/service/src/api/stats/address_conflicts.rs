@@ -0,0 +1,97 @@
+use std::net::IpAddr;
+
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::network::IndexedAddressRow,
+    utils::network::{IgnoredInterfaces, is_reachable_ip},
+};
+
+/// An interface of a robot holding a conflicting address.
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct AddressHolder {
+    pub robot_uuid: String,
+    pub robot_name: String,
+    pub interface: String,
+}
+
+/// An address reported by more than one robot.
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct AddressConflict {
+    pub address: String,
+    pub holders: Vec<AddressHolder>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct AddressConflictsResponse {
+    pub ipv4: Vec<AddressConflict>,
+    pub mac: Vec<AddressConflict>,
+}
+
+fn is_shared_ipv4(address: &str) -> bool {
+    address
+        .parse::<IpAddr>()
+        .is_ok_and(|ip| !is_reachable_ip(ip))
+}
+
+fn is_shared_mac(address: &str) -> bool {
+    address.chars().all(|c| c == '0' || c == ':')
+}
+
+/// Groups rows sorted by address and keeps the addresses held by more
+/// than one robot.
+fn find_conflicts(
+    rows: Vec<IndexedAddressRow>,
+    ignored: &IgnoredInterfaces,
+    is_shared: fn(&str) -> bool,
+) -> Vec<AddressConflict> {
+    let mut conflicts: Vec<AddressConflict> = Vec::new();
+    let mut current: Option<AddressConflict> = None;
+
+    let rows = rows.into_iter().filter(|row| {
+        !ignored.contains(&row.interface) && !is_shared(&row.address)
+    });
+    for row in rows {
+        let holder = AddressHolder {
+            robot_uuid: row.robot_uuid,
+            robot_name: row.robot_name,
+            interface: row.interface,
+        };
+        match &mut current {
+            Some(conflict) if conflict.address == row.address => {
+                conflict.holders.push(holder);
+            }
+            _ => {
+                conflicts.extend(current.take());
+                current = Some(AddressConflict {
+                    address: row.address,
+                    holders: vec![holder],
+                });
+            }
+        }
+    }
+    conflicts.extend(current);
+
+    conflicts.retain(|conflict| {
+        let first = &conflict.holders[0].robot_uuid;
+        conflict
+            .holders
+            .iter()
+            .any(|holder| &holder.robot_uuid != first)
+    });
+    conflicts
+}
+
+impl AddressConflictsResponse {
+    pub fn new(
+        ipv4_rows: Vec<IndexedAddressRow>,
+        mac_rows: Vec<IndexedAddressRow>,
+        ignored: &IgnoredInterfaces,
+    ) -> Self {
+        Self {
+            ipv4: find_conflicts(ipv4_rows, ignored, is_shared_ipv4),
+            mac: find_conflicts(mac_rows, ignored, is_shared_mac),
+        }
+    }
+}
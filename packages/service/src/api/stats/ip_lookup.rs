@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+
+use crate::database::{network::IpLookupRow, robot::RobotIdent};
+
+/// A robot currently reporting the looked-up IP address.
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct IpLookupMatch {
+    pub robot: RobotIdent,
+    pub interface: String,
+    pub prefix_len: Option<i64>,
+    pub last_updated: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct IpLookupResponse {
+    pub ip: String,
+    pub matches: Vec<IpLookupMatch>,
+}
+
+impl From<IpLookupRow> for IpLookupMatch {
+    fn from(row: IpLookupRow) -> Self {
+        Self {
+            robot: RobotIdent {
+                mac: row.mac,
+                name: row.name,
+                uuid: row.uuid,
+            },
+            interface: row.interface,
+            prefix_len: row.prefix_len,
+            last_updated: row.last_updated,
+        }
+    }
+}
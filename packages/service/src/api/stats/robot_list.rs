@@ -0,0 +1,225 @@
+use std::{cmp::Ordering, collections::HashMap, net::IpAddr};
+
+use chrono::{DateTime, Utc};
+use poem_openapi::{Enum, Object};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::{
+        Database,
+        network::RobotAddressRow,
+        robot::{RobotFilter, RobotPresenceRow, RobotProfile},
+        with_database,
+    },
+    service::CONNECTIONS,
+    utils::network::IgnoredInterfaces,
+};
+
+pub const DEFAULT_LIMIT: u32 = 50;
+pub const MAX_LIMIT: u32 = 500;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Enum)]
+#[oai(rename_all = "snake_case")]
+pub enum RobotSortKey {
+    #[default]
+    Name,
+    Uuid,
+    LastSeen,
+    ConnectedSince,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Enum)]
+#[oai(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Latest network info of a robot, reduced to the addresses it can be
+/// reached at.
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct NetworkSummary {
+    /// Reachable addresses, IPv4 first.
+    pub addresses: Vec<String>,
+    pub last_updated: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct RobotListEntry {
+    #[oai(flatten)]
+    #[serde(flatten)]
+    pub profile: RobotProfile,
+    pub online: bool,
+    /// Start of the current connection, if the robot is online.
+    pub connected_since: Option<DateTime<Utc>>,
+    /// Last message received from the robot, or the end of its last
+    /// connection while it is offline.
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Bot version reported by the robot when it last connected.
+    pub version: Option<String>,
+    pub network: Option<NetworkSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct RobotListResponse {
+    /// Number of robots matching the query, before pagination.
+    pub total: u32,
+    pub offset: u32,
+    pub limit: u32,
+    pub robots: Vec<RobotListEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RobotListQuery {
+    pub filter: RobotFilter,
+    pub online: Option<bool>,
+    /// Case-insensitive substring of the robot name.
+    pub search: Option<String>,
+    pub sort: RobotSortKey,
+    pub order: SortOrder,
+    pub offset: u32,
+    pub limit: u32,
+}
+
+/// Orders missing values after present ones regardless of `order`.
+fn compare_optional<T: Ord>(
+    a: Option<&T>,
+    b: Option<&T>,
+    order: SortOrder,
+) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => match order {
+            SortOrder::Asc => a.cmp(b),
+            SortOrder::Desc => b.cmp(a),
+        },
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn network_summary(
+    addresses: &[RobotAddressRow],
+    last_updated: DateTime<Utc>,
+    ignored: &IgnoredInterfaces,
+) -> NetworkSummary {
+    let mut reachable: Vec<IpAddr> = addresses
+        .iter()
+        .filter_map(|row| row.reachable_ip(ignored))
+        .collect();
+    reachable.sort_by_key(IpAddr::is_ipv6);
+    reachable.dedup();
+    NetworkSummary {
+        addresses: reachable.iter().map(ToString::to_string).collect(),
+        last_updated,
+    }
+}
+
+/// Lists registered robots joined with their connection state and latest
+/// network info.
+pub async fn list_robots(
+    query: &RobotListQuery,
+) -> anyhow::Result<RobotListResponse> {
+    let profiles = with_database(|db| db.filter_robots(&query.filter))?.await?;
+    let mut presence: HashMap<String, RobotPresenceRow> =
+        with_database(Database::get_robot_presence)?
+            .await?
+            .into_iter()
+            .map(|row| (row.uuid.clone(), row))
+            .collect();
+    let mut addresses: HashMap<String, Vec<RobotAddressRow>> = HashMap::new();
+    for (uuid, row) in with_database(Database::get_all_robot_addresses)?.await?
+    {
+        addresses.entry(uuid).or_default().push(row);
+    }
+    let update_times: HashMap<String, DateTime<Utc>> =
+        with_database(Database::get_network_update_times)?
+            .await?
+            .into_iter()
+            .collect();
+    let ignored = IgnoredInterfaces::from_env()?;
+    let search = query.search.as_deref().map(str::to_lowercase);
+
+    let mut entries: Vec<RobotListEntry> = profiles
+        .into_iter()
+        .filter(|profile| {
+            search.as_deref().is_none_or(|search| {
+                profile.ident.name.to_lowercase().contains(search)
+            })
+        })
+        .map(|profile| {
+            let uuid = &profile.ident.uuid;
+            let connection =
+                CONNECTIONS.get(uuid).map(|conn| conn.value().clone());
+            let presence = presence.remove(uuid);
+            let network = update_times.get(uuid).map(|last_updated| {
+                network_summary(
+                    addresses.get(uuid).map_or(&[], Vec::as_slice),
+                    *last_updated,
+                    &ignored,
+                )
+            });
+            RobotListEntry {
+                online: connection.is_some(),
+                connected_since: connection
+                    .as_ref()
+                    .map(|conn| conn.connected_since),
+                last_seen: connection.as_ref().map_or_else(
+                    || presence.as_ref().and_then(|row| row.last_seen),
+                    |conn| Some(conn.last_seen()),
+                ),
+                version: connection
+                    .as_ref()
+                    .and_then(|conn| conn.version.clone())
+                    .or_else(|| presence.and_then(|row| row.version)),
+                network,
+                profile,
+            }
+        })
+        .filter(|entry| {
+            query.online.is_none_or(|online| entry.online == online)
+        })
+        .collect();
+
+    entries.sort_by(|a, b| {
+        let ordering = match query.sort {
+            RobotSortKey::Name => compare_optional(
+                Some(&a.profile.ident.name),
+                Some(&b.profile.ident.name),
+                query.order,
+            ),
+            RobotSortKey::Uuid => compare_optional(
+                Some(&a.profile.ident.uuid),
+                Some(&b.profile.ident.uuid),
+                query.order,
+            ),
+            RobotSortKey::LastSeen => compare_optional(
+                a.last_seen.as_ref(),
+                b.last_seen.as_ref(),
+                query.order,
+            ),
+            RobotSortKey::ConnectedSince => compare_optional(
+                a.connected_since.as_ref(),
+                b.connected_since.as_ref(),
+                query.order,
+            ),
+        };
+        ordering
+            .then_with(|| a.profile.ident.name.cmp(&b.profile.ident.name))
+            .then_with(|| a.profile.ident.uuid.cmp(&b.profile.ident.uuid))
+    });
+
+    let total = u32::try_from(entries.len()).unwrap_or(u32::MAX);
+    let robots = entries
+        .into_iter()
+        .skip(query.offset as usize)
+        .take(query.limit as usize)
+        .collect();
+    Ok(RobotListResponse {
+        total,
+        offset: query.offset,
+        limit: query.limit,
+        robots,
+    })
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+
+use crate::database::{
+    connection_session::ConnectionSession,
+    robot::{RobotIdent, RobotProfile},
+};
+
+/// Range covered when the query does not specify one.
+pub const DEFAULT_RANGE: TimeDelta = TimeDelta::hours(24);
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct RobotUptime {
+    pub robot: RobotIdent,
+    /// Share of the range the robot was connected, from 0 to 100.
+    pub uptime_percent: f64,
+    pub online_secs: i64,
+    /// Connections overlapping the range.
+    pub connections: u32,
+    /// Connections that ended within the range.
+    pub drops: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct UptimeResponse {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub robots: Vec<RobotUptime>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct ConnectionTimelineResponse {
+    pub robot_uuid: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Connections overlapping the range, oldest first.
+    pub sessions: Vec<ConnectionSession>,
+}
+
+/// Resolves the queried range, defaulting to the last
+/// [`DEFAULT_RANGE`]. The end is capped at `now` since no presence is
+/// known beyond it.
+pub fn resolve_range(
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
+    let to = to.map_or(now, |to| to.min(now));
+    let from = from.unwrap_or(to - DEFAULT_RANGE);
+    if from >= to {
+        return Err(format!(
+            "Invalid range: `from` ({from}) must be before `to` ({to}) and \
+             in the past"
+        ));
+    }
+    Ok((from, to))
+}
+
+/// Time `session` was connected within `[from, to]`. Sessions still open
+/// are counted as connected until `to`.
+fn online_time(
+    session: &ConnectionSession,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> TimeDelta {
+    let start = session.connected_at.max(from);
+    let end = session.disconnected_at.unwrap_or(to).min(to);
+    (end - start).max(TimeDelta::zero())
+}
+
+/// Computes the uptime of every robot in `robots` from the connection
+/// sessions overlapping `[from, to]`.
+pub fn compute_uptime(
+    robots: Vec<RobotProfile>,
+    sessions: &[ConnectionSession],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<RobotUptime> {
+    let mut by_robot: HashMap<&str, Vec<&ConnectionSession>> = HashMap::new();
+    for session in sessions {
+        by_robot
+            .entry(session.robot_uuid.as_str())
+            .or_default()
+            .push(session);
+    }
+    let range_ms = (to - from).num_milliseconds().max(1);
+
+    robots
+        .into_iter()
+        .map(|profile| {
+            let sessions = by_robot
+                .get(profile.ident.uuid.as_str())
+                .map_or(&[][..], Vec::as_slice);
+            // Sessions of one robot do not overlap in practice, but a
+            // quick reconnect may briefly leave two open.
+            let online = sessions
+                .iter()
+                .map(|session| online_time(session, from, to))
+                .sum::<TimeDelta>()
+                .min(to - from);
+            let drops = sessions
+                .iter()
+                .filter(|session| {
+                    session.disconnected_at.is_some_and(|at| at <= to)
+                })
+                .count();
+            #[allow(clippy::cast_precision_loss)]
+            let uptime_percent =
+                online.num_milliseconds() as f64 * 100.0 / range_ms as f64;
+            RobotUptime {
+                robot: profile.ident,
+                uptime_percent,
+                online_secs: online.num_seconds(),
+                connections: u32::try_from(sessions.len()).unwrap_or(u32::MAX),
+                drops: u32::try_from(drops).unwrap_or(u32::MAX),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::robot::EnrollmentStatus;
+
+    fn at(hour: i64) -> DateTime<Utc> {
+        DateTime::UNIX_EPOCH + TimeDelta::hours(hour)
+    }
+
+    fn robot(uuid: &str) -> RobotProfile {
+        RobotProfile {
+            ident: RobotIdent {
+                mac: String::new(),
+                name: uuid.to_string(),
+                uuid: uuid.to_string(),
+            },
+            role: None,
+            team_color: None,
+            tags: Vec::new(),
+            enrollment: EnrollmentStatus::Approved,
+            archived_at: None,
+        }
+    }
+
+    fn session(
+        uuid: &str,
+        connected_at: DateTime<Utc>,
+        disconnected_at: Option<DateTime<Utc>>,
+    ) -> ConnectionSession {
+        ConnectionSession {
+            id: 0,
+            robot_uuid: uuid.to_string(),
+            connected_at,
+            disconnected_at,
+            remote_addr: None,
+            disconnect_reason: None,
+        }
+    }
+
+    #[test]
+    fn defaults_to_the_last_day() {
+        assert_eq!(resolve_range(None, None, at(48)), Ok((at(24), at(48))));
+    }
+
+    #[test]
+    fn caps_the_range_at_now() {
+        assert_eq!(
+            resolve_range(Some(at(1)), Some(at(100)), at(10)),
+            Ok((at(1), at(10)))
+        );
+    }
+
+    #[test]
+    fn rejects_empty_and_future_ranges() {
+        assert!(resolve_range(Some(at(5)), Some(at(5)), at(10)).is_err());
+        assert!(resolve_range(Some(at(6)), Some(at(5)), at(10)).is_err());
+        assert!(resolve_range(Some(at(20)), None, at(10)).is_err());
+    }
+
+    #[test]
+    fn clips_sessions_to_the_range() {
+        let sessions = [
+            session("a", at(0), Some(at(12))),
+            session("a", at(18), None),
+        ];
+        let uptime =
+            compute_uptime(vec![robot("a")], &sessions, at(10), at(20));
+        assert_eq!(uptime.len(), 1);
+        assert_eq!(uptime[0].online_secs, 4 * 3600);
+        assert!((uptime[0].uptime_percent - 40.0).abs() < 1e-9);
+        assert_eq!(uptime[0].connections, 2);
+        assert_eq!(uptime[0].drops, 1);
+    }
+
+    #[test]
+    fn caps_overlapping_sessions_at_the_range() {
+        let sessions = [session("a", at(0), None), session("a", at(5), None)];
+        let uptime = compute_uptime(vec![robot("a")], &sessions, at(0), at(10));
+        assert_eq!(uptime[0].online_secs, 10 * 3600);
+        assert!((uptime[0].uptime_percent - 100.0).abs() < 1e-9);
+        assert_eq!(uptime[0].drops, 0);
+    }
+
+    #[test]
+    fn reports_robots_without_sessions_as_offline() {
+        let sessions = [session("a", at(0), None)];
+        let uptime = compute_uptime(
+            vec![robot("a"), robot("b")],
+            &sessions,
+            at(0),
+            at(10),
+        );
+        assert_eq!(uptime[1].robot.uuid, "b");
+        assert_eq!(uptime[1].online_secs, 0);
+        assert_eq!(uptime[1].connections, 0);
+    }
+}
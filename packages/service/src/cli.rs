@@ -0,0 +1,42 @@
+//! Maintenance commands, run instead of the service when the binary is
+//! given arguments.
+
+use anyhow::bail;
+
+use crate::database::Database;
+
+const USAGE: &str = "Usage: rmcs-actions-service [migrations <status|dry-run>]";
+
+pub async fn run(db: &Database, args: &[String]) -> anyhow::Result<()> {
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["migrations", "status"] => {
+            for migration in db.migration_status().await? {
+                let state = match migration.applied_at {
+                    Some(applied_at) => format!("applied {applied_at}"),
+                    None => "pending".to_string(),
+                };
+                println!(
+                    "{:04} {:<40} {state}",
+                    migration.version, migration.description
+                );
+            }
+        }
+        ["migrations", "dry-run"] => {
+            let versions = db.dry_run_migrations().await?;
+            if versions.is_empty() {
+                println!("The database is up to date");
+            } else {
+                for version in versions {
+                    println!("Would apply migration {version:04}");
+                }
+            }
+        }
+        _ => bail!("{USAGE}"),
+    }
+    Ok(())
+}
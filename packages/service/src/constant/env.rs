@@ -3,3 +3,70 @@ pub const ENV_NAME_STORAGE_DIR: &str = "STORAGE_DIR";
 pub const ENV_NAME_BIND_ADDR: &str = "BIND_ADDR";
 
 pub const DEFAULT_BIND_ADDR: &str = "0.0.0.0:3000";
+
+pub const ENV_NAME_NETWORK_REFRESH_INTERVAL_SECS: &str =
+    "NETWORK_REFRESH_INTERVAL_SECS";
+pub const ENV_NAME_NETWORK_REFRESH_JITTER_SECS: &str =
+    "NETWORK_REFRESH_JITTER_SECS";
+pub const ENV_NAME_NETWORK_REFRESH_CONCURRENCY: &str =
+    "NETWORK_REFRESH_CONCURRENCY";
+pub const ENV_NAME_NETWORK_REFRESH_TIMEOUT_SECS: &str =
+    "NETWORK_REFRESH_TIMEOUT_SECS";
+
+pub const DEFAULT_NETWORK_REFRESH_INTERVAL_SECS: u64 = 60;
+pub const DEFAULT_NETWORK_REFRESH_JITTER_SECS: u64 = 10;
+pub const DEFAULT_NETWORK_REFRESH_CONCURRENCY: usize = 4;
+pub const DEFAULT_NETWORK_REFRESH_TIMEOUT_SECS: u64 = 10;
+
+pub const ENV_NAME_NETWORK_IGNORED_INTERFACES: &str =
+    "NETWORK_IGNORED_INTERFACES";
+
+pub const DEFAULT_NETWORK_IGNORED_INTERFACES: &str = "lo,docker,br-,veth,virbr";
+
+pub const ENV_NAME_DNS_BIND_ADDR: &str = "DNS_BIND_ADDR";
+pub const ENV_NAME_DNS_ZONE: &str = "DNS_ZONE";
+pub const ENV_NAME_DNS_TTL_SECS: &str = "DNS_TTL_SECS";
+
+pub const DEFAULT_DNS_ZONE: &str = "rmcs.lan";
+pub const DEFAULT_DNS_TTL_SECS: u32 = 30;
+
+pub const ENV_NAME_AUTH_BOOTSTRAP_USERNAME: &str = "AUTH_BOOTSTRAP_USERNAME";
+pub const ENV_NAME_AUTH_BOOTSTRAP_PASSWORD: &str = "AUTH_BOOTSTRAP_PASSWORD";
+pub const ENV_NAME_AUTH_SESSION_TTL_SECS: &str = "AUTH_SESSION_TTL_SECS";
+pub const ENV_NAME_AUTH_COOKIE_SECURE: &str = "AUTH_COOKIE_SECURE";
+pub const ENV_NAME_CORS_ALLOWED_ORIGINS: &str = "CORS_ALLOWED_ORIGINS";
+
+pub const DEFAULT_AUTH_SESSION_TTL_SECS: i64 = 7 * 24 * 60 * 60;
+pub const DEFAULT_AUTH_COOKIE_SECURE: bool = false;
+
+pub const ENV_NAME_ENROLLMENT_TOKEN: &str = "ENROLLMENT_TOKEN";
+
+pub const ENV_NAME_BACKUP_DIR: &str = "BACKUP_DIR";
+pub const ENV_NAME_BACKUP_INTERVAL_SECS: &str = "BACKUP_INTERVAL_SECS";
+pub const ENV_NAME_BACKUP_KEEP: &str = "BACKUP_KEEP";
+
+pub const DEFAULT_BACKUP_INTERVAL_SECS: u64 = 60 * 60;
+pub const DEFAULT_BACKUP_KEEP: usize = 24;
+
+pub const ENV_NAME_RETENTION_INTERVAL_SECS: &str = "RETENTION_INTERVAL_SECS";
+pub const ENV_NAME_RETENTION_VACUUM: &str = "RETENTION_VACUUM";
+pub const ENV_NAME_RETENTION_CONNECTION_SESSIONS_MAX_AGE_DAYS: &str =
+    "RETENTION_CONNECTION_SESSIONS_MAX_AGE_DAYS";
+pub const ENV_NAME_RETENTION_CONNECTION_SESSIONS_MAX_PER_ROBOT: &str =
+    "RETENTION_CONNECTION_SESSIONS_MAX_PER_ROBOT";
+pub const ENV_NAME_RETENTION_JOBS_MAX_AGE_DAYS: &str =
+    "RETENTION_JOBS_MAX_AGE_DAYS";
+pub const ENV_NAME_RETENTION_AUDIT_LOG_MAX_AGE_DAYS: &str =
+    "RETENTION_AUDIT_LOG_MAX_AGE_DAYS";
+
+pub const DEFAULT_RETENTION_INTERVAL_SECS: u64 = 24 * 60 * 60;
+pub const DEFAULT_RETENTION_VACUUM: bool = true;
+pub const DEFAULT_RETENTION_CONNECTION_SESSIONS_MAX_AGE_DAYS: u32 = 90;
+pub const DEFAULT_RETENTION_CONNECTION_SESSIONS_MAX_PER_ROBOT: u32 = 1000;
+pub const DEFAULT_RETENTION_JOBS_MAX_AGE_DAYS: u32 = 90;
+pub const DEFAULT_RETENTION_AUDIT_LOG_MAX_AGE_DAYS: u32 = 365;
+
+pub const ENV_NAME_METRICS_BIND_ADDR: &str = "METRICS_BIND_ADDR";
+pub const ENV_NAME_METRICS_TOKEN: &str = "METRICS_TOKEN";
+
+pub const DEFAULT_METRICS_BIND_ADDR: &str = "127.0.0.1:9464";
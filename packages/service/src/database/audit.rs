@@ -0,0 +1,250 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use poem_openapi::{Enum, Object};
+use serde::{Deserialize, Serialize};
+
+use crate::database::{Database, user::User};
+
+/// What produced an audit entry.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum AuditCategory {
+    /// A mutating HTTP API call.
+    Api,
+    /// An instruction sent to a robot.
+    Instruction,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+    /// The caller was not authenticated or lacked permission.
+    Denied,
+    /// The caller stopped waiting for the robot, e.g. on a timeout or a
+    /// cancelled job.
+    Abandoned,
+}
+
+impl AuditOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditOutcome::Success => "success",
+            AuditOutcome::Failure => "failure",
+            AuditOutcome::Denied => "denied",
+            AuditOutcome::Abandoned => "abandoned",
+        }
+    }
+}
+
+/// Who performed an audited action.
+#[derive(Debug, Clone)]
+pub struct Actor {
+    pub user_id: Option<i64>,
+    pub name: String,
+}
+
+impl Actor {
+    /// The service itself, e.g. the periodic network refresh.
+    pub fn system() -> Self {
+        Self {
+            user_id: None,
+            name: "system".to_string(),
+        }
+    }
+
+    pub fn is_system(&self) -> bool {
+        self.user_id.is_none() && self.name == "system"
+    }
+
+    /// An unauthenticated caller, such as a robot or a failed login.
+    pub fn anonymous() -> Self {
+        Self {
+            user_id: None,
+            name: "anonymous".to_string(),
+        }
+    }
+}
+
+impl From<&User> for Actor {
+    fn from(user: &User) -> Self {
+        Self {
+            user_id: Some(user.id),
+            name: user.username.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewAuditEntry {
+    pub category: AuditCategory,
+    pub actor: Actor,
+    pub action: String,
+    pub robot_uuid: Option<String>,
+    pub parameters: Option<serde_json::Value>,
+    pub outcome: AuditOutcome,
+    pub message: Option<String>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub category: AuditCategory,
+    /// Username, or `system` / `anonymous`.
+    pub actor: String,
+    pub actor_id: Option<i64>,
+    /// `METHOD /path` of an API call, or the instruction name.
+    pub action: String,
+    pub robot_uuid: Option<String>,
+    /// Request body or instruction parameters, with secrets redacted.
+    pub parameters: Option<serde_json::Value>,
+    pub outcome: AuditOutcome,
+    pub message: Option<String>,
+    pub duration_ms: i64,
+}
+
+/// Criteria selecting audit entries; unset criteria match every entry.
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub category: Option<AuditCategory>,
+    pub actor: Option<String>,
+    pub robot_uuid: Option<String>,
+    pub action: Option<String>,
+    pub outcome: Option<AuditOutcome>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl Database {
+    pub async fn insert_audit_entry(
+        &self,
+        entry: &NewAuditEntry,
+    ) -> Result<(), sqlx::Error> {
+        let parameters =
+            entry.parameters.as_ref().map(serde_json::Value::to_string);
+        let duration_ms =
+            i64::try_from(entry.duration.as_millis()).unwrap_or(i64::MAX);
+        sqlx::query!(
+            "INSERT INTO audit_log (
+                category, actor, actor_id, action, robot_uuid, parameters,
+                outcome, message, duration_ms
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            entry.category,
+            entry.actor.name,
+            entry.actor.user_id,
+            entry.action,
+            entry.robot_uuid,
+            parameters,
+            entry.outcome,
+            entry.message,
+            duration_ms
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(())
+    }
+
+    /// Lists entries matching `filter`, newest first. A negative `limit`
+    /// returns every entry after `offset`.
+    pub async fn list_audit_entries(
+        &self,
+        filter: &AuditFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<AuditEntry>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+                SELECT id,
+                    created_at AS "created_at: DateTime<Utc>",
+                    category AS "category: AuditCategory",
+                    actor, actor_id, action, robot_uuid, parameters,
+                    outcome AS "outcome: AuditOutcome",
+                    message, duration_ms
+                FROM audit_log
+                WHERE (?1 IS NULL OR category = ?1)
+                    AND (?2 IS NULL OR actor = ?2)
+                    AND (?3 IS NULL OR robot_uuid = ?3)
+                    AND (?4 IS NULL OR action = ?4)
+                    AND (?5 IS NULL OR outcome = ?5)
+                    AND (?6 IS NULL
+                        OR julianday(created_at) >= julianday(?6))
+                    AND (?7 IS NULL
+                        OR julianday(created_at) < julianday(?7))
+                ORDER BY id DESC
+                LIMIT ?8 OFFSET ?9
+            "#,
+            filter.category,
+            filter.actor,
+            filter.robot_uuid,
+            filter.action,
+            filter.outcome,
+            filter.from,
+            filter.to,
+            limit,
+            offset
+        )
+        .fetch_all(&self.connection)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| AuditEntry {
+                id: row.id,
+                created_at: row.created_at,
+                category: row.category,
+                actor: row.actor,
+                actor_id: row.actor_id,
+                action: row.action,
+                robot_uuid: row.robot_uuid,
+                // Only ever written from a serialised value.
+                parameters: row
+                    .parameters
+                    .and_then(|value| serde_json::from_str(&value).ok()),
+                outcome: row.outcome,
+                message: row.message,
+                duration_ms: row.duration_ms,
+            })
+            .collect())
+    }
+
+    pub async fn count_audit_entries(
+        &self,
+        filter: &AuditFilter,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+                SELECT COUNT(*) FROM audit_log
+                WHERE (?1 IS NULL OR category = ?1)
+                    AND (?2 IS NULL OR actor = ?2)
+                    AND (?3 IS NULL OR robot_uuid = ?3)
+                    AND (?4 IS NULL OR action = ?4)
+                    AND (?5 IS NULL OR outcome = ?5)
+                    AND (?6 IS NULL
+                        OR julianday(created_at) >= julianday(?6))
+                    AND (?7 IS NULL
+                        OR julianday(created_at) < julianday(?7))
+            "#,
+            filter.category,
+            filter.actor,
+            filter.robot_uuid,
+            filter.action,
+            filter.outcome,
+            filter.from,
+            filter.to
+        )
+        .fetch_one(&self.connection)
+        .await
+    }
+}
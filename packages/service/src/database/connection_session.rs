@@ -0,0 +1,135 @@
+use chrono::{DateTime, Utc};
+use poem_openapi::{Enum, Object};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::database::Database;
+
+/// Why a robot's connection ended.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum DisconnectReason {
+    /// The robot sent a websocket close frame.
+    ClosedByRobot,
+    /// The websocket stream ended without a close frame.
+    ConnectionLost,
+    /// A message could not be written to the robot.
+    SendFailed,
+    /// The service closed the connection, e.g. because the robot's
+    /// enrollment was revoked.
+    ClosedByServer,
+    /// The service stopped while the robot was connected.
+    ServiceRestart,
+}
+
+/// One websocket connection of a robot.
+#[derive(Debug, Clone, Serialize, Deserialize, Object, FromRow)]
+pub struct ConnectionSession {
+    pub id: i64,
+    pub robot_uuid: String,
+    pub connected_at: DateTime<Utc>,
+    /// Unset while the robot is still connected.
+    pub disconnected_at: Option<DateTime<Utc>>,
+    pub remote_addr: Option<String>,
+    pub disconnect_reason: Option<DisconnectReason>,
+}
+
+impl Database {
+    /// Records a new connection of a robot and returns its session ID.
+    pub async fn open_connection_session(
+        &self,
+        robot_uuid: &str,
+        remote_addr: Option<&str>,
+    ) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query!(
+            "INSERT INTO connection_sessions (robot_uuid, remote_addr)
+             VALUES (?, ?)",
+            robot_uuid,
+            remote_addr
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn close_connection_session(
+        &self,
+        id: i64,
+        reason: DisconnectReason,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE connection_sessions
+             SET disconnected_at = CURRENT_TIMESTAMP, disconnect_reason = ?
+             WHERE id = ? AND disconnected_at IS NULL",
+            reason,
+            id
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(())
+    }
+
+    /// Lists the connection sessions overlapping `[from, to)`, optionally
+    /// of a single robot, oldest first.
+    pub async fn get_connection_sessions(
+        &self,
+        robot_uuid: Option<&str>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<ConnectionSession>, sqlx::Error> {
+        sqlx::query_as!(
+            ConnectionSession,
+            r#"
+                SELECT
+                    id, robot_uuid,
+                    connected_at AS "connected_at: DateTime<Utc>",
+                    disconnected_at AS "disconnected_at: DateTime<Utc>",
+                    remote_addr,
+                    disconnect_reason AS "disconnect_reason: DisconnectReason"
+                FROM connection_sessions
+                WHERE (?1 IS NULL OR robot_uuid = ?1)
+                    AND julianday(connected_at) < julianday(?3)
+                    AND (
+                        disconnected_at IS NULL
+                        OR julianday(disconnected_at) > julianday(?2)
+                    )
+                ORDER BY connected_at, id
+            "#,
+            robot_uuid,
+            from,
+            to
+        )
+        .fetch_all(&self.connection)
+        .await
+    }
+
+    /// Ends the sessions left open by a previous run of the service. As
+    /// the exact time of the drop is unknown, the session ends when the
+    /// robot was last seen.
+    pub async fn close_stale_connection_sessions(
+        &self,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE connection_sessions
+             SET disconnect_reason = ?,
+                 disconnected_at = COALESCE(
+                     (
+                         SELECT r.last_seen FROM robots r
+                         WHERE r.uuid = connection_sessions.robot_uuid
+                             AND julianday(r.last_seen)
+                                 >= julianday(connection_sessions.connected_at)
+                     ),
+                     connected_at
+                 )
+             WHERE disconnected_at IS NULL",
+            DisconnectReason::ServiceRestart
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(())
+    }
+}
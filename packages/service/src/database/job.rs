@@ -0,0 +1,252 @@
+use chrono::{DateTime, Utc};
+use poem_openapi::{Enum, Object};
+use serde::{Deserialize, Serialize};
+
+use crate::database::Database;
+
+/// Lifecycle of an asynchronous job.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Finished,
+    Cancelled,
+    /// The service stopped while the job was still running.
+    Interrupted,
+}
+
+/// Status of a robot that has not been instructed yet.
+pub const ROBOT_STATUS_PENDING: &str = "pending";
+/// Status of a robot that is executing the instruction.
+pub const ROBOT_STATUS_RUNNING: &str = "running";
+/// Status of a robot whose instruction was stopped by a cancellation.
+pub const ROBOT_STATUS_CANCELLED: &str = "cancelled";
+/// Status of a robot that was skipped because another instruction held its
+/// operation lock.
+pub const ROBOT_STATUS_LOCKED: &str = "locked";
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct JobRobot {
+    pub robot_id: String,
+    /// `pending`, `running`, `cancelled`, `locked` or the instruction's own
+    /// result status once it completed.
+    pub status: String,
+    pub message: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct Job {
+    pub id: String,
+    pub kind: String,
+    pub status: JobStatus,
+    /// `ok` or `partial_failure` once the job finished.
+    pub outcome: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub robots: Vec<JobRobot>,
+}
+
+impl Database {
+    /// Records a new pending job targeting `robot_uuids`.
+    pub async fn create_job(
+        &self,
+        id: &str,
+        kind: &str,
+        robot_uuids: &[String],
+    ) -> Result<(), sqlx::Error> {
+        let mut transaction = self.connection.begin().await?;
+        sqlx::query!(
+            "INSERT INTO jobs (id, kind, status) VALUES (?, ?, ?)",
+            id,
+            kind,
+            JobStatus::Pending
+        )
+        .execute(&mut *transaction)
+        .await?;
+        for robot_uuid in robot_uuids {
+            sqlx::query!(
+                "INSERT INTO job_robots (job_id, robot_uuid, status)
+                 VALUES (?, ?, ?)",
+                id,
+                robot_uuid,
+                ROBOT_STATUS_PENDING
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await
+    }
+
+    pub async fn start_job(&self, id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE jobs SET status = ?, started_at = CURRENT_TIMESTAMP
+             WHERE id = ? AND status = ?",
+            JobStatus::Running,
+            id,
+            JobStatus::Pending
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(())
+    }
+
+    /// Marks a running job as finished. Has no effect on a job that was
+    /// cancelled in the meantime.
+    pub async fn finish_job(
+        &self,
+        id: &str,
+        outcome: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE jobs
+             SET status = ?, outcome = ?, finished_at = CURRENT_TIMESTAMP
+             WHERE id = ? AND status = ?",
+            JobStatus::Finished,
+            outcome,
+            id,
+            JobStatus::Running
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(())
+    }
+
+    /// Marks a pending or running job and its unfinished robots as
+    /// cancelled. Returns `false` if the job does not exist or is already
+    /// done.
+    pub async fn cancel_job(&self, id: &str) -> Result<bool, sqlx::Error> {
+        let mut transaction = self.connection.begin().await?;
+        let result = sqlx::query!(
+            "UPDATE jobs SET status = ?, finished_at = CURRENT_TIMESTAMP
+             WHERE id = ? AND status IN (?, ?)",
+            JobStatus::Cancelled,
+            id,
+            JobStatus::Pending,
+            JobStatus::Running
+        )
+        .execute(&mut *transaction)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        sqlx::query!(
+            "UPDATE job_robots
+             SET status = ?, updated_at = CURRENT_TIMESTAMP
+             WHERE job_id = ? AND status IN (?, ?)",
+            ROBOT_STATUS_CANCELLED,
+            id,
+            ROBOT_STATUS_PENDING,
+            ROBOT_STATUS_RUNNING
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    /// Records the progress of one robot. Has no effect on a robot whose
+    /// instruction was cancelled in the meantime, so that a worker that is
+    /// just starting or finishing cannot undo the cancellation.
+    pub async fn set_job_robot_status(
+        &self,
+        job_id: &str,
+        robot_uuid: &str,
+        status: &str,
+        message: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE job_robots
+             SET status = ?, message = ?, updated_at = CURRENT_TIMESTAMP
+             WHERE job_id = ? AND robot_uuid = ? AND status <> ?",
+            status,
+            message,
+            job_id,
+            robot_uuid,
+            ROBOT_STATUS_CANCELLED
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_job(&self, id: &str) -> Result<Option<Job>, sqlx::Error> {
+        let Some(job) = sqlx::query!(
+            r#"
+                SELECT
+                    id, kind,
+                    status AS "status: JobStatus",
+                    outcome,
+                    created_at AS "created_at: DateTime<Utc>",
+                    started_at AS "started_at: DateTime<Utc>",
+                    finished_at AS "finished_at: DateTime<Utc>"
+                FROM jobs WHERE id = ?
+            "#,
+            id
+        )
+        .fetch_optional(&self.connection)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let robots = sqlx::query_as!(
+            JobRobot,
+            r#"
+                SELECT
+                    robot_uuid AS robot_id, status, message,
+                    updated_at AS "updated_at: DateTime<Utc>"
+                FROM job_robots WHERE job_id = ?
+                ORDER BY robot_uuid
+            "#,
+            id
+        )
+        .fetch_all(&self.connection)
+        .await?;
+
+        Ok(Some(Job {
+            id: job.id,
+            kind: job.kind,
+            status: job.status,
+            outcome: job.outcome,
+            created_at: job.created_at,
+            started_at: job.started_at,
+            finished_at: job.finished_at,
+            robots,
+        }))
+    }
+
+    pub async fn interrupt_unfinished_jobs(&self) -> Result<(), sqlx::Error> {
+        let mut transaction = self.connection.begin().await?;
+        sqlx::query!(
+            "UPDATE job_robots
+             SET status = ?, updated_at = CURRENT_TIMESTAMP
+             WHERE status IN (?, ?) AND job_id IN (
+                 SELECT id FROM jobs WHERE status IN (?, ?)
+             )",
+            ROBOT_STATUS_CANCELLED,
+            ROBOT_STATUS_PENDING,
+            ROBOT_STATUS_RUNNING,
+            JobStatus::Pending,
+            JobStatus::Running
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            "UPDATE jobs SET status = ?, finished_at = CURRENT_TIMESTAMP
+             WHERE status IN (?, ?)",
+            JobStatus::Interrupted,
+            JobStatus::Pending,
+            JobStatus::Running
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await
+    }
+}
@@ -0,0 +1,265 @@
+//! Versioned schema migrations.
+//!
+//! Migrations are the SQL files in `migrations/`, embedded into the binary
+//! and applied in order of their version at startup. Each applied
+//! migration is recorded in `schema_migrations` together with a checksum
+//! of its SQL, so that a migration edited after it was applied is noticed
+//! instead of silently leaving databases diverged.
+
+use std::collections::HashMap;
+
+use anyhow::{Context, bail};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::{
+    SqliteConnection,
+    migrate::{Migration, Migrator},
+};
+
+use crate::database::Database;
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Migration rebuilding a `network_info` table that old releases created
+/// without its foreign key to `robots`.
+const NETWORK_INFO_FOREIGN_KEY_VERSION: i64 = 5;
+
+const CREATE_SCHEMA_MIGRATIONS_TABLE_SQL: &str = "
+    CREATE TABLE IF NOT EXISTS schema_migrations (
+        version     INTEGER PRIMARY KEY NOT NULL,
+        description TEXT NOT NULL,
+        checksum    BLOB NOT NULL,
+        applied_at  TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+    )
+";
+
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    /// Unset while the migration is pending.
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+struct AppliedMigration {
+    checksum: Vec<u8>,
+    applied_at: DateTime<Utc>,
+}
+
+fn migrations() -> impl Iterator<Item = &'static Migration> {
+    MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+}
+
+async fn table_exists(
+    conn: &mut SqliteConnection,
+    table: &str,
+) -> Result<bool, sqlx::Error> {
+    let count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?",
+    )
+    .bind(table)
+    .fetch_one(conn)
+    .await?;
+    Ok(count > 0)
+}
+
+async fn network_info_has_foreign_key(
+    conn: &mut SqliteConnection,
+) -> Result<bool, sqlx::Error> {
+    let count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM pragma_foreign_key_list('network_info')
+         WHERE \"table\" = 'robots' AND \"from\" = 'robot_uuid'
+         AND \"to\" = 'uuid'",
+    )
+    .fetch_one(conn)
+    .await?;
+    Ok(count > 0)
+}
+
+/// Reads the migration record, which is empty for databases that have
+/// never been migrated.
+async fn applied_migrations(
+    conn: &mut SqliteConnection,
+) -> Result<HashMap<i64, AppliedMigration>, sqlx::Error> {
+    if !table_exists(&mut *conn, "schema_migrations").await? {
+        return Ok(HashMap::new());
+    }
+    let rows: Vec<(i64, Vec<u8>, NaiveDateTime)> = sqlx::query_as(
+        "SELECT version, checksum, applied_at FROM schema_migrations",
+    )
+    .fetch_all(conn)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(version, checksum, applied_at)| {
+            (
+                version,
+                AppliedMigration {
+                    checksum,
+                    applied_at: applied_at.and_utc(),
+                },
+            )
+        })
+        .collect())
+}
+
+/// Fails if the database was migrated by a build whose migrations differ
+/// from the embedded ones.
+fn verify_applied(
+    applied: &HashMap<i64, AppliedMigration>,
+) -> anyhow::Result<()> {
+    for (version, record) in applied {
+        let Some(migration) =
+            migrations().find(|migration| migration.version == *version)
+        else {
+            bail!(
+                "Database has migration {version} applied, which this build \
+                 does not know; upgrade the service"
+            );
+        };
+        if *migration.checksum != *record.checksum {
+            bail!(
+                "Migration {version} ({}) was changed after it was applied",
+                migration.description
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn record_migration(
+    conn: &mut SqliteConnection,
+    migration: &Migration,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO schema_migrations (version, description, checksum)
+         VALUES (?, ?, ?)",
+    )
+    .bind(migration.version)
+    .bind(&*migration.description)
+    .bind(&*migration.checksum)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Creates the migration record. A database created before migrations
+/// existed has the schema of the first migration and is migrated like any
+/// other.
+async fn start_migration_record(
+    conn: &mut SqliteConnection,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(CREATE_SCHEMA_MIGRATIONS_TABLE_SQL)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Tells whether `migration` has anything to change. Migrations repairing
+/// databases of old releases are recorded without running elsewhere.
+async fn is_needed(
+    conn: &mut SqliteConnection,
+    migration: &Migration,
+) -> Result<bool, sqlx::Error> {
+    if migration.version == NETWORK_INFO_FOREIGN_KEY_VERSION {
+        return Ok(!network_info_has_foreign_key(conn).await?);
+    }
+    Ok(true)
+}
+
+/// Runs the pending migrations on `conn`, returning the versions applied.
+/// With `dry_run` every migration and the migration record are rolled back
+/// at the end.
+async fn apply_pending(
+    conn: &mut SqliteConnection,
+    dry_run: bool,
+) -> anyhow::Result<Vec<i64>> {
+    let mut transaction = sqlx::Connection::begin(&mut *conn).await?;
+    start_migration_record(&mut transaction).await?;
+    let applied = applied_migrations(&mut transaction).await?;
+    verify_applied(&applied)?;
+    if !dry_run {
+        // Later migrations are applied in transactions of their own, so
+        // that each one is either fully applied or not at all.
+        transaction.commit().await?;
+        transaction = sqlx::Connection::begin(&mut *conn).await?;
+    }
+
+    let mut versions = Vec::new();
+    for migration in migrations()
+        .filter(|migration| !applied.contains_key(&migration.version))
+    {
+        let needed = is_needed(&mut transaction, migration).await?;
+        if needed {
+            sqlx::raw_sql(&migration.sql)
+                .execute(&mut *transaction)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to apply migration {} ({})",
+                        migration.version, migration.description
+                    )
+                })?;
+        }
+        record_migration(&mut transaction, migration).await?;
+        versions.push(migration.version);
+        if !dry_run {
+            transaction.commit().await?;
+            log::info!(
+                "{} migration {} ({})",
+                if needed {
+                    "Applied"
+                } else {
+                    "Recorded unneeded"
+                },
+                migration.version,
+                migration.description
+            );
+            transaction = sqlx::Connection::begin(&mut *conn).await?;
+        }
+    }
+
+    if dry_run {
+        transaction.rollback().await?;
+    } else {
+        transaction.commit().await?;
+    }
+    Ok(versions)
+}
+
+impl Database {
+    /// Applies every pending migration.
+    pub async fn migrate(&self) -> anyhow::Result<()> {
+        let mut conn = self.connection.acquire().await?;
+        apply_pending(&mut conn, false).await?;
+        Ok(())
+    }
+
+    /// Applies the pending migrations and rolls them back, returning the
+    /// versions that would have been applied. Fails like [`Self::migrate`]
+    /// would.
+    pub async fn dry_run_migrations(&self) -> anyhow::Result<Vec<i64>> {
+        let mut conn = self.connection.acquire().await?;
+        apply_pending(&mut conn, true).await
+    }
+
+    /// Lists every known migration and when it was applied, without
+    /// changing the database.
+    pub async fn migration_status(
+        &self,
+    ) -> anyhow::Result<Vec<MigrationStatus>> {
+        let mut conn = self.connection.acquire().await?;
+        let applied = applied_migrations(&mut conn).await?;
+        verify_applied(&applied)?;
+        Ok(migrations()
+            .map(|migration| MigrationStatus {
+                version: migration.version,
+                description: migration.description.to_string(),
+                applied_at: applied
+                    .get(&migration.version)
+                    .map(|record| record.applied_at),
+            })
+            .collect())
+    }
+}
@@ -1,9 +1,15 @@
+use std::net::IpAddr;
+
 use chrono::{DateTime, Utc};
 use poem_openapi::Object;
 use serde::{Deserialize, Serialize};
 use serde_with::{DefaultOnNull, serde_as};
+use sqlx::{Sqlite, Transaction, prelude::FromRow};
 
-use crate::database::Database;
+use crate::{
+    database::Database,
+    utils::network::{IgnoredInterfaces, is_reachable_ip},
+};
 
 pub type NetworkInfo = Vec<NetworkInfoItem>;
 
@@ -25,6 +31,18 @@ pub struct Addr {
     pub addr: String,
 }
 
+impl Addr {
+    /// Splits the address into its IP and optional CIDR prefix length.
+    /// Returns `None` when the address is not a valid IP address.
+    pub fn parse(&self) -> Option<(IpAddr, Option<u8>)> {
+        let (ip, prefix_len) = match self.addr.split_once('/') {
+            Some((ip, prefix_len)) => (ip, Some(prefix_len.parse().ok()?)),
+            None => (self.addr.as_str(), None),
+        };
+        Some((ip.parse().ok()?, prefix_len))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Object)]
 pub struct NetworkInfoRow {
     pub info: NetworkInfo,
@@ -38,16 +56,151 @@ impl Database {
         info: &NetworkInfo,
     ) -> anyhow::Result<()> {
         let info_json = serde_json::to_string(info)?;
+        let mut transaction = self.connection.begin().await?;
         sqlx::query!(
             "INSERT OR REPLACE INTO network_info (robot_uuid, info) VALUES (?, ?)",
             uuid,
             info_json,
         )
-        .execute(&self.connection)
+        .execute(&mut *transaction)
         .await?;
+        index_network_addresses(&mut transaction, uuid, info).await?;
+        transaction.commit().await?;
         Ok(())
     }
 
+    /// Rebuilds the address index of every robot from `network_info`.
+    pub async fn reindex_network_addresses(&self) -> Result<(), sqlx::Error> {
+        let rows = sqlx::query!("SELECT robot_uuid, info FROM network_info")
+            .fetch_all(&self.connection)
+            .await?;
+        let mut transaction = self.connection.begin().await?;
+        for row in rows {
+            match serde_json::from_str::<NetworkInfo>(&row.info) {
+                Ok(info) => {
+                    index_network_addresses(
+                        &mut transaction,
+                        &row.robot_uuid,
+                        &info,
+                    )
+                    .await?;
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Skipping unreadable network info of robot {}: {e}",
+                        row.robot_uuid
+                    );
+                }
+            }
+        }
+        transaction.commit().await
+    }
+
+    /// Finds every robot whose latest network info holds `ip`.
+    pub async fn lookup_ip(
+        &self,
+        ip: &str,
+    ) -> Result<Vec<IpLookupRow>, sqlx::Error> {
+        sqlx::query_as!(
+            IpLookupRow,
+            r#"
+                SELECT
+                    r.uuid, r.name, r.mac,
+                    a.interface, a.prefix_len,
+                    n.last_updated AS "last_updated: DateTime<Utc>"
+                FROM network_addresses a
+                JOIN robots r ON r.uuid = a.robot_uuid
+                JOIN network_info n ON n.robot_uuid = a.robot_uuid
+                WHERE a.ip = ? AND r.archived_at IS NULL
+                ORDER BY r.name
+            "#,
+            ip
+        )
+        .fetch_all(&self.connection)
+        .await
+    }
+    /// Returns when the network info of each robot was last updated.
+    pub async fn get_network_update_times(
+        &self,
+    ) -> Result<Vec<(String, DateTime<Utc>)>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+                SELECT robot_uuid,
+                    last_updated AS "last_updated: DateTime<Utc>"
+                FROM network_info
+            "#
+        )
+        .fetch_all(&self.connection)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.robot_uuid, row.last_updated))
+            .collect())
+    }
+
+    /// Lists the indexed IP addresses of every robot, grouped by robot.
+    pub async fn get_all_robot_addresses(
+        &self,
+    ) -> Result<Vec<(String, RobotAddressRow)>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "
+                SELECT a.robot_uuid, a.interface, a.ip
+                FROM network_addresses a
+                ORDER BY a.robot_uuid, a.interface, a.ip
+            "
+        )
+        .fetch_all(&self.connection)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.robot_uuid,
+                    RobotAddressRow {
+                        interface: row.interface,
+                        ip: row.ip,
+                    },
+                )
+            })
+            .collect())
+    }
+
+    pub async fn get_indexed_ipv4_addresses(
+        &self,
+    ) -> Result<Vec<IndexedAddressRow>, sqlx::Error> {
+        sqlx::query_as!(
+            IndexedAddressRow,
+            "
+                SELECT a.robot_uuid, r.name AS robot_name, a.interface,
+                    a.ip AS address
+                FROM network_addresses a
+                JOIN robots r ON r.uuid = a.robot_uuid
+                WHERE a.family = 'ipv4' AND r.archived_at IS NULL
+                ORDER BY a.ip, r.name
+            "
+        )
+        .fetch_all(&self.connection)
+        .await
+    }
+
+    pub async fn get_indexed_hardware_addresses(
+        &self,
+    ) -> Result<Vec<IndexedAddressRow>, sqlx::Error> {
+        sqlx::query_as!(
+            IndexedAddressRow,
+            "
+                SELECT i.robot_uuid, r.name AS robot_name,
+                    i.name AS interface, i.hardware_addr AS address
+                FROM network_interfaces i
+                JOIN robots r ON r.uuid = i.robot_uuid
+                WHERE i.hardware_addr != '' AND r.archived_at IS NULL
+                ORDER BY i.hardware_addr, r.name
+            "
+        )
+        .fetch_all(&self.connection)
+        .await
+    }
+
     pub async fn get_network_info(
         &self,
         uuid: &str,
@@ -69,3 +222,92 @@ impl Database {
         }
     }
 }
+
+#[derive(Debug, Clone, FromRow)]
+pub struct IpLookupRow {
+    pub uuid: String,
+    pub name: String,
+    pub mac: String,
+    pub interface: String,
+    pub prefix_len: Option<i64>,
+    pub last_updated: DateTime<Utc>,
+}
+
+/// An IP address held by one interface of a robot.
+#[derive(Debug, Clone, FromRow)]
+pub struct RobotAddressRow {
+    pub interface: String,
+    pub ip: String,
+}
+
+impl RobotAddressRow {
+    /// Returns the address if other hosts can use it to reach the robot.
+    pub fn reachable_ip(&self, ignored: &IgnoredInterfaces) -> Option<IpAddr> {
+        if ignored.contains(&self.interface) {
+            return None;
+        }
+        self.ip.parse().ok().filter(|ip| is_reachable_ip(*ip))
+    }
+}
+
+/// An IPv4 or hardware address held by one interface of a robot.
+#[derive(Debug, Clone, FromRow)]
+pub struct IndexedAddressRow {
+    pub robot_uuid: String,
+    pub robot_name: String,
+    pub interface: String,
+    pub address: String,
+}
+
+/// Replaces the indexed interfaces and addresses of `uuid` with those
+/// found in `info`.
+async fn index_network_addresses(
+    transaction: &mut Transaction<'_, Sqlite>,
+    uuid: &str,
+    info: &NetworkInfo,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM network_interfaces WHERE robot_uuid = ?", uuid)
+        .execute(&mut **transaction)
+        .await?;
+    sqlx::query!("DELETE FROM network_addresses WHERE robot_uuid = ?", uuid)
+        .execute(&mut **transaction)
+        .await?;
+
+    for item in info {
+        let hardware_addr = item.hardware_addr.to_lowercase();
+        sqlx::query!(
+            "INSERT OR REPLACE INTO network_interfaces
+             (robot_uuid, name, hardware_addr) VALUES (?, ?, ?)",
+            uuid,
+            item.name,
+            hardware_addr,
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        for addr in &item.addrs {
+            let Some((ip, prefix_len)) = addr.parse() else {
+                log::warn!(
+                    "Not indexing unparsable address `{}` of robot {uuid}",
+                    addr.addr
+                );
+                continue;
+            };
+            let ip_text = ip.to_string();
+            let family = if ip.is_ipv4() { "ipv4" } else { "ipv6" };
+            sqlx::query!(
+                "INSERT INTO network_addresses
+                 (robot_uuid, interface, ip, prefix_len, family)
+                 VALUES (?, ?, ?, ?, ?)",
+                uuid,
+                item.name,
+                ip_text,
+                prefix_len,
+                family,
+            )
+            .execute(&mut **transaction)
+            .await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,231 @@
+//! Export and import of the robot registry, for moving the service to
+//! another machine without re-registering the fleet.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+
+use crate::database::{
+    Database,
+    robot::{
+        EnrollmentStatus, RobotIdent, RobotRole, TeamColor, normalize_mac,
+        normalize_tags,
+    },
+};
+
+/// Version of the export format, raised whenever an export would no
+/// longer be read correctly by an older service.
+pub const REGISTRY_FORMAT_VERSION: u32 = 1;
+
+/// A robot together with everything the registry knows about it.
+#[derive(Debug, Clone, Object, Serialize, Deserialize)]
+pub struct RegistryRobot {
+    #[oai(flatten)]
+    #[serde(flatten)]
+    pub ident: RobotIdent,
+    pub machine_id: Option<String>,
+    pub role: Option<RobotRole>,
+    pub team_color: Option<TeamColor>,
+    pub tags: Vec<String>,
+    pub enrollment: EnrollmentStatus,
+    pub version: Option<String>,
+    pub last_seen: Option<DateTime<Utc>>,
+    pub archived_at: Option<DateTime<Utc>>,
+}
+
+/// A UUID of a merged robot that resolves to `robot_uuid`.
+#[derive(Debug, Clone, Object, Serialize, Deserialize)]
+pub struct RobotAlias {
+    pub alias_uuid: String,
+    pub robot_uuid: String,
+}
+
+#[derive(Debug, Clone, Object, Serialize, Deserialize)]
+pub struct RegistryExport {
+    pub format_version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub robots: Vec<RegistryRobot>,
+    pub aliases: Vec<RobotAlias>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Object, Serialize, Deserialize)]
+pub struct RegistryImportSummary {
+    /// Robots that were not registered before.
+    pub created: u32,
+    /// Registered robots whose entry was replaced by the imported one.
+    pub updated: u32,
+    pub aliases: u32,
+}
+
+impl Database {
+    pub async fn export_registry(&self) -> Result<RegistryExport, sqlx::Error> {
+        let mut transaction = self.connection.begin().await?;
+        let rows = sqlx::query!(
+            r#"
+                SELECT mac, name, uuid, machine_id,
+                    role AS "role: RobotRole",
+                    team_color AS "team_color: TeamColor",
+                    enrollment AS "enrollment: EnrollmentStatus",
+                    version,
+                    last_seen AS "last_seen: DateTime<Utc>",
+                    archived_at AS "archived_at: DateTime<Utc>"
+                FROM robots ORDER BY name, uuid
+            "#
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        let tag_rows =
+            sqlx::query!("SELECT robot_uuid, tag FROM robot_tags ORDER BY tag")
+                .fetch_all(&mut *transaction)
+                .await?;
+        let aliases = sqlx::query_as!(
+            RobotAlias,
+            "SELECT alias_uuid, robot_uuid FROM robot_aliases
+             ORDER BY alias_uuid"
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+
+        let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+        for row in tag_rows {
+            tags.entry(row.robot_uuid).or_default().push(row.tag);
+        }
+        let robots = rows
+            .into_iter()
+            .map(|row| RegistryRobot {
+                tags: tags.remove(&row.uuid).unwrap_or_default(),
+                ident: RobotIdent {
+                    mac: row.mac,
+                    name: row.name,
+                    uuid: row.uuid,
+                },
+                machine_id: row.machine_id,
+                role: row.role,
+                team_color: row.team_color,
+                enrollment: row.enrollment,
+                version: row.version,
+                last_seen: row.last_seen,
+                archived_at: row.archived_at,
+            })
+            .collect();
+
+        Ok(RegistryExport {
+            format_version: REGISTRY_FORMAT_VERSION,
+            exported_at: Utc::now(),
+            robots,
+            aliases,
+        })
+    }
+
+    /// Adds the robots and aliases of an export to the registry in a
+    /// single transaction. Robots already registered under the same UUID
+    /// are overwritten, including their tags; other robots are left alone.
+    pub async fn import_registry(
+        &self,
+        export: &RegistryExport,
+    ) -> Result<RegistryImportSummary, sqlx::Error> {
+        let mut summary = RegistryImportSummary::default();
+        let mut transaction = self.connection.begin().await?;
+        for robot in &export.robots {
+            let uuid = &robot.ident.uuid;
+            let mac = normalize_mac(&robot.ident.mac);
+            let exists = sqlx::query_scalar!(
+                "SELECT uuid FROM robots WHERE uuid = ?",
+                uuid
+            )
+            .fetch_optional(&mut *transaction)
+            .await?
+            .is_some();
+            // Stored like CURRENT_TIMESTAMP writes them, so that they
+            // compare correctly with timestamps written by the service.
+            let last_seen = robot.last_seen.map(|at| at.naive_utc());
+            let archived_at = robot.archived_at.map(|at| at.naive_utc());
+            sqlx::query!(
+                "INSERT INTO robots (
+                    uuid, name, mac, machine_id, role, team_color,
+                    enrollment, version, last_seen, archived_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT(uuid) DO UPDATE SET
+                    name = excluded.name, mac = excluded.mac,
+                    machine_id = excluded.machine_id, role = excluded.role,
+                    team_color = excluded.team_color,
+                    enrollment = excluded.enrollment,
+                    version = excluded.version,
+                    last_seen = excluded.last_seen,
+                    archived_at = excluded.archived_at",
+                uuid,
+                robot.ident.name,
+                mac,
+                robot.machine_id,
+                robot.role,
+                robot.team_color,
+                robot.enrollment,
+                robot.version,
+                last_seen,
+                archived_at
+            )
+            .execute(&mut *transaction)
+            .await?;
+            sqlx::query!("DELETE FROM robot_tags WHERE robot_uuid = ?", uuid)
+                .execute(&mut *transaction)
+                .await?;
+            // A UUID names either a robot or an alias, never both.
+            sqlx::query!(
+                "DELETE FROM robot_aliases WHERE alias_uuid = ?",
+                uuid
+            )
+            .execute(&mut *transaction)
+            .await?;
+            for tag in normalize_tags(&robot.tags) {
+                sqlx::query!(
+                    "INSERT OR IGNORE INTO robot_tags (robot_uuid, tag)
+                     VALUES (?, ?)",
+                    uuid,
+                    tag
+                )
+                .execute(&mut *transaction)
+                .await?;
+            }
+            if exists {
+                summary.updated += 1;
+            } else {
+                summary.created += 1;
+            }
+        }
+        for alias in &export.aliases {
+            sqlx::query!(
+                "INSERT INTO robot_aliases (alias_uuid, robot_uuid)
+                 VALUES (?, ?)
+                 ON CONFLICT(alias_uuid) DO UPDATE SET
+                    robot_uuid = excluded.robot_uuid",
+                alias.alias_uuid,
+                alias.robot_uuid
+            )
+            .execute(&mut *transaction)
+            .await?;
+            summary.aliases += 1;
+        }
+        transaction.commit().await?;
+        Ok(summary)
+    }
+
+    /// Writes a consistent copy of the whole database to `path`, which
+    /// must not exist yet.
+    ///
+    /// This uses `VACUUM INTO` rather than the online backup API, which
+    /// sqlx does not expose. The copy is read in a single transaction:
+    /// other connections keep reading, but writers wait for it to finish
+    /// and fail once their busy timeout runs out, so large databases block
+    /// writes for as long as the copy takes. In exchange the copy is
+    /// compacted and never larger than the database itself.
+    pub async fn backup_into(&self, path: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("VACUUM INTO ?")
+            .bind(path)
+            .execute(&self.connection)
+            .await?;
+        Ok(())
+    }
+}
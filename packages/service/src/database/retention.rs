@@ -0,0 +1,106 @@
+use crate::database::Database;
+
+/// Formats a `julianday` modifier reaching `days` into the past.
+fn days_ago(days: u32) -> String {
+    format!("-{days} days")
+}
+
+impl Database {
+    /// Deletes closed connection sessions that ended more than
+    /// `max_age_days` ago. Sessions still open are always kept.
+    pub async fn prune_connection_sessions_by_age(
+        &self,
+        max_age_days: u32,
+    ) -> Result<u64, sqlx::Error> {
+        let cutoff = days_ago(max_age_days);
+        let result = sqlx::query!(
+            "DELETE FROM connection_sessions
+             WHERE disconnected_at IS NOT NULL
+                AND julianday(disconnected_at) < julianday('now', ?)",
+            cutoff
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Deletes the closed connection sessions of each robot beyond its
+    /// newest `max_per_robot` sessions.
+    pub async fn prune_connection_sessions_per_robot(
+        &self,
+        max_per_robot: u32,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM connection_sessions
+             WHERE disconnected_at IS NOT NULL AND id IN (
+                SELECT id FROM (
+                    SELECT id, ROW_NUMBER() OVER (
+                        PARTITION BY robot_uuid
+                        ORDER BY connected_at DESC, id DESC
+                    ) AS position
+                    FROM connection_sessions
+                ) WHERE position > ?
+             )",
+            max_per_robot
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Deletes jobs, together with their per-robot results, that ended
+    /// more than `max_age_days` ago. Jobs that have not ended are kept.
+    pub async fn prune_jobs(
+        &self,
+        max_age_days: u32,
+    ) -> Result<u64, sqlx::Error> {
+        let cutoff = days_ago(max_age_days);
+        let result = sqlx::query!(
+            "DELETE FROM jobs
+             WHERE finished_at IS NOT NULL
+                AND julianday(finished_at) < julianday('now', ?)",
+            cutoff
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Age in days below which the audit log refuses to delete entries.
+    pub async fn get_audit_log_retention_floor_days(
+        &self,
+    ) -> Result<u32, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT floor_days AS "floor_days: u32" FROM audit_log_retention"#
+        )
+        .fetch_one(&self.connection)
+        .await
+    }
+
+    /// Deletes audit entries older than `max_age_days`. The audit log
+    /// refuses to delete entries younger than its retention floor.
+    pub async fn prune_audit_log(
+        &self,
+        max_age_days: u32,
+    ) -> Result<u64, sqlx::Error> {
+        let cutoff = days_ago(max_age_days);
+        let result = sqlx::query!(
+            "DELETE FROM audit_log
+             WHERE julianday(created_at) < julianday('now', ?)",
+            cutoff
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Refreshes the query planner statistics and, with `vacuum`, rebuilds
+    /// the database file to return the space of deleted rows.
+    pub async fn optimize(&self, vacuum: bool) -> Result<(), sqlx::Error> {
+        sqlx::query("ANALYZE").execute(&self.connection).await?;
+        if vacuum {
+            sqlx::query("VACUUM").execute(&self.connection).await?;
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,24 @@
-use poem_openapi::Object;
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use poem_openapi::{Enum, Object};
 use serde::{Deserialize, Serialize};
-use sqlx::prelude::FromRow;
+use sqlx::{SqliteConnection, prelude::FromRow};
+
+use crate::{
+    database::Database,
+    utils::credentials::{generate_token, hash_token},
+};
 
-use crate::database::Database;
+const ROBOT_SECRET_PREFIX: &str = "rmcsr_";
+
+/// The secret a robot presents when connecting.
+#[derive(Debug, Clone)]
+pub struct RobotSecret {
+    pub hash: String,
+    /// Whether the robot has picked the secret up.
+    pub delivered: bool,
+}
 
 #[derive(Debug, Clone, FromRow, Object, Serialize, Deserialize)]
 pub struct RobotIdent {
@@ -11,66 +27,635 @@ pub struct RobotIdent {
     pub uuid: String,
 }
 
-impl Database {
-    pub async fn get_robots(&self) -> Result<Vec<String>, sqlx::Error> {
-        let rows = sqlx::query!("SELECT uuid FROM robots")
-            .fetch_all(&self.connection)
-            .await?;
-        Ok(rows.into_iter().map(|row| row.uuid).collect())
+/// Competition role of a robot.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    Enum,
+    sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum RobotRole {
+    Hero,
+    Engineer,
+    Infantry,
+    Sentry,
+    Aerial,
+    Dart,
+}
+
+impl RobotRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RobotRole::Hero => "hero",
+            RobotRole::Engineer => "engineer",
+            RobotRole::Infantry => "infantry",
+            RobotRole::Sentry => "sentry",
+            RobotRole::Aerial => "aerial",
+            RobotRole::Dart => "dart",
+        }
     }
+}
 
-    pub async fn get_robot_by_id(
-        &self,
-        uuid: &str,
-    ) -> Result<Option<RobotIdent>, sqlx::Error> {
-        let row = sqlx::query_as!(
-            RobotIdent,
-            "SELECT mac, name, uuid FROM robots WHERE uuid = ?",
-            uuid
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    Enum,
+    sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum TeamColor {
+    Red,
+    Blue,
+}
+
+impl TeamColor {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TeamColor::Red => "red",
+            TeamColor::Blue => "blue",
+        }
+    }
+}
+
+/// Whether a registered robot has been admitted to the fleet. Only
+/// approved robots receive instructions.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum EnrollmentStatus {
+    /// Registered and waiting for an admin's decision.
+    Pending,
+    Approved,
+    /// Turned down while pending; the robot may not connect.
+    Rejected,
+    /// Removed from the fleet after being approved; the robot may not
+    /// connect.
+    Revoked,
+}
+
+impl EnrollmentStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EnrollmentStatus::Pending => "pending",
+            EnrollmentStatus::Approved => "approved",
+            EnrollmentStatus::Rejected => "rejected",
+            EnrollmentStatus::Revoked => "revoked",
+        }
+    }
+
+    /// Whether the robot is refused a websocket connection.
+    pub fn is_banned(self) -> bool {
+        matches!(self, EnrollmentStatus::Rejected | EnrollmentStatus::Revoked)
+    }
+}
+
+/// A robot's identity together with its fleet metadata.
+#[derive(Debug, Clone, Object, Serialize, Deserialize)]
+pub struct RobotProfile {
+    #[oai(flatten)]
+    #[serde(flatten)]
+    pub ident: RobotIdent,
+    pub role: Option<RobotRole>,
+    pub team_color: Option<TeamColor>,
+    pub tags: Vec<String>,
+    pub enrollment: EnrollmentStatus,
+    /// When the robot was decommissioned. Archived robots are left out of
+    /// robot lists, bulk actions and the address index.
+    pub archived_at: Option<DateTime<Utc>>,
+}
+
+/// Last recorded presence of a robot, kept while it is offline.
+#[derive(Debug, Clone, FromRow)]
+pub struct RobotPresenceRow {
+    pub uuid: String,
+    pub version: Option<String>,
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+/// Criteria selecting robots by their fleet metadata. Empty criteria
+/// match every robot; a robot must have one of the listed roles and carry
+/// all listed tags to match.
+#[derive(Debug, Clone, Default)]
+pub struct RobotFilter {
+    pub roles: Vec<RobotRole>,
+    pub team_color: Option<TeamColor>,
+    pub tags: Vec<String>,
+}
+
+impl RobotFilter {
+    pub fn is_empty(&self) -> bool {
+        self.roles.is_empty()
+            && self.team_color.is_none()
+            && normalize_tags(&self.tags).is_empty()
+    }
+
+    /// Whether the robot matches. Tags are normalized like stored tags
+    /// before they are compared.
+    pub fn matches(&self, profile: &RobotProfile) -> bool {
+        (self.roles.is_empty()
+            || profile.role.is_some_and(|role| self.roles.contains(&role)))
+            && self
+                .team_color
+                .is_none_or(|color| profile.team_color == Some(color))
+            && normalize_tags(&self.tags)
+                .iter()
+                .all(|tag| profile.tags.contains(tag))
+    }
+}
+
+/// What happens to the connection history and job results of a deleted
+/// robot. Audit log entries are always kept.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Enum,
+)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+pub enum RobotHistory {
+    #[default]
+    Retain,
+    Purge,
+}
+
+/// Brings a MAC address to the lower-case, colon-separated form robots
+/// are matched by.
+pub fn normalize_mac(mac: &str) -> String {
+    mac.trim().to_ascii_lowercase().replace('-', ":")
+}
+
+/// Escapes `text` to match literally inside a `LIKE` pattern with
+/// `ESCAPE '\'`.
+fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Trims tags, drops empty ones and removes duplicates.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = tags
+        .iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    normalized.sort();
+    normalized.dedup();
+    normalized
+}
+
+/// Moves the network information of `duplicate` to `target` unless the
+/// target has its own.
+async fn move_network_info(
+    conn: &mut SqliteConnection,
+    target: &str,
+    duplicate: &str,
+) -> Result<(), sqlx::Error> {
+    let target_has_network = sqlx::query_scalar!(
+        "SELECT robot_uuid FROM network_info WHERE robot_uuid = ?",
+        target
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .is_some();
+    if !target_has_network {
+        sqlx::query!(
+            "UPDATE network_info SET robot_uuid = ?
+             WHERE robot_uuid = ?",
+            target,
+            duplicate
         )
-        .fetch_optional(&self.connection)
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query!(
+            "UPDATE network_interfaces SET robot_uuid = ?
+             WHERE robot_uuid = ?",
+            target,
+            duplicate
+        )
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query!(
+            "UPDATE network_addresses SET robot_uuid = ?
+             WHERE robot_uuid = ?",
+            target,
+            duplicate
+        )
+        .execute(&mut *conn)
         .await?;
-        Ok(row)
     }
+    Ok(())
+}
 
+impl Database {
+    /// Registers a robot or updates its identity, and returns its
+    /// enrollment status. New robots are pending unless `approve` is set,
+    /// which also approves a robot that is still pending. A known machine ID
+    /// is kept when the robot does not report one.
     pub async fn register_robot(
         &self,
         mac_address: &str,
         name: &str,
         uuid: &str,
-    ) -> Result<(), sqlx::Error> {
-        sqlx::query!(
-            "
+        machine_id: Option<&str>,
+        approve: bool,
+    ) -> Result<EnrollmentStatus, sqlx::Error> {
+        let enrollment = if approve {
+            EnrollmentStatus::Approved
+        } else {
+            EnrollmentStatus::Pending
+        };
+        let mac_address = normalize_mac(mac_address);
+        sqlx::query_scalar!(
+            r#"
                 INSERT INTO robots
-                (mac, name, uuid) VALUES (?, ?, ?)
+                (mac, name, uuid, machine_id, enrollment)
+                VALUES (?, ?, ?, ?, ?)
                 ON CONFLICT(uuid) DO UPDATE SET
-                mac=excluded.mac, name=excluded.name
-            ",
+                mac=excluded.mac, name=excluded.name,
+                machine_id = COALESCE(excluded.machine_id, robots.machine_id),
+                enrollment = CASE
+                    WHEN robots.enrollment = 'pending'
+                    THEN excluded.enrollment
+                    ELSE robots.enrollment
+                END
+                RETURNING enrollment AS "enrollment: EnrollmentStatus"
+            "#,
             mac_address,
             name,
+            uuid,
+            machine_id,
+            enrollment
+        )
+        .fetch_one(&self.connection)
+        .await
+    }
+
+    pub async fn get_robot_enrollment(
+        &self,
+        uuid: &str,
+    ) -> Result<Option<EnrollmentStatus>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+                SELECT enrollment AS "enrollment: EnrollmentStatus"
+                FROM robots WHERE uuid = ?
+            "#,
             uuid
         )
-        .execute(&self.connection)
+        .fetch_optional(&self.connection)
+        .await
+    }
+
+    /// Moves a robot to `to` if its enrollment status is one of `from`.
+    /// Returns `false` if the robot does not exist or is in another state.
+    pub async fn transition_robot_enrollment(
+        &self,
+        uuid: &str,
+        from: &[EnrollmentStatus],
+        to: EnrollmentStatus,
+    ) -> Result<bool, sqlx::Error> {
+        let mut transaction = self.connection.begin().await?;
+        let current = sqlx::query_scalar!(
+            r#"
+                SELECT enrollment AS "enrollment: EnrollmentStatus"
+                FROM robots WHERE uuid = ?
+            "#,
+            uuid
+        )
+        .fetch_optional(&mut *transaction)
         .await?;
-        Ok(())
+        if !current.is_some_and(|current| from.contains(&current)) {
+            return Ok(false);
+        }
+        sqlx::query!(
+            "UPDATE robots SET enrollment = ? WHERE uuid = ?",
+            to,
+            uuid
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    /// Issues a new connection secret to a robot, replacing any previous
+    /// one and those of the robots merged into it. The secret is handed out
+    /// once by [`Self::take_robot_secret`].
+    pub async fn issue_robot_secret(
+        &self,
+        uuid: &str,
+    ) -> Result<(), sqlx::Error> {
+        let secret = generate_token(ROBOT_SECRET_PREFIX);
+        let secret_hash = hash_token(&secret);
+        let mut transaction = self.connection.begin().await?;
+        sqlx::query!(
+            "UPDATE robots SET secret_hash = ?, undelivered_secret = ?
+             WHERE uuid = ?",
+            secret_hash,
+            secret,
+            uuid
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            "UPDATE robot_aliases
+             SET secret_hash = NULL, undelivered_secret = NULL
+             WHERE robot_uuid = ?",
+            uuid
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await
     }
 
-    pub async fn fuzz_search_by_name(
+    pub async fn get_robot_secret(
+        &self,
+        uuid: &str,
+    ) -> Result<Option<RobotSecret>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+                SELECT secret_hash,
+                    undelivered_secret IS NULL AS "delivered!: bool"
+                FROM robots WHERE uuid = ?
+            "#,
+            uuid
+        )
+        .fetch_optional(&self.connection)
+        .await?;
+        Ok(row.and_then(|row| {
+            row.secret_hash.map(|hash| RobotSecret {
+                hash,
+                delivered: row.delivered,
+            })
+        }))
+    }
+
+    /// Returns the connection secret of an approved robot unless it was
+    /// handed out before, and forgets it.
+    pub async fn take_robot_secret(
+        &self,
+        uuid: &str,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let mut transaction = self.connection.begin().await?;
+        let secret = sqlx::query_scalar!(
+            "SELECT undelivered_secret FROM robots
+             WHERE uuid = ? AND enrollment = 'approved'",
+            uuid
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        .flatten();
+        if secret.is_some() {
+            sqlx::query!(
+                "UPDATE robots SET undelivered_secret = NULL WHERE uuid = ?",
+                uuid
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(secret)
+    }
+
+    /// Returns the secret the robot merged under `alias_uuid` held before
+    /// the merge, which its bot keeps presenting.
+    pub async fn get_alias_secret(
+        &self,
+        alias_uuid: &str,
+    ) -> Result<Option<RobotSecret>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+                SELECT secret_hash,
+                    undelivered_secret IS NULL AS "delivered!: bool"
+                FROM robot_aliases WHERE alias_uuid = ?
+            "#,
+            alias_uuid
+        )
+        .fetch_optional(&self.connection)
+        .await?;
+        Ok(row.and_then(|row| {
+            row.secret_hash.map(|hash| RobotSecret {
+                hash,
+                delivered: row.delivered,
+            })
+        }))
+    }
+
+    /// Like [`Self::take_robot_secret`] for the secret of the robot merged
+    /// under `alias_uuid`, while the robot it was merged into is approved.
+    pub async fn take_alias_secret(
+        &self,
+        alias_uuid: &str,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let mut transaction = self.connection.begin().await?;
+        let secret = sqlx::query_scalar!(
+            "SELECT undelivered_secret FROM robot_aliases
+             WHERE alias_uuid = ? AND robot_uuid IN
+                (SELECT uuid FROM robots WHERE enrollment = 'approved')",
+            alias_uuid
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        .flatten();
+        if secret.is_some() {
+            sqlx::query!(
+                "UPDATE robot_aliases SET undelivered_secret = NULL
+                 WHERE alias_uuid = ?",
+                alias_uuid
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(secret)
+    }
+
+    /// Finds the robot a device has been registered as before, so that a
+    /// reinstalled robot keeps its UUID.
+    ///
+    /// A robot with the same machine ID matches first. Otherwise robots
+    /// with the same MAC address match unless they report a different
+    /// machine ID, preferring those whose name contains `name_hint` and
+    /// then the most recently seen.
+    pub async fn find_robot_by_identity(
         &self,
-        username: &str,
         mac_address: &str,
+        machine_id: Option<&str>,
+        name_hint: Option<&str>,
     ) -> Result<Option<RobotIdent>, sqlx::Error> {
-        let pattern = format!("%{username}%");
-        let row = sqlx::query_as!(
+        let mac_address = normalize_mac(mac_address);
+        let name_pattern =
+            name_hint.map(|name| format!("%{}%", escape_like(name)));
+        sqlx::query_as!(
             RobotIdent,
-            "SELECT mac, name, uuid FROM robots
-            WHERE name = ? AND mac = ? LIMIT 1",
-            pattern,
-            mac_address
+            r#"
+                SELECT mac, name, uuid FROM robots
+                WHERE (?2 IS NOT NULL AND machine_id = ?2)
+                    OR ((?2 IS NULL OR machine_id IS NULL)
+                        AND lower(replace(mac, '-', ':')) = ?1)
+                ORDER BY (?2 IS NOT NULL AND machine_id = ?2) DESC,
+                    (?3 IS NOT NULL AND name LIKE ?3 ESCAPE '\') DESC,
+                    last_seen IS NULL, last_seen DESC
+                LIMIT 1
+            "#,
+            mac_address,
+            machine_id,
+            name_pattern
+        )
+        .fetch_optional(&self.connection)
+        .await
+    }
+
+    /// Finds the most recently seen robot whose name contains `name`, for
+    /// devices whose MAC address changed since they were registered.
+    /// Robots that report a machine ID other than `machine_id` are skipped.
+    pub async fn find_robot_by_name(
+        &self,
+        name: &str,
+        machine_id: Option<&str>,
+    ) -> Result<Option<RobotIdent>, sqlx::Error> {
+        let name = escape_like(name.trim());
+        if name.is_empty() {
+            return Ok(None);
+        }
+        sqlx::query_as!(
+            RobotIdent,
+            r#"
+                SELECT mac, name, uuid FROM robots
+                WHERE name LIKE '%' || ?1 || '%' ESCAPE '\'
+                    AND (?2 IS NULL OR machine_id IS NULL OR machine_id = ?2)
+                ORDER BY last_seen IS NULL, last_seen DESC
+                LIMIT 1
+            "#,
+            name,
+            machine_id
+        )
+        .fetch_optional(&self.connection)
+        .await
+    }
+
+    /// Returns the UUID of the robot `uuid` was merged into, or `uuid`
+    /// itself if it is not an alias.
+    pub async fn resolve_robot_uuid(
+        &self,
+        uuid: &str,
+    ) -> Result<String, sqlx::Error> {
+        let robot_uuid = sqlx::query_scalar!(
+            "SELECT robot_uuid FROM robot_aliases WHERE alias_uuid = ?",
+            uuid
         )
         .fetch_optional(&self.connection)
         .await?;
-        Ok(row)
+        Ok(robot_uuid.unwrap_or_else(|| uuid.to_string()))
+    }
+
+    /// Folds duplicate records of the robot `target` into it and deletes
+    /// them. The target keeps its own identity, enrollment and network
+    /// information; it gains the duplicates' tags, connection history and
+    /// job results, and their role, team color, version and network
+    /// information where it has none. The duplicates' UUIDs become aliases
+    /// of the target so that their bots still reach it, and keep their
+    /// secrets so that the bots can still prove who they are.
+    pub async fn merge_robots(
+        &self,
+        target: &str,
+        duplicates: &[String],
+    ) -> Result<(), sqlx::Error> {
+        let mut transaction = self.connection.begin().await?;
+        for duplicate in duplicates {
+            sqlx::query!(
+                "INSERT OR IGNORE INTO robot_tags (robot_uuid, tag)
+                 SELECT ?, tag FROM robot_tags WHERE robot_uuid = ?",
+                target,
+                duplicate
+            )
+            .execute(&mut *transaction)
+            .await?;
+            sqlx::query!(
+                "UPDATE robots SET
+                    role = COALESCE(role,
+                        (SELECT role FROM robots WHERE uuid = ?2)),
+                    team_color = COALESCE(team_color,
+                        (SELECT team_color FROM robots WHERE uuid = ?2)),
+                    version = COALESCE(version,
+                        (SELECT version FROM robots WHERE uuid = ?2)),
+                    machine_id = COALESCE(machine_id,
+                        (SELECT machine_id FROM robots WHERE uuid = ?2)),
+                    last_seen = (SELECT MAX(last_seen) FROM robots
+                        WHERE uuid IN (?1, ?2))
+                 WHERE uuid = ?1",
+                target,
+                duplicate
+            )
+            .execute(&mut *transaction)
+            .await?;
+
+            move_network_info(&mut transaction, target, duplicate).await?;
+
+            sqlx::query!(
+                "UPDATE connection_sessions SET robot_uuid = ?
+                 WHERE robot_uuid = ?",
+                target,
+                duplicate
+            )
+            .execute(&mut *transaction)
+            .await?;
+            // A job that targeted both records keeps its result for the
+            // duplicate under the duplicate's UUID.
+            sqlx::query!(
+                "UPDATE OR IGNORE job_robots SET robot_uuid = ?
+                 WHERE robot_uuid = ?",
+                target,
+                duplicate
+            )
+            .execute(&mut *transaction)
+            .await?;
+
+            sqlx::query!(
+                "UPDATE robot_aliases SET robot_uuid = ? WHERE robot_uuid = ?",
+                target,
+                duplicate
+            )
+            .execute(&mut *transaction)
+            .await?;
+            sqlx::query!(
+                "INSERT OR REPLACE INTO robot_aliases
+                    (alias_uuid, robot_uuid, secret_hash, undelivered_secret)
+                 SELECT uuid, ?, secret_hash, undelivered_secret
+                 FROM robots WHERE uuid = ?",
+                target,
+                duplicate
+            )
+            .execute(&mut *transaction)
+            .await?;
+            sqlx::query!("DELETE FROM robots WHERE uuid = ?", duplicate)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await
     }
 
     pub async fn set_robot_name(
@@ -83,4 +668,378 @@ impl Database {
             .await?;
         Ok(())
     }
+
+    /// Lists the profiles of all robots that are not archived.
+    pub async fn get_robot_profiles(
+        &self,
+    ) -> Result<Vec<RobotProfile>, sqlx::Error> {
+        self.query_robot_profiles(false).await
+    }
+
+    pub async fn get_archived_robot_profiles(
+        &self,
+    ) -> Result<Vec<RobotProfile>, sqlx::Error> {
+        self.query_robot_profiles(true).await
+    }
+
+    async fn query_robot_profiles(
+        &self,
+        archived: bool,
+    ) -> Result<Vec<RobotProfile>, sqlx::Error> {
+        let rows = sqlx::query!(
+            r#"
+                SELECT mac, name, uuid,
+                    role AS "role: RobotRole",
+                    team_color AS "team_color: TeamColor",
+                    enrollment AS "enrollment: EnrollmentStatus",
+                    archived_at AS "archived_at: DateTime<Utc>"
+                FROM robots WHERE (archived_at IS NOT NULL) = ?
+                ORDER BY name
+            "#,
+            archived
+        )
+        .fetch_all(&self.connection)
+        .await?;
+
+        let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+        let tag_rows =
+            sqlx::query!("SELECT robot_uuid, tag FROM robot_tags ORDER BY tag")
+                .fetch_all(&self.connection)
+                .await?;
+        for row in tag_rows {
+            tags.entry(row.robot_uuid).or_default().push(row.tag);
+        }
+
+        Ok(rows
+            .into_iter()
+            .map(|row| RobotProfile {
+                tags: tags.remove(&row.uuid).unwrap_or_default(),
+                ident: RobotIdent {
+                    mac: row.mac,
+                    name: row.name,
+                    uuid: row.uuid,
+                },
+                role: row.role,
+                team_color: row.team_color,
+                enrollment: row.enrollment,
+                archived_at: row.archived_at,
+            })
+            .collect())
+    }
+
+    pub async fn get_robot_profile(
+        &self,
+        uuid: &str,
+    ) -> Result<Option<RobotProfile>, sqlx::Error> {
+        let Some(row) = sqlx::query!(
+            r#"
+                SELECT mac, name, uuid,
+                    role AS "role: RobotRole",
+                    team_color AS "team_color: TeamColor",
+                    enrollment AS "enrollment: EnrollmentStatus",
+                    archived_at AS "archived_at: DateTime<Utc>"
+                FROM robots WHERE uuid = ?
+            "#,
+            uuid
+        )
+        .fetch_optional(&self.connection)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let tags = sqlx::query_scalar!(
+            "SELECT tag FROM robot_tags WHERE robot_uuid = ? ORDER BY tag",
+            uuid
+        )
+        .fetch_all(&self.connection)
+        .await?;
+
+        Ok(Some(RobotProfile {
+            ident: RobotIdent {
+                mac: row.mac,
+                name: row.name,
+                uuid: row.uuid,
+            },
+            role: row.role,
+            team_color: row.team_color,
+            tags,
+            enrollment: row.enrollment,
+            archived_at: row.archived_at,
+        }))
+    }
+
+    /// Decommissions a robot: it keeps its history but loses its approval.
+    /// Returns `false` if no robot has the given UUID or it is already
+    /// archived.
+    pub async fn archive_robot(&self, uuid: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE robots
+             SET archived_at = CURRENT_TIMESTAMP, enrollment = ?
+             WHERE uuid = ? AND archived_at IS NULL",
+            EnrollmentStatus::Revoked,
+            uuid
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Returns an archived robot to the fleet. It stays revoked until an
+    /// admin approves it again. Returns `false` if no robot has the given
+    /// UUID or it is not archived.
+    pub async fn unarchive_robot(
+        &self,
+        uuid: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE robots SET archived_at = NULL
+             WHERE uuid = ? AND archived_at IS NOT NULL",
+            uuid
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Deletes a robot together with its metadata, network information
+    /// and aliases. Returns `false` if no robot has the given UUID.
+    pub async fn delete_robot(
+        &self,
+        uuid: &str,
+        history: RobotHistory,
+    ) -> Result<bool, sqlx::Error> {
+        let mut transaction = self.connection.begin().await?;
+        let result = sqlx::query!("DELETE FROM robots WHERE uuid = ?", uuid)
+            .execute(&mut *transaction)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        if history == RobotHistory::Purge {
+            sqlx::query!(
+                "DELETE FROM connection_sessions WHERE robot_uuid = ?",
+                uuid
+            )
+            .execute(&mut *transaction)
+            .await?;
+            sqlx::query!("DELETE FROM job_robots WHERE robot_uuid = ?", uuid)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    /// Records that a robot was seen just now, along with the version of
+    /// its bot if it reported one.
+    pub async fn touch_robot(
+        &self,
+        uuid: &str,
+        version: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE robots
+             SET last_seen = CURRENT_TIMESTAMP, version = COALESCE(?, version)
+             WHERE uuid = ?",
+            version,
+            uuid
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the last recorded presence of every robot.
+    pub async fn get_robot_presence(
+        &self,
+    ) -> Result<Vec<RobotPresenceRow>, sqlx::Error> {
+        sqlx::query_as!(
+            RobotPresenceRow,
+            r#"
+                SELECT uuid, version,
+                    last_seen AS "last_seen: DateTime<Utc>"
+                FROM robots
+            "#
+        )
+        .fetch_all(&self.connection)
+        .await
+    }
+
+    /// Lists the profiles of the robots matching `filter`.
+    pub async fn filter_robots(
+        &self,
+        filter: &RobotFilter,
+    ) -> Result<Vec<RobotProfile>, sqlx::Error> {
+        let mut profiles = self.get_robot_profiles().await?;
+        profiles.retain(|profile| filter.matches(profile));
+        Ok(profiles)
+    }
+
+    /// Returns `false` if no robot has the given UUID.
+    pub async fn set_robot_role(
+        &self,
+        uuid: &str,
+        role: Option<RobotRole>,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE robots SET role = ? WHERE uuid = ?",
+            role,
+            uuid
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Returns `false` if no robot has the given UUID.
+    pub async fn set_robot_team_color(
+        &self,
+        uuid: &str,
+        team_color: Option<TeamColor>,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE robots SET team_color = ? WHERE uuid = ?",
+            team_color,
+            uuid
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Replaces the tags of a robot. Returns `false` if no robot has the
+    /// given UUID.
+    pub async fn set_robot_tags(
+        &self,
+        uuid: &str,
+        tags: &[String],
+    ) -> Result<bool, sqlx::Error> {
+        let mut transaction = self.connection.begin().await?;
+        let exists =
+            sqlx::query_scalar!("SELECT uuid FROM robots WHERE uuid = ?", uuid)
+                .fetch_optional(&mut *transaction)
+                .await?
+                .is_some();
+        if !exists {
+            return Ok(false);
+        }
+        sqlx::query!("DELETE FROM robot_tags WHERE robot_uuid = ?", uuid)
+            .execute(&mut *transaction)
+            .await?;
+        for tag in tags {
+            sqlx::query!(
+                "INSERT INTO robot_tags (robot_uuid, tag) VALUES (?, ?)",
+                uuid,
+                tag
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn robot(
+        role: Option<RobotRole>,
+        team_color: Option<TeamColor>,
+        tags: &[&str],
+    ) -> RobotProfile {
+        RobotProfile {
+            ident: RobotIdent {
+                mac: String::new(),
+                name: "robot".to_string(),
+                uuid: "uuid".to_string(),
+            },
+            role,
+            team_color,
+            tags: tags.iter().map(ToString::to_string).collect(),
+            enrollment: EnrollmentStatus::Approved,
+            archived_at: None,
+        }
+    }
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn empty_filter_matches_every_robot() {
+        let filter = RobotFilter {
+            tags: tags(&[" ", ""]),
+            ..RobotFilter::default()
+        };
+        assert!(filter.is_empty());
+        assert!(filter.matches(&robot(None, None, &[])));
+    }
+
+    #[test]
+    fn matches_any_of_the_roles() {
+        let filter = RobotFilter {
+            roles: vec![RobotRole::Hero, RobotRole::Sentry],
+            ..RobotFilter::default()
+        };
+        assert!(filter.matches(&robot(Some(RobotRole::Sentry), None, &[])));
+        assert!(!filter.matches(&robot(Some(RobotRole::Dart), None, &[])));
+        assert!(!filter.matches(&robot(None, None, &[])));
+    }
+
+    #[test]
+    fn matches_the_team_color() {
+        let filter = RobotFilter {
+            team_color: Some(TeamColor::Red),
+            ..RobotFilter::default()
+        };
+        assert!(filter.matches(&robot(None, Some(TeamColor::Red), &[])));
+        assert!(!filter.matches(&robot(None, Some(TeamColor::Blue), &[])));
+        assert!(!filter.matches(&robot(None, None, &[])));
+    }
+
+    #[test]
+    fn requires_every_tag_after_normalizing() {
+        let filter = RobotFilter {
+            tags: tags(&[" lab ", "arm", "arm"]),
+            ..RobotFilter::default()
+        };
+        assert!(filter.matches(&robot(None, None, &["arm", "lab", "x"])));
+        assert!(!filter.matches(&robot(None, None, &["lab"])));
+    }
+
+    #[test]
+    fn combines_all_criteria() {
+        let filter = RobotFilter {
+            roles: vec![RobotRole::Hero],
+            team_color: Some(TeamColor::Blue),
+            tags: tags(&["lab"]),
+        };
+        assert!(filter.matches(&robot(
+            Some(RobotRole::Hero),
+            Some(TeamColor::Blue),
+            &["lab"]
+        )));
+        assert!(!filter.matches(&robot(
+            Some(RobotRole::Hero),
+            Some(TeamColor::Red),
+            &["lab"]
+        )));
+    }
+
+    #[test]
+    fn normalizes_tags_and_macs() {
+        assert_eq!(
+            normalize_tags(&tags(&["b", " a ", "", "b"])),
+            tags(&["a", "b"])
+        );
+        assert_eq!(normalize_mac(" AA-BB-cc:DD "), "aa:bb:cc:dd");
+    }
+
+    #[test]
+    fn escapes_like_wildcards() {
+        assert_eq!(escape_like(r"50%_a\b"), r"50\%\_a\\b");
+    }
 }
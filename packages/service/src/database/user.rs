@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use poem_openapi::{Enum, Object};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::database::Database;
+
+/// What a user may do through the HTTP API. Each role includes the
+/// permissions of the ones before it.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    Enum,
+    sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum UserRole {
+    /// Read robot state, statistics and jobs.
+    Viewer,
+    /// Also instruct robots and edit their metadata.
+    Operator,
+    /// Also manage users.
+    Admin,
+}
+
+impl UserRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UserRole::Viewer => "viewer",
+            UserRole::Operator => "operator",
+            UserRole::Admin => "admin",
+        }
+    }
+}
+
+/// An operator account of the HTTP API.
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct User {
+    pub id: i64,
+    pub username: String,
+    pub role: UserRole,
+    /// When not empty, an operator may only act on robots carrying at least
+    /// one of these tags. Admins are never scoped.
+    pub scope_tags: Vec<String>,
+}
+
+impl User {
+    pub fn is_scoped(&self) -> bool {
+        self.role < UserRole::Admin && !self.scope_tags.is_empty()
+    }
+
+    /// Whether the user may act on a robot carrying `robot_tags`.
+    pub fn can_act_on(&self, robot_tags: &[String]) -> bool {
+        !self.is_scoped()
+            || robot_tags.iter().any(|tag| self.scope_tags.contains(tag))
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct UserRow {
+    id: i64,
+    username: String,
+    role: UserRole,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct UserCredentials {
+    pub id: i64,
+    pub password_hash: String,
+}
+
+/// An API token as listed to its owner; the token itself is only shown
+/// once, when it is created.
+#[derive(Debug, Clone, Serialize, Deserialize, Object, FromRow)]
+pub struct ApiTokenInfo {
+    pub id: i64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl Database {
+    pub async fn count_users(&self) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!("SELECT COUNT(*) FROM users")
+            .fetch_one(&self.connection)
+            .await
+    }
+
+    /// Attaches the scope tags of each user.
+    async fn with_scope_tags(
+        &self,
+        rows: Vec<UserRow>,
+    ) -> Result<Vec<User>, sqlx::Error> {
+        let mut scope_tags: HashMap<i64, Vec<String>> = HashMap::new();
+        let tag_rows = sqlx::query!(
+            "SELECT user_id, tag FROM user_scope_tags ORDER BY tag"
+        )
+        .fetch_all(&self.connection)
+        .await?;
+        for row in tag_rows {
+            scope_tags.entry(row.user_id).or_default().push(row.tag);
+        }
+        Ok(rows
+            .into_iter()
+            .map(|row| User {
+                scope_tags: scope_tags.remove(&row.id).unwrap_or_default(),
+                id: row.id,
+                username: row.username,
+                role: row.role,
+            })
+            .collect())
+    }
+
+    async fn with_scope_tags_one(
+        &self,
+        row: Option<UserRow>,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let scope_tags = sqlx::query_scalar!(
+            "SELECT tag FROM user_scope_tags WHERE user_id = ? ORDER BY tag",
+            row.id
+        )
+        .fetch_all(&self.connection)
+        .await?;
+        Ok(Some(User {
+            id: row.id,
+            username: row.username,
+            role: row.role,
+            scope_tags,
+        }))
+    }
+
+    pub async fn list_users(&self) -> Result<Vec<User>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            UserRow,
+            r#"
+                SELECT id, username, role AS "role: UserRole"
+                FROM users ORDER BY username
+            "#
+        )
+        .fetch_all(&self.connection)
+        .await?;
+        self.with_scope_tags(rows).await
+    }
+
+    pub async fn get_user(&self, id: i64) -> Result<Option<User>, sqlx::Error> {
+        let row = sqlx::query_as!(
+            UserRow,
+            r#"
+                SELECT id, username, role AS "role: UserRole"
+                FROM users WHERE id = ?
+            "#,
+            id
+        )
+        .fetch_optional(&self.connection)
+        .await?;
+        self.with_scope_tags_one(row).await
+    }
+
+    pub async fn count_admins(&self) -> Result<i64, sqlx::Error> {
+        let admin = UserRole::Admin;
+        sqlx::query_scalar!("SELECT COUNT(*) FROM users WHERE role = ?", admin)
+            .fetch_one(&self.connection)
+            .await
+    }
+
+    /// Creates a user. Returns `None` if the username is already taken.
+    pub async fn create_user(
+        &self,
+        username: &str,
+        password_hash: &str,
+        role: UserRole,
+        scope_tags: &[String],
+    ) -> Result<Option<User>, sqlx::Error> {
+        let mut transaction = self.connection.begin().await?;
+        let result = sqlx::query!(
+            "INSERT INTO users (username, password_hash, role) VALUES (?, ?, ?)
+             ON CONFLICT (username) DO NOTHING",
+            username,
+            password_hash,
+            role
+        )
+        .execute(&mut *transaction)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        let id = result.last_insert_rowid();
+        for tag in scope_tags {
+            sqlx::query!(
+                "INSERT INTO user_scope_tags (user_id, tag) VALUES (?, ?)",
+                id,
+                tag
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(Some(User {
+            id,
+            username: username.to_string(),
+            role,
+            scope_tags: scope_tags.to_vec(),
+        }))
+    }
+
+    /// Replaces a user's role and scope tags. Returns `false` if no user
+    /// has the given ID.
+    pub async fn set_user_role(
+        &self,
+        user_id: i64,
+        role: UserRole,
+        scope_tags: &[String],
+    ) -> Result<bool, sqlx::Error> {
+        let mut transaction = self.connection.begin().await?;
+        let result = sqlx::query!(
+            "UPDATE users SET role = ? WHERE id = ?",
+            role,
+            user_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        sqlx::query!("DELETE FROM user_scope_tags WHERE user_id = ?", user_id)
+            .execute(&mut *transaction)
+            .await?;
+        for tag in scope_tags {
+            sqlx::query!(
+                "INSERT INTO user_scope_tags (user_id, tag) VALUES (?, ?)",
+                user_id,
+                tag
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    pub async fn get_user_credentials(
+        &self,
+        username: &str,
+    ) -> Result<Option<UserCredentials>, sqlx::Error> {
+        sqlx::query_as!(
+            UserCredentials,
+            "SELECT id, password_hash FROM users WHERE username = ?",
+            username
+        )
+        .fetch_optional(&self.connection)
+        .await
+    }
+
+    /// Replaces a user's password and ends all of their login sessions.
+    pub async fn set_user_password(
+        &self,
+        user_id: i64,
+        password_hash: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut transaction = self.connection.begin().await?;
+        sqlx::query!(
+            "UPDATE users SET password_hash = ? WHERE id = ?",
+            password_hash,
+            user_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!("DELETE FROM auth_sessions WHERE user_id = ?", user_id)
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await
+    }
+
+    pub async fn create_auth_session(
+        &self,
+        token_hash: &str,
+        user_id: i64,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        // Expired sessions are only ever looked up by their owners' stale
+        // cookies, so dropping them on login keeps the table small.
+        sqlx::query!(
+            "DELETE FROM auth_sessions
+             WHERE julianday(expires_at) <= julianday('now')"
+        )
+        .execute(&self.connection)
+        .await?;
+        sqlx::query!(
+            "INSERT INTO auth_sessions (token_hash, user_id, expires_at)
+             VALUES (?, ?, ?)",
+            token_hash,
+            user_id,
+            expires_at
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the user owning an unexpired login session.
+    pub async fn get_session_user(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let row = sqlx::query_as!(
+            UserRow,
+            r#"
+                SELECT u.id, u.username, u.role AS "role: UserRole"
+                FROM auth_sessions s JOIN users u ON u.id = s.user_id
+                WHERE s.token_hash = ?
+                    AND julianday(s.expires_at) > julianday('now')
+            "#,
+            token_hash
+        )
+        .fetch_optional(&self.connection)
+        .await?;
+        self.with_scope_tags_one(row).await
+    }
+
+    pub async fn delete_auth_session(
+        &self,
+        token_hash: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM auth_sessions WHERE token_hash = ?",
+            token_hash
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn create_api_token(
+        &self,
+        user_id: i64,
+        name: &str,
+        token_hash: &str,
+    ) -> Result<ApiTokenInfo, sqlx::Error> {
+        let result = sqlx::query!(
+            "INSERT INTO api_tokens (user_id, name, token_hash)
+             VALUES (?, ?, ?)",
+            user_id,
+            name,
+            token_hash
+        )
+        .execute(&self.connection)
+        .await?;
+        let id = result.last_insert_rowid();
+        sqlx::query_as!(
+            ApiTokenInfo,
+            r#"
+                SELECT id, name,
+                    created_at AS "created_at: DateTime<Utc>",
+                    last_used_at AS "last_used_at: DateTime<Utc>"
+                FROM api_tokens WHERE id = ?
+            "#,
+            id
+        )
+        .fetch_one(&self.connection)
+        .await
+    }
+
+    /// Returns the owner of an API token and records that it was used.
+    pub async fn use_api_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let row = sqlx::query_as!(
+            UserRow,
+            r#"
+                SELECT u.id, u.username, u.role AS "role: UserRole"
+                FROM api_tokens t JOIN users u ON u.id = t.user_id
+                WHERE t.token_hash = ?
+            "#,
+            token_hash
+        )
+        .fetch_optional(&self.connection)
+        .await?;
+        if row.is_some() {
+            sqlx::query!(
+                "UPDATE api_tokens SET last_used_at = CURRENT_TIMESTAMP
+                 WHERE token_hash = ?",
+                token_hash
+            )
+            .execute(&self.connection)
+            .await?;
+        }
+        self.with_scope_tags_one(row).await
+    }
+
+    pub async fn list_api_tokens(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<ApiTokenInfo>, sqlx::Error> {
+        sqlx::query_as!(
+            ApiTokenInfo,
+            r#"
+                SELECT id, name,
+                    created_at AS "created_at: DateTime<Utc>",
+                    last_used_at AS "last_used_at: DateTime<Utc>"
+                FROM api_tokens WHERE user_id = ?
+                ORDER BY created_at, id
+            "#,
+            user_id
+        )
+        .fetch_all(&self.connection)
+        .await
+    }
+
+    /// Returns `false` if the user owns no token with the given ID.
+    pub async fn delete_api_token(
+        &self,
+        user_id: i64,
+        id: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM api_tokens WHERE id = ? AND user_id = ?",
+            id,
+            user_id
+        )
+        .execute(&self.connection)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,156 @@
+//! Optional DNS responder resolving `<alias>.<zone>` to the addresses
+//! found in the robot's latest network info. Robots are named by the same
+//! aliases as in the inventory exports.
+//!
+//! The responder is authoritative for the configured zone only and refuses
+//! every other query, so it can be listed as a conditional forwarder or as
+//! a secondary resolver without leaking other lookups.
+
+use std::{net::IpAddr, sync::Arc};
+
+use tokio::{net::UdpSocket, task::JoinHandle};
+
+use crate::{
+    api::export::inventory::Inventory,
+    constant::env::{
+        DEFAULT_DNS_TTL_SECS, DEFAULT_DNS_ZONE, ENV_NAME_DNS_BIND_ADDR,
+        ENV_NAME_DNS_TTL_SECS, ENV_NAME_DNS_ZONE,
+    },
+    database::{Database, robot::RobotFilter, with_database},
+    dns::packet::{
+        CLASS_IN, OPCODE_QUERY, Query, ResponseCode, TYPE_A, TYPE_AAAA,
+    },
+    env::parse_env_or,
+    utils::network::IgnoredInterfaces,
+};
+
+pub mod packet;
+
+/// Large enough for any query without EDNS.
+const MAX_QUERY_LEN: usize = 512;
+
+pub struct DnsConfig {
+    pub bind_addr: String,
+    /// Lowercase zone without leading or trailing dots.
+    pub zone: String,
+    pub ttl: u32,
+}
+
+impl DnsConfig {
+    /// Reads the responder configuration. Returns `None` when no bind
+    /// address is configured, which disables the responder.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(bind_addr) = std::env::var(ENV_NAME_DNS_BIND_ADDR) else {
+            return Ok(None);
+        };
+        let zone =
+            parse_env_or(ENV_NAME_DNS_ZONE, DEFAULT_DNS_ZONE.to_string())?
+                .trim_matches('.')
+                .to_ascii_lowercase();
+        Ok(Some(Self {
+            bind_addr,
+            zone,
+            ttl: parse_env_or(ENV_NAME_DNS_TTL_SECS, DEFAULT_DNS_TTL_SECS)?,
+        }))
+    }
+}
+
+/// Binds the responder socket and serves queries in the background.
+pub async fn spawn(config: DnsConfig) -> anyhow::Result<JoinHandle<()>> {
+    let socket = Arc::new(UdpSocket::bind(&config.bind_addr).await?);
+    log::info!(
+        "Serving DNS for zone `{}` on {}",
+        config.zone,
+        config.bind_addr
+    );
+    let config = Arc::new(config);
+
+    Ok(tokio::spawn(async move {
+        let mut buf = [0u8; MAX_QUERY_LEN];
+        loop {
+            let (len, peer) = match socket.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(e) => {
+                    log::warn!("Failed to receive DNS query: {e}");
+                    continue;
+                }
+            };
+            let Some(query) = Query::parse(&buf[..len]) else {
+                continue;
+            };
+            let socket = socket.clone();
+            let config = config.clone();
+            tokio::spawn(async move {
+                let response = answer(&config, &query).await;
+                if let Err(e) = socket.send_to(&response, peer).await {
+                    log::warn!("Failed to send DNS response to {peer}: {e}");
+                }
+            });
+        }
+    }))
+}
+
+async fn answer(config: &DnsConfig, query: &Query) -> Vec<u8> {
+    if query.opcode != OPCODE_QUERY {
+        return query.respond(ResponseCode::NotImplemented, &[], config.ttl);
+    }
+    let Some(question) = &query.question else {
+        return query.respond(ResponseCode::FormatError, &[], config.ttl);
+    };
+    if question.qclass != CLASS_IN {
+        return query.respond(ResponseCode::Refused, &[], config.ttl);
+    }
+    let Some(robot_name) = question
+        .name
+        .strip_suffix(config.zone.as_str())
+        .and_then(|name| name.strip_suffix('.'))
+        .filter(|name| !name.is_empty() && !name.contains('.'))
+    else {
+        return query.respond(ResponseCode::Refused, &[], config.ttl);
+    };
+    match resolve(robot_name).await {
+        // Only addresses are served; other types of an existing name get an
+        // empty answer, so that resolvers know it has no such records.
+        Ok(Some(addrs)) => {
+            let answers: Vec<IpAddr> = addrs
+                .into_iter()
+                .filter(|ip| match question.qtype {
+                    TYPE_A => ip.is_ipv4(),
+                    TYPE_AAAA => ip.is_ipv6(),
+                    _ => false,
+                })
+                .collect();
+            query.respond(ResponseCode::NoError, &answers, config.ttl)
+        }
+        Ok(None) => query.respond(ResponseCode::NameError, &[], config.ttl),
+        Err(e) => {
+            log::error!("Failed to resolve robot `{robot_name}`: {e:?}");
+            query.respond(ResponseCode::ServerFailure, &[], config.ttl)
+        }
+    }
+}
+
+/// Looks up the reachable addresses of the robot with inventory alias
+/// `alias`. Returns `None` when no robot is listed in the inventory under
+/// it.
+async fn resolve(alias: &str) -> anyhow::Result<Option<Vec<IpAddr>>> {
+    let robots = with_database(Database::get_robot_profiles)?.await?;
+    let addresses = with_database(Database::get_all_robot_addresses)?.await?;
+    let ignored = IgnoredInterfaces::from_env()?;
+    let inventory = Inventory::new(
+        robots,
+        addresses.clone(),
+        &ignored,
+        &RobotFilter::default(),
+    );
+    let Some(host) = inventory.find_alias(alias) else {
+        return Ok(None);
+    };
+    Ok(Some(
+        addresses
+            .iter()
+            .filter(|(uuid, _)| *uuid == host.uuid)
+            .filter_map(|(_, row)| row.reachable_ip(&ignored))
+            .collect(),
+    ))
+}
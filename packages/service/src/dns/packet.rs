@@ -0,0 +1,273 @@
+//! Minimal DNS wire format support, covering what the responder needs:
+//! parsing a single-question query and encoding an answer with `A`/`AAAA`
+//! records (RFC 1035, RFC 3596).
+
+use std::net::IpAddr;
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_AAAA: u16 = 28;
+pub const CLASS_IN: u16 = 1;
+
+pub const OPCODE_QUERY: u8 = 0;
+
+/// Largest response sent over UDP without EDNS.
+const MAX_UDP_RESPONSE_LEN: usize = 512;
+const HEADER_LEN: usize = 12;
+/// Pointer to the question name, which always starts right after the
+/// header.
+const QUESTION_NAME_POINTER: [u8; 2] = [0xc0, 0x0c];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ResponseCode {
+    NoError = 0,
+    FormatError = 1,
+    ServerFailure = 2,
+    NameError = 3,
+    NotImplemented = 4,
+    Refused = 5,
+}
+
+#[derive(Debug, Clone)]
+pub struct Question {
+    /// Lowercase name without the trailing dot.
+    pub name: String,
+    pub qtype: u16,
+    pub qclass: u16,
+    /// The question section as received, echoed back in the response.
+    raw: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Query {
+    pub id: u16,
+    pub opcode: u8,
+    recursion_desired: bool,
+    pub question: Option<Question>,
+}
+
+impl Query {
+    /// Parses a query. Returns `None` when the packet is too short to
+    /// answer at all; a query whose question cannot be parsed is returned
+    /// without one so that it can be answered with a format error.
+    pub fn parse(packet: &[u8]) -> Option<Self> {
+        if packet.len() < HEADER_LEN {
+            return None;
+        }
+        let id = u16::from_be_bytes([packet[0], packet[1]]);
+        let is_response = packet[2] & 0x80 != 0;
+        if is_response {
+            return None;
+        }
+        let opcode = (packet[2] >> 3) & 0x0f;
+        let recursion_desired = packet[2] & 0x01 != 0;
+        let question_count = u16::from_be_bytes([packet[4], packet[5]]);
+
+        let question = if question_count == 1 {
+            Question::parse(&packet[HEADER_LEN..])
+        } else {
+            None
+        };
+
+        Some(Self {
+            id,
+            opcode,
+            recursion_desired,
+            question,
+        })
+    }
+
+    /// Encodes a response carrying `answers`, dropping trailing answers
+    /// (and setting the truncation flag) if they do not fit into a UDP
+    /// response.
+    pub fn respond(
+        &self,
+        code: ResponseCode,
+        answers: &[IpAddr],
+        ttl: u32,
+    ) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(MAX_UDP_RESPONSE_LEN);
+        packet.extend_from_slice(&self.id.to_be_bytes());
+        // QR = 1, AA = 1, RA = 0.
+        packet.push(
+            0x80 | (self.opcode << 3) | 0x04 | u8::from(self.recursion_desired),
+        );
+        packet.push(code as u8);
+        packet.extend_from_slice(
+            &u16::from(self.question.is_some()).to_be_bytes(),
+        );
+        // Answer count, patched below once the answers are written.
+        packet.extend_from_slice(&[0, 0]);
+        // Authority and additional counts.
+        packet.extend_from_slice(&[0, 0, 0, 0]);
+
+        let Some(question) = &self.question else {
+            return packet;
+        };
+        packet.extend_from_slice(&question.raw);
+
+        let mut answer_count: u16 = 0;
+        for answer in answers {
+            let record = encode_record(*answer, ttl);
+            if packet.len() + record.len() > MAX_UDP_RESPONSE_LEN {
+                packet[2] |= 0x02;
+                break;
+            }
+            packet.extend_from_slice(&record);
+            answer_count += 1;
+        }
+        packet[6..8].copy_from_slice(&answer_count.to_be_bytes());
+        packet
+    }
+}
+
+impl Question {
+    fn parse(section: &[u8]) -> Option<Self> {
+        let mut labels = Vec::new();
+        let mut offset = 0;
+        loop {
+            let len = usize::from(*section.get(offset)?);
+            offset += 1;
+            if len == 0 {
+                break;
+            }
+            // Compression pointers and extended label types are not
+            // expected in a question.
+            if len > 63 {
+                return None;
+            }
+            let label = section.get(offset..offset + len)?;
+            labels.push(std::str::from_utf8(label).ok()?.to_ascii_lowercase());
+            offset += len;
+        }
+        let fixed = section.get(offset..offset + 4)?;
+        let qtype = u16::from_be_bytes([fixed[0], fixed[1]]);
+        let qclass = u16::from_be_bytes([fixed[2], fixed[3]]);
+        offset += 4;
+
+        Some(Self {
+            name: labels.join("."),
+            qtype,
+            qclass,
+            raw: section[..offset].to_vec(),
+        })
+    }
+}
+
+fn encode_record(ip: IpAddr, ttl: u32) -> Vec<u8> {
+    let (rtype, data) = match ip {
+        IpAddr::V4(ip) => (TYPE_A, ip.octets().to_vec()),
+        IpAddr::V6(ip) => (TYPE_AAAA, ip.octets().to_vec()),
+    };
+    let mut record = Vec::with_capacity(12 + data.len());
+    record.extend_from_slice(&QUESTION_NAME_POINTER);
+    record.extend_from_slice(&rtype.to_be_bytes());
+    record.extend_from_slice(&CLASS_IN.to_be_bytes());
+    record.extend_from_slice(&ttl.to_be_bytes());
+    // Address data is at most 16 bytes long.
+    #[allow(clippy::cast_possible_truncation)]
+    record.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    record.extend_from_slice(&data);
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    /// Builds a query for `name` with recursion desired.
+    fn query_packet(name: &str, qtype: u16) -> Vec<u8> {
+        let mut packet = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        for label in name.split('.') {
+            packet.push(u8::try_from(label.len()).unwrap());
+            packet.extend_from_slice(label.as_bytes());
+        }
+        packet.push(0);
+        packet.extend_from_slice(&qtype.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn parses_a_query() {
+        let query = Query::parse(&query_packet("Sentry.RMCS.lan", TYPE_A))
+            .expect("query parses");
+        assert_eq!(query.id, 0x1234);
+        assert_eq!(query.opcode, OPCODE_QUERY);
+        let question = query.question.expect("question parses");
+        assert_eq!(question.name, "sentry.rmcs.lan");
+        assert_eq!(question.qtype, TYPE_A);
+        assert_eq!(question.qclass, CLASS_IN);
+    }
+
+    #[test]
+    fn rejects_short_packets_and_responses() {
+        assert!(Query::parse(&[0; HEADER_LEN - 1]).is_none());
+        let mut packet = query_packet("sentry.rmcs.lan", TYPE_A);
+        packet[2] |= 0x80;
+        assert!(Query::parse(&packet).is_none());
+    }
+
+    #[test]
+    fn keeps_queries_with_a_truncated_question() {
+        let mut packet = query_packet("sentry.rmcs.lan", TYPE_A);
+        packet.truncate(packet.len() - 2);
+        let query = Query::parse(&packet).expect("header parses");
+        assert!(query.question.is_none());
+        let response = query.respond(ResponseCode::FormatError, &[], 30);
+        assert_eq!(response.len(), HEADER_LEN);
+        assert_eq!(response[3], ResponseCode::FormatError as u8);
+        assert_eq!(&response[4..6], &[0, 0]);
+    }
+
+    #[test]
+    fn rejects_compressed_question_names() {
+        let mut packet = query_packet("sentry.rmcs.lan", TYPE_A);
+        packet[HEADER_LEN] = 0xc0;
+        let query = Query::parse(&packet).expect("header parses");
+        assert!(query.question.is_none());
+    }
+
+    #[test]
+    fn encodes_answers() {
+        let packet = query_packet("sentry.rmcs.lan", TYPE_A);
+        let query = Query::parse(&packet).expect("query parses");
+        let v4 = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10));
+        let v6 = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        let response = query.respond(ResponseCode::NoError, &[v4, v6], 30);
+
+        assert_eq!(&response[0..2], &[0x12, 0x34]);
+        // QR, AA and RD are set, no truncation.
+        assert_eq!(response[2], 0x85);
+        assert_eq!(response[3], ResponseCode::NoError as u8);
+        assert_eq!(&response[4..6], &[0, 1]);
+        assert_eq!(&response[6..8], &[0, 2]);
+        // The question is echoed back as received.
+        assert_eq!(&response[HEADER_LEN..packet.len()], &packet[HEADER_LEN..]);
+
+        let a = &response[packet.len()..packet.len() + 16];
+        assert_eq!(&a[0..2], &QUESTION_NAME_POINTER);
+        assert_eq!(&a[2..4], &TYPE_A.to_be_bytes());
+        assert_eq!(&a[6..10], &30u32.to_be_bytes());
+        assert_eq!(&a[10..12], &[0, 4]);
+        assert_eq!(&a[12..16], &[192, 168, 1, 10]);
+        let aaaa = &response[packet.len() + 16..];
+        assert_eq!(&aaaa[2..4], &TYPE_AAAA.to_be_bytes());
+        assert_eq!(&aaaa[10..12], &[0, 16]);
+        assert_eq!(aaaa.len(), 28);
+    }
+
+    #[test]
+    fn truncates_answers_that_do_not_fit() {
+        let query = Query::parse(&query_packet("sentry.rmcs.lan", TYPE_A))
+            .expect("query parses");
+        let answers = vec![IpAddr::V4(Ipv4Addr::LOCALHOST); 64];
+        let response = query.respond(ResponseCode::NoError, &answers, 30);
+        assert!(response.len() <= MAX_UDP_RESPONSE_LEN);
+        assert_eq!(response[2] & 0x02, 0x02);
+        let count = u16::from_be_bytes([response[6], response[7]]);
+        assert!(usize::from(count) < answers.len());
+    }
+}
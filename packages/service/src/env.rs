@@ -30,3 +30,20 @@ pub fn load_env() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Reads an optional environment variable and parses it, falling back to
+/// `default` when the variable is unset.
+pub fn parse_env_or<T>(var: &str, default: T) -> anyhow::Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match std::env::var(var) {
+        Ok(value) => value.parse().map_err(|e| {
+            anyhow::anyhow!(
+                "Environment variable `{var}` has invalid value `{value}`: {e}"
+            )
+        }),
+        Err(_) => Ok(default),
+    }
+}
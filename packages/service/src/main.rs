@@ -1,14 +1,37 @@
-use poem::{EndpointExt, Route, get, middleware::Cors};
+use poem::{
+    EndpointExt, IntoEndpoint, Route, get,
+    middleware::{CookieJarManager, Cors},
+};
 use poem_openapi::OpenApiService;
 
-use crate::api::{Api, action::ActionApi, ident::IdentApi, stats::StatsApi};
-use crate::constant::env::{DEFAULT_BIND_ADDR, ENV_NAME_BIND_ADDR};
+use crate::api::{
+    Api,
+    action::ActionApi,
+    audit::{AuditApi, middleware::AuditLog},
+    auth::AuthApi,
+    backup::BackupApi,
+    enrollment::EnrollmentApi,
+    export::ExportApi,
+    health::HealthApi,
+    ident::IdentApi,
+    jobs::JobsApi,
+    registry::RegistryApi,
+    retention::RetentionApi,
+    sessions::SessionsApi,
+    stats::StatsApi,
+};
+use crate::constant::env::{
+    DEFAULT_BIND_ADDR, ENV_NAME_BIND_ADDR, ENV_NAME_CORS_ALLOWED_ORIGINS,
+};
 
 mod api;
+mod cli;
 mod constant;
 mod database;
+mod dns;
 mod env;
 mod logger;
+mod metrics;
 mod service;
 mod utils;
 
@@ -21,34 +44,101 @@ async fn main() -> anyhow::Result<()> {
     let database_url =
         std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     let db = database::Database::new(&database_url).await?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::run(&db, &args).await;
+    }
     db.init().await?;
     database::DATABASE
         .set(db)
         .map_err(|_| anyhow::anyhow!("Failed to set database"))?;
+    api::auth::bootstrap_user().await?;
+
+    let refresh_config =
+        service::network_refresh::NetworkRefreshConfig::from_env()?;
+    let refresher =
+        service::network_refresh::NETWORK_REFRESHER.get_or_init(|| {
+            service::network_refresh::NetworkRefresher::new(refresh_config)
+        });
+    service::health::register_task("network_refresh", refresher.spawn());
+
+    let backup_config = service::backup::BackupConfig::from_env()?;
+    let backups = service::backup::BACKUP_SERVICE
+        .get_or_init(|| service::backup::BackupService::new(backup_config));
+    service::health::register_task("backup", backups.spawn());
+
+    let audit_log_floor_days = database::with_database(
+        database::Database::get_audit_log_retention_floor_days,
+    )?
+    .await?;
+    let retention_config =
+        service::retention::RetentionConfig::from_env(audit_log_floor_days)?;
+    let retention = service::retention::RETENTION_SERVICE.get_or_init(|| {
+        service::retention::RetentionService::new(retention_config)
+    });
+    service::health::register_task("retention", retention.spawn());
+
+    let dns = match dns::DnsConfig::from_env()? {
+        Some(dns_config) => Some(dns::spawn(dns_config).await?),
+        None => None,
+    };
+    service::health::register_task("dns", dns);
+
+    service::health::register_task("metrics", Some(metrics::spawn().await?));
 
     let bind_addr = std::env::var(ENV_NAME_BIND_ADDR)
         .unwrap_or_else(|_| DEFAULT_BIND_ADDR.to_string());
 
+    // Session cookies are only accepted from explicitly allowed origins;
+    // other origins can still use bearer tokens.
+    let allowed_origins: Vec<String> =
+        std::env::var(ENV_NAME_CORS_ALLOWED_ORIGINS)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .map(ToString::to_string)
+            .collect();
     let cors = Cors::new()
         .allow_methods(vec!["GET", "POST"])
-        .allow_headers(vec!["content-type"])
-        .allow_credentials(true);
+        .allow_headers(vec!["content-type", "authorization"])
+        .allow_credentials(!allowed_origins.is_empty())
+        .allow_origins(allowed_origins);
 
     let api_service = OpenApiService::new(
-        (Api, ActionApi, IdentApi, StatsApi),
+        (
+            Api,
+            ActionApi,
+            AuditApi,
+            AuthApi,
+            BackupApi,
+            EnrollmentApi,
+            ExportApi,
+            HealthApi,
+            IdentApi,
+            JobsApi,
+            RegistryApi,
+            RetentionApi,
+            SessionsApi,
+            StatsApi,
+        ),
         "RMCS Actions Service",
         "1.0",
     )
     .server("/api");
     let ui = api_service.swagger_ui();
     let app = Route::new()
-        .nest("/api", api_service)
-        .nest("/swagger", ui)
-        .at(
-            "/ws/:robot_uuid",
-            get(service::websocket_service
-                .data(tokio::sync::broadcast::channel::<String>(32).0)),
+        .nest(
+            "/api",
+            api_service
+                .into_endpoint()
+                .catch_all_error(api::rejected_request)
+                .with(AuditLog),
         )
+        .nest("/swagger", ui)
+        .at("/ws/:robot_uuid", get(service::websocket_service))
+        .with(metrics::middleware::HttpMetrics)
+        .with(CookieJarManager::new())
         .with(cors);
 
     log::info!("Starting server on {bind_addr}");
@@ -0,0 +1,258 @@
+//! Prometheus metrics of the service, served at `/metrics` on their own
+//! address, apart from the API.
+//!
+//! Counters and histograms are updated where the measured work happens;
+//! gauges describing the connected fleet are read from [`CONNECTIONS`]
+//! when metrics are scraped.
+
+use std::sync::LazyLock;
+
+use poem::{
+    IntoResponse, Request, Response, Route, get, handler,
+    http::{StatusCode, header},
+    listener::{Listener, TcpListener},
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec,
+    Opts, Registry, TextEncoder, exponential_buckets,
+};
+
+use tokio::task::JoinHandle;
+
+use crate::{
+    constant::env::{
+        DEFAULT_METRICS_BIND_ADDR, ENV_NAME_METRICS_BIND_ADDR,
+        ENV_NAME_METRICS_TOKEN,
+    },
+    service::CONNECTIONS,
+    utils::credentials::hash_token,
+};
+
+pub mod database;
+pub mod middleware;
+
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec,
+    pub http_request_duration: HistogramVec,
+    pub instructions: IntCounterVec,
+    pub instruction_duration: HistogramVec,
+    pub websocket_messages: IntCounterVec,
+    pub websocket_bytes: IntCounterVec,
+    pub database_query_duration: HistogramVec,
+    connected_robots: IntGaugeVec,
+    instruction_sessions: IntGauge,
+}
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Direction of a websocket message, seen from the service.
+#[derive(Debug, Clone, Copy)]
+pub enum Direction {
+    Received,
+    Sent,
+}
+
+impl Direction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Direction::Received => "received",
+            Direction::Sent => "sent",
+        }
+    }
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("rmcs".to_string()), None)
+            .expect("metric prefix is valid");
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled"),
+            &["method", "path", "status"],
+        )
+        .expect("metric is valid");
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time taken to handle HTTP requests",
+            ),
+            &["method", "path"],
+        )
+        .expect("metric is valid");
+        let instructions = IntCounterVec::new(
+            Opts::new(
+                "instructions_total",
+                "Instructions sent to robots, by outcome",
+            ),
+            &["instruction", "outcome"],
+        )
+        .expect("metric is valid");
+        let instruction_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "instruction_duration_seconds",
+                "Time from sending an instruction to its outcome",
+            )
+            .buckets(vec![
+                0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+                300.0,
+            ]),
+            &["instruction"],
+        )
+        .expect("metric is valid");
+        let websocket_messages = IntCounterVec::new(
+            Opts::new(
+                "websocket_messages_total",
+                "Text messages exchanged with robots",
+            ),
+            &["direction"],
+        )
+        .expect("metric is valid");
+        let websocket_bytes = IntCounterVec::new(
+            Opts::new(
+                "websocket_bytes_total",
+                "Bytes of text messages exchanged with robots",
+            ),
+            &["direction"],
+        )
+        .expect("metric is valid");
+        let database_query_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "database_query_duration_seconds",
+                "Time taken by database calls",
+            )
+            .buckets(
+                exponential_buckets(0.0001, 4.0, 8).expect("buckets are valid"),
+            ),
+            &["file"],
+        )
+        .expect("metric is valid");
+        let connected_robots = IntGaugeVec::new(
+            Opts::new("connected_robots", "Robots connected over websocket"),
+            &["approved"],
+        )
+        .expect("metric is valid");
+        let instruction_sessions = IntGauge::new(
+            "instruction_sessions",
+            "Instruction and event sessions open on robot connections",
+        )
+        .expect("metric is valid");
+
+        for collector in [
+            Box::new(http_requests.clone())
+                as Box<dyn prometheus::core::Collector>,
+            Box::new(http_request_duration.clone()),
+            Box::new(instructions.clone()),
+            Box::new(instruction_duration.clone()),
+            Box::new(websocket_messages.clone()),
+            Box::new(websocket_bytes.clone()),
+            Box::new(database_query_duration.clone()),
+            Box::new(connected_robots.clone()),
+            Box::new(instruction_sessions.clone()),
+        ] {
+            registry
+                .register(collector)
+                .expect("metric names are unique");
+        }
+
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            instructions,
+            instruction_duration,
+            websocket_messages,
+            websocket_bytes,
+            database_query_duration,
+            connected_robots,
+            instruction_sessions,
+        }
+    }
+
+    pub fn record_websocket_message(&self, direction: Direction, bytes: usize) {
+        let direction = direction.as_str();
+        self.websocket_messages
+            .with_label_values(&[direction])
+            .inc();
+        self.websocket_bytes
+            .with_label_values(&[direction])
+            .inc_by(u64::try_from(bytes).unwrap_or(u64::MAX));
+    }
+
+    /// Renders every metric in the Prometheus text format.
+    fn render(&self) -> anyhow::Result<String> {
+        let (mut approved, mut unapproved, mut sessions) = (0, 0, 0);
+        for entry in CONNECTIONS.iter() {
+            if entry.value().is_approved() {
+                approved += 1;
+            } else {
+                unapproved += 1;
+            }
+            sessions += entry.value().sessions.len();
+        }
+        self.connected_robots
+            .with_label_values(&["true"])
+            .set(approved);
+        self.connected_robots
+            .with_label_values(&["false"])
+            .set(unapproved);
+        self.instruction_sessions
+            .set(i64::try_from(sessions).unwrap_or(i64::MAX));
+
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+/// Whether `req` may scrape metrics: either no token is configured or the
+/// request carries it as a bearer token.
+fn metrics_token_matches(req: &Request) -> bool {
+    let Ok(expected) = std::env::var(ENV_NAME_METRICS_TOKEN) else {
+        return true;
+    };
+    let presented = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    // Comparing digests keeps the comparison time independent of how much
+    // of the token is right.
+    expected.is_empty()
+        || presented
+            .is_some_and(|token| hash_token(token) == hash_token(&expected))
+}
+
+#[handler]
+fn metrics_service(req: &Request) -> Response {
+    if !metrics_token_matches(req) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    match METRICS.render() {
+        Ok(body) => body
+            .with_content_type("text/plain; version=0.0.4")
+            .into_response(),
+        Err(err) => {
+            log::error!("Failed to render metrics: {err:?}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Binds `METRICS_BIND_ADDR` and serves `/metrics` on it in the background.
+/// The address defaults to the loopback interface, so that metrics are not
+/// reachable from other hosts unless configured.
+pub async fn spawn() -> anyhow::Result<JoinHandle<()>> {
+    let bind_addr = std::env::var(ENV_NAME_METRICS_BIND_ADDR)
+        .unwrap_or_else(|_| DEFAULT_METRICS_BIND_ADDR.to_string());
+    let acceptor = TcpListener::bind(&bind_addr).into_acceptor().await?;
+    log::info!("Serving metrics on {bind_addr}");
+    let app = Route::new().at("/metrics", get(metrics_service));
+
+    Ok(tokio::spawn(async move {
+        if let Err(err) =
+            poem::Server::new_with_acceptor(acceptor).run(app).await
+        {
+            log::error!("Metrics server stopped: {err}");
+        }
+    }))
+}
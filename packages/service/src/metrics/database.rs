@@ -0,0 +1,27 @@
+//! Timing of database calls.
+//!
+//! [`with_database`](crate::database::with_database) times the future of
+//! every call it makes and reports it under the source file that made the
+//! call, so that slow handlers and tasks can be told apart.
+
+use std::{panic::Location, time::Instant};
+
+use crate::metrics::METRICS;
+
+/// Names the source file of `location` relative to `src`, e.g.
+/// `api/stats.rs`.
+pub fn caller(location: &'static Location<'static>) -> &'static str {
+    let file = location.file();
+    file.strip_prefix("src/").unwrap_or(file)
+}
+
+/// Awaits `future` and records how long it took under `caller`.
+pub async fn timed<F: Future>(caller: &'static str, future: F) -> F::Output {
+    let started = Instant::now();
+    let output = future.await;
+    METRICS
+        .database_query_duration
+        .with_label_values(&[caller])
+        .observe(started.elapsed().as_secs_f64());
+    output
+}
@@ -0,0 +1,52 @@
+use std::time::Instant;
+
+use poem::{
+    Endpoint, IntoResponse, Middleware, PathPattern, Request, Response, Result,
+};
+
+use crate::metrics::METRICS;
+
+/// Label of requests that matched no route, so that scanning unknown paths
+/// does not create a label per path.
+const UNMATCHED_PATH: &str = "unmatched";
+
+/// Counts and times every HTTP request by method, route and status.
+pub struct HttpMetrics;
+
+impl<E: Endpoint> Middleware<E> for HttpMetrics {
+    type Output = HttpMetricsEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        HttpMetricsEndpoint(ep)
+    }
+}
+
+pub struct HttpMetricsEndpoint<E>(E);
+
+impl<E: Endpoint> Endpoint for HttpMetricsEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let method = req.method().to_string();
+        let started = Instant::now();
+        let result = self.0.call(req).await.map(IntoResponse::into_response);
+        let duration = started.elapsed();
+
+        // The router reports the route pattern, such as `/ws/:robot_uuid`,
+        // on the response.
+        let (status, pattern) = match &result {
+            Ok(resp) => (resp.status(), resp.data::<PathPattern>()),
+            Err(err) => (err.status(), err.data::<PathPattern>()),
+        };
+        let path = pattern.map_or(UNMATCHED_PATH, |pattern| &*pattern.0);
+        METRICS
+            .http_requests
+            .with_label_values(&[method.as_str(), path, status.as_str()])
+            .inc();
+        METRICS
+            .http_request_duration
+            .with_label_values(&[method.as_str(), path])
+            .observe(duration.as_secs_f64());
+        result
+    }
+}
@@ -1,68 +1,294 @@
 use std::sync::{Arc, LazyLock};
 
 use dashmap::DashMap;
-use futures_util::{SinkExt, StreamExt};
+use futures_util::{Sink, SinkExt, StreamExt};
 use poem::{
-    IntoResponse, handler,
+    IntoResponse, Request, handler,
+    http::{StatusCode, header},
     web::{
-        Path,
+        Path, Query, RealIp,
         websocket::{Message, WebSocket},
     },
 };
-use tokio::select;
+use serde::Deserialize;
+use tokio::{
+    select,
+    sync::{mpsc, oneshot},
+};
 
-use crate::service::connection::Connection;
+use crate::{
+    database::{
+        connection_session::DisconnectReason, robot::EnrollmentStatus,
+        with_database,
+    },
+    metrics::{Direction, METRICS},
+    service::connection::Connection,
+    utils::credentials::hash_token,
+};
 
 pub mod action;
+pub mod audit;
+pub mod backup;
 pub mod connection;
 pub mod events;
+pub mod fleet_events;
+pub mod health;
 pub mod instructions;
+pub mod jobs;
 pub mod message;
+pub mod network_refresh;
+pub mod retention;
 
 pub static CONNECTIONS: LazyLock<Arc<DashMap<String, Arc<Connection>>>> =
     LazyLock::new(|| Arc::new(DashMap::new()));
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConnectParams {
+    /// Version of the bot binary, sent by bots that report it.
+    version: Option<String>,
+}
+
+/// How often the last-seen time of a connected robot is persisted. It
+/// bounds how much uptime is lost when the service stops while robots are
+/// connected.
+const PRESENCE_PERSIST_INTERVAL: chrono::TimeDelta =
+    chrono::TimeDelta::seconds(60);
+
+/// Persists the time a robot was last seen, and its version if known, so
+/// that it remains available once the robot goes offline.
+fn record_presence(robot_id: String, version: Option<String>) {
+    tokio::spawn(async move {
+        let recorded = async {
+            with_database(|db| db.touch_robot(&robot_id, version.as_deref()))?
+                .await?;
+            anyhow::Ok(())
+        };
+        if let Err(err) = recorded.await {
+            log::warn!(
+                "Failed to record presence of robot {robot_id}: {err:?}"
+            );
+        }
+    });
+}
+
+/// Records the start of a robot connection. Returns the ID of the
+/// connection session, or `None` if it could not be recorded.
+async fn record_connect(
+    robot_id: &str,
+    remote_addr: Option<String>,
+) -> Option<i64> {
+    let opened = async {
+        let id = with_database(|db| {
+            db.open_connection_session(robot_id, remote_addr.as_deref())
+        })?
+        .await?;
+        anyhow::Ok(id)
+    };
+    match opened.await {
+        Ok(id) => Some(id),
+        Err(err) => {
+            log::warn!(
+                "Failed to record connection of robot {robot_id}: {err:?}"
+            );
+            None
+        }
+    }
+}
+
+/// Records the end of a robot connection.
+fn record_disconnect(
+    robot_id: String,
+    session_id: Option<i64>,
+    reason: DisconnectReason,
+) {
+    log::info!("Robot {robot_id} disconnected: {reason:?}");
+    record_presence(robot_id.clone(), None);
+    let Some(session_id) = session_id else {
+        return;
+    };
+    tokio::spawn(async move {
+        let closed = async {
+            with_database(|db| {
+                db.close_connection_session(session_id, reason)
+            })?
+            .await?;
+            anyhow::Ok(())
+        };
+        if let Err(err) = closed.await {
+            log::warn!(
+                "Failed to record disconnection of robot {robot_id}: {err:?}"
+            );
+        }
+    });
+}
+
+/// Looks up the UUID a robot is registered under, which differs from the
+/// one it connects with if it was merged into another robot, whether it
+/// may connect and receive instructions, and the digest of the secret it
+/// has to present. Robots that are not registered may connect but stay
+/// unapproved.
+async fn lookup_robot(
+    robot_id: String,
+) -> (String, Option<EnrollmentStatus>, Option<String>) {
+    let lookup = async {
+        let uuid =
+            with_database(|db| db.resolve_robot_uuid(&robot_id))?.await?;
+        let enrollment =
+            with_database(|db| db.get_robot_enrollment(&uuid))?.await?;
+        // A merged robot's bot keeps the secret it held before the merge.
+        let secret =
+            match with_database(|db| db.get_alias_secret(&robot_id))?.await? {
+                Some(secret) => Some(secret),
+                None => with_database(|db| db.get_robot_secret(&uuid))?.await?,
+            };
+        anyhow::Ok((uuid, enrollment, secret.map(|secret| secret.hash)))
+    };
+    match lookup.await {
+        Ok(found) => found,
+        Err(err) => {
+            log::warn!("Failed to look up robot {robot_id}: {err:?}");
+            (robot_id, None, None)
+        }
+    }
+}
+
+/// Whether the request carries the robot secret whose digest is
+/// `secret_hash` as a bearer token.
+fn presents_secret(req: &Request, secret_hash: Option<&str>) -> bool {
+    let presented = req
+        .header(header::AUTHORIZATION)
+        .and_then(|value| value.strip_prefix("Bearer "));
+    matches!(
+        (presented, secret_hash),
+        (Some(presented), Some(secret_hash))
+            if hash_token(presented) == secret_hash
+    )
+}
+
+/// Forwards outgoing messages to the robot until the connection ends, and
+/// returns why it ended.
+async fn write_messages<S>(
+    sink: &mut S,
+    ws_reader: &mut mpsc::Receiver<message::Message>,
+    shutdown: &mut oneshot::Receiver<DisconnectReason>,
+    connection: &Connection,
+) -> DisconnectReason
+where
+    S: Sink<Message> + Unpin,
+    S::Error: std::fmt::Display,
+{
+    loop {
+        select! {
+            Some(msg) = ws_reader.recv() => {
+                let text = serde_json::to_string(&msg).unwrap();
+                METRICS.record_websocket_message(Direction::Sent, text.len());
+                let msg = Message::Text(text);
+                log::debug!("Sending WebSocket message: {msg:?}");
+                if let Err(e) = sink.send(msg).await {
+                    log::error!("Failed to send websocket message: {e}");
+                    break DisconnectReason::SendFailed;
+                }
+            }
+            reason = &mut *shutdown => {
+                log::info!("Shutting down WebSocket writer");
+                break reason.unwrap_or(DisconnectReason::ConnectionLost);
+            }
+            () = connection.disconnect_requested() => {
+                log::info!(
+                    "Closing WebSocket connection of robot {}",
+                    connection.robot_id
+                );
+                let _ = sink.send(Message::Close(None)).await;
+                break DisconnectReason::ClosedByServer;
+            }
+        }
+    }
+}
+
 #[handler]
-pub fn websocket_service(
+pub async fn websocket_service(
     Path(robot_uuid): Path<String>,
+    Query(params): Query<ConnectParams>,
+    RealIp(remote_ip): RealIp,
+    req: &Request,
     ws: WebSocket,
-) -> impl IntoResponse {
+) -> poem::Result<impl IntoResponse> {
+    let (robot_uuid, enrollment, secret_hash) = lookup_robot(robot_uuid).await;
+    if enrollment.is_some_and(EnrollmentStatus::is_banned) {
+        log::info!("Refusing connection of {enrollment:?} robot {robot_uuid}");
+        return Err(poem::Error::from_status(StatusCode::FORBIDDEN));
+    }
+    // Approved robots have to prove who they are; others may connect but
+    // receive no instructions.
+    let approved = enrollment == Some(EnrollmentStatus::Approved);
+    if approved && !presents_secret(req, secret_hash.as_deref()) {
+        log::info!(
+            "Refusing connection of robot {robot_uuid} without its secret"
+        );
+        return Err(poem::Error::from_status(StatusCode::UNAUTHORIZED));
+    }
+
     // Sync robot id and register it
     log::info!("WebSocket connection established for robot: {robot_uuid}");
 
-    ws.on_upgrade(move |socket| async move {
+    Ok(ws.on_upgrade(move |socket| async move {
         let (mut sink, mut stream) = socket.split();
-        let (ws_writer, mut ws_reader) =
-            tokio::sync::mpsc::channel::<message::Message>(100);
+        let (ws_writer, mut ws_reader) = mpsc::channel::<message::Message>(100);
 
-        let connection = Arc::new(Connection::new(robot_uuid, ws_writer));
-        CONNECTIONS
-            .insert(connection.robot_id.clone(), connection.clone());
+        record_presence(robot_uuid.clone(), params.version.clone());
+        let session_id =
+            record_connect(&robot_uuid, remote_ip.map(|ip| ip.to_string()))
+                .await;
+        let connection = Arc::new(Connection::new(
+            robot_uuid,
+            ws_writer,
+            params.version,
+            approved,
+        ));
+        CONNECTIONS.insert(connection.robot_id.clone(), connection.clone());
+        if let Some(refresher) = network_refresh::NETWORK_REFRESHER.get() {
+            refresher.schedule(connection.clone());
+        }
 
         let (shutdown_listener, mut shutdown) =
-            tokio::sync::oneshot::channel::<()>();
+            oneshot::channel::<DisconnectReason>();
 
         let connection_c = connection.clone();
 
         tokio::spawn(async move {
-            while let Some(msg) = stream.next().await {
+            let mut presence_recorded_at = connection.connected_since;
+            let reason = loop {
+                let Some(msg) = stream.next().await else {
+                    break DisconnectReason::ConnectionLost;
+                };
                 match msg {
                     Ok(msg) => {
                         if let Message::Text(text) = msg {
+                            METRICS.record_websocket_message(
+                                Direction::Received,
+                                text.len(),
+                            );
                             log::info!("Received WebSocket message: {text}");
-                            if let Err(err) =
-                                connection.recv(&text).await
-                            {
+                            if let Err(err) = connection.recv(&text).await {
                                 log::error!(
                                     "Failed to process message: {err:?}"
                                 );
                             }
+                            let last_seen = connection.last_seen();
+                            if last_seen - presence_recorded_at
+                                >= PRESENCE_PERSIST_INTERVAL
+                            {
+                                presence_recorded_at = last_seen;
+                                record_presence(
+                                    connection.robot_id.clone(),
+                                    None,
+                                );
+                            }
                         } else if msg.is_ping() || msg.is_pong() {
                             log::debug!("Received WebSocket ping/pong");
                         } else if msg.is_close() {
                             log::info!("WebSocket connection closed");
-                            let _ = shutdown_listener.send(());
-                            break;
+                            break DisconnectReason::ClosedByRobot;
                         } else {
                             log::warn!("Unsupported WebSocket message type");
                         }
@@ -71,31 +297,27 @@ pub fn websocket_service(
                         log::error!("WebSocket error: {e:?}");
                     }
                 }
-            }
+            };
+            let _ = shutdown_listener.send(reason);
         });
 
         tokio::spawn(async move {
-            loop {
-                select! {
-                    Some(msg) = ws_reader.recv() => {
-                        let msg = Message::Text(serde_json::to_string(&msg).unwrap());
-                        log::debug!("Sending WebSocket message: {msg:?}");
-                        if let Err(e) = sink.send(msg).await {
-                            log::error!("Failed to send websocket message: {e}");
-                            break;
-                        }
-                    }
-                    _ = &mut shutdown => {
-                        log::info!("Shutting down WebSocket writer");
-                        CONNECTIONS.remove(connection_c.robot_id.as_str());
-                        break;
-                    }
-                    else => {
-                        log::info!("WebSocket writer channel closed");
-                        break;
-                    }
-                }
-            }
+            let reason = write_messages(
+                &mut sink,
+                &mut ws_reader,
+                &mut shutdown,
+                &connection_c,
+            )
+            .await;
+            // A reconnect may already have replaced this connection.
+            CONNECTIONS.remove_if(connection_c.robot_id.as_str(), |_, conn| {
+                Arc::ptr_eq(conn, &connection_c)
+            });
+            record_disconnect(
+                connection_c.robot_id.clone(),
+                session_id,
+                reason,
+            );
         });
-    })
+    }))
 }
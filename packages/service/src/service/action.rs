@@ -4,11 +4,18 @@ use tokio::select;
 use tokio::sync::{mpsc, oneshot};
 use tokio::task::JoinHandle;
 use uuid::Uuid;
+
+use crate::service::message::ProtocolError;
+
+/// Message the peer sent to a session: the content of a response, or the
+/// error it reported instead.
+pub type ActionInput = Result<serde_json::Value, ProtocolError>;
+
 pub struct Action {
     #[allow(unused)]
     pub session_id: Uuid,
     handle: JoinHandle<anyhow::Result<()>>,
-    sender: mpsc::Sender<serde_json::Value>,
+    sender: mpsc::Sender<ActionInput>,
 }
 
 impl Action {
@@ -21,7 +28,7 @@ impl Action {
     ) -> Self
     where
         F: FnOnce(
-                mpsc::Receiver<serde_json::Value>,
+                mpsc::Receiver<ActionInput>,
                 mpsc::Sender<serde_json::Value>,
                 oneshot::Receiver<()>,
             ) -> Fut
@@ -30,7 +37,7 @@ impl Action {
         Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
     {
         let (inbound_sender, inbound_receiver) =
-            mpsc::channel::<serde_json::Value>(32);
+            mpsc::channel::<ActionInput>(32);
         let fut = task_func(inbound_receiver, receiver, close_listener);
         let handle = tokio::spawn(async move {
             let result = fut.await;
@@ -45,13 +52,23 @@ impl Action {
     }
 
     pub async fn resume(&self, msg: serde_json::Value) -> anyhow::Result<()> {
-        self.sender.send(msg).await?;
+        self.sender.send(Ok(msg)).await?;
+        Ok(())
+    }
+
+    /// Passes an error the peer reported for the session to the action.
+    pub async fn fail(&self, error: ProtocolError) -> anyhow::Result<()> {
+        self.sender.send(Err(error)).await?;
         Ok(())
     }
 
     pub fn abort(&self) {
         self.handle.abort();
     }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
 }
 
 #[sealed::sealed]
@@ -191,7 +208,7 @@ where
                             inbound_receiver.recv().await
                         {
                             let input: Input =
-                                serde_json::from_value(json_input)?;
+                                serde_json::from_value(json_input?)?;
                             let result = f(session_id, input).await?;
                             let json_output = serde_json::to_value(result)?;
                             outbound_sender.send(json_output).await?;
@@ -275,6 +292,13 @@ where
                         while let Some(json_value) =
                             json_in_receiver.recv().await
                         {
+                            let json_value = match json_value {
+                                Ok(json_value) => json_value,
+                                Err(e) => {
+                                    log::warn!("Peer reported an error: {e}");
+                                    break;
+                                }
+                            };
                             match serde_json::from_value::<Input>(json_value) {
                                 Ok(typed_input) => {
                                     if typed_in_sender
@@ -347,9 +371,7 @@ where
     F: FnOnce(Uuid) -> OutputFut + Send + 'static,
     Input: 'static + Send + Sync + DeserializeOwned,
     OutputFut: Future<Output = anyhow::Result<Output>> + Send + 'static,
-    R: FnOnce(Uuid, oneshot::Receiver<serde_json::Value>) -> ResFut
-        + Send
-        + 'static,
+    R: FnOnce(Uuid, oneshot::Receiver<ActionInput>) -> ResFut + Send + 'static,
     ResFut: Future<Output = ()> + Send + 'static,
     Output: 'static + Send + Sync + Serialize + DeserializeOwned,
 {
@@ -397,14 +419,10 @@ where
                         select! {
                             _ = close_listener => Ok(()),
                             resp = inbound_receiver.recv() => {
-                                match resp {
-                                    Some(resp) => {
-                                        let json_value = serde_json::to_value(resp)?;
-                                        let _ = response_sender.send(json_value);
-                                        Ok(())
-                                    }
-                                    None => Ok(()),
+                                if let Some(resp) = resp {
+                                    let _ = response_sender.send(resp);
                                 }
+                                Ok(())
                             }
                         }
                     }
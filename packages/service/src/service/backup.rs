@@ -0,0 +1,174 @@
+//! Scheduled backups and on-demand snapshots of the service database.
+//!
+//! Copies are made with `VACUUM INTO`, which like the online backup API
+//! reads the database in a single transaction, so they are consistent
+//! without stopping the service or blocking its writes. Backups are written
+//! to the backup directory under timestamped names, and only the newest
+//! ones are kept.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use anyhow::Context;
+use chrono::Utc;
+use tokio::task::JoinHandle;
+
+use crate::{
+    constant::env::{
+        DEFAULT_BACKUP_INTERVAL_SECS, DEFAULT_BACKUP_KEEP, ENV_NAME_BACKUP_DIR,
+        ENV_NAME_BACKUP_INTERVAL_SECS, ENV_NAME_BACKUP_KEEP,
+        ENV_NAME_STORAGE_DIR,
+    },
+    database::with_database,
+    env::parse_env_or,
+};
+
+const BACKUP_PREFIX: &str = "rmcs-actions-";
+const BACKUP_SUFFIX: &str = ".db";
+
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    /// Time between two scheduled backups. Zero disables them; snapshots
+    /// can still be downloaded.
+    pub interval: Duration,
+    /// Number of scheduled backups kept; older ones are deleted.
+    pub keep: usize,
+    pub dir: PathBuf,
+}
+
+impl BackupConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let dir = match std::env::var(ENV_NAME_BACKUP_DIR) {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) => PathBuf::from(std::env::var(ENV_NAME_STORAGE_DIR)?)
+                .join("backups"),
+        };
+        Ok(Self {
+            interval: Duration::from_secs(parse_env_or(
+                ENV_NAME_BACKUP_INTERVAL_SECS,
+                DEFAULT_BACKUP_INTERVAL_SECS,
+            )?),
+            keep: parse_env_or(ENV_NAME_BACKUP_KEEP, DEFAULT_BACKUP_KEEP)?
+                .max(1),
+            dir,
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.interval.is_zero()
+    }
+}
+
+pub struct BackupService {
+    config: BackupConfig,
+}
+
+pub static BACKUP_SERVICE: OnceLock<BackupService> = OnceLock::new();
+
+/// Copies the database to `path` through a temporary file, so that an
+/// interrupted copy never leaves a truncated database under `path`.
+async fn copy_database(path: &Path) -> anyhow::Result<()> {
+    let partial = path.with_extension("partial");
+    // `VACUUM INTO` refuses to overwrite an existing file.
+    let _ = tokio::fs::remove_file(&partial).await;
+    let partial_str = partial.to_str().with_context(|| {
+        format!("Invalid backup path {}", partial.display())
+    })?;
+    if let Err(err) = with_database(|db| db.backup_into(partial_str))?.await {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(err.into());
+    }
+    tokio::fs::rename(&partial, path).await?;
+    Ok(())
+}
+
+impl BackupService {
+    pub fn new(config: BackupConfig) -> Self {
+        Self { config }
+    }
+
+    /// Spawns the loop writing a backup once per configured interval.
+    pub fn spawn(&'static self) -> Option<JoinHandle<()>> {
+        if !self.config.enabled() {
+            log::info!("Scheduled database backups are disabled");
+            return None;
+        }
+        log::info!(
+            "Backing up the database to {} every {} seconds",
+            self.config.dir.display(),
+            self.config.interval.as_secs()
+        );
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.config.interval);
+            // The first tick completes immediately; restarting the service
+            // should not rotate out older backups.
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match self.backup().await {
+                    Ok(path) => {
+                        log::info!(
+                            "Backed up the database to {}",
+                            path.display()
+                        );
+                    }
+                    Err(err) => {
+                        log::warn!("Failed to back up the database: {err:?}");
+                    }
+                }
+            }
+        }))
+    }
+
+    /// Writes a timestamped backup and deletes the ones beyond the
+    /// configured number to keep.
+    pub async fn backup(&self) -> anyhow::Result<PathBuf> {
+        tokio::fs::create_dir_all(&self.config.dir).await?;
+        let path = self.config.dir.join(format!(
+            "{BACKUP_PREFIX}{}{BACKUP_SUFFIX}",
+            Utc::now().format("%Y%m%dT%H%M%SZ")
+        ));
+        copy_database(&path).await?;
+        self.rotate().await?;
+        Ok(path)
+    }
+
+    async fn rotate(&self) -> anyhow::Result<()> {
+        let mut backups = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.config.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            if name.starts_with(BACKUP_PREFIX) && name.ends_with(BACKUP_SUFFIX)
+            {
+                backups.push(entry.path());
+            }
+        }
+        // Timestamps in the names sort chronologically.
+        backups.sort();
+        let excess = backups.len().saturating_sub(self.config.keep);
+        for path in &backups[..excess] {
+            tokio::fs::remove_file(path).await?;
+            log::debug!("Deleted old backup {}", path.display());
+        }
+        Ok(())
+    }
+
+    /// Returns a consistent copy of the whole database file.
+    pub async fn snapshot(&self) -> anyhow::Result<Vec<u8>> {
+        tokio::fs::create_dir_all(&self.config.dir).await?;
+        let path = self
+            .config
+            .dir
+            .join(format!(".snapshot-{}.db", uuid::Uuid::new_v4()));
+        copy_database(&path).await?;
+        let contents = tokio::fs::read(&path).await;
+        tokio::fs::remove_file(&path).await?;
+        Ok(contents?)
+    }
+}
@@ -1,56 +1,600 @@
-use std::sync::Arc;
+use std::{
+    fmt,
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicBool, AtomicI64, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
+use chrono::{DateTime, TimeDelta, Utc};
 use dashmap::DashMap;
+use poem_openapi::{Enum, Object};
 use serde::de::DeserializeOwned;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{Notify, mpsc, oneshot};
 use uuid::Uuid;
 
-use crate::service::{
-    action::Action,
-    events,
-    instructions::Instruction,
-    message::{Message, MessagePayload},
+use crate::{
+    database::audit::{Actor, AuditCategory, AuditOutcome, NewAuditEntry},
+    metrics::METRICS,
+    service::{
+        action::Action,
+        audit, events,
+        instructions::Instruction,
+        message::{ErrorCode, Message, MessagePayload, ProtocolError},
+    },
 };
 
+/// What a session was opened for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename_all = "snake_case")]
+pub enum SessionKind {
+    /// An instruction sent by the service.
+    Instruction,
+    /// An event reported by the robot.
+    Event,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename_all = "snake_case")]
+pub enum SessionState {
+    /// The instruction was sent and the robot has not answered yet.
+    AwaitingResponse,
+    /// The event session is open and exchanging messages.
+    Streaming,
+    /// The session's action has ended and the session is being removed.
+    Closing,
+}
+
+/// An open session of a connection.
+pub struct Session {
+    action: Action,
+    close_sender: oneshot::Sender<()>,
+    pub kind: SessionKind,
+    /// Name of the instruction or event.
+    pub name: &'static str,
+    pub started_at: DateTime<Utc>,
+    /// Resolves the caller waiting for the instruction's response as
+    /// cancelled.
+    canceller: Option<oneshot::Sender<()>>,
+}
+
+impl Session {
+    pub fn state(&self) -> SessionState {
+        if self.action.is_finished() {
+            SessionState::Closing
+        } else if self.kind == SessionKind::Instruction {
+            SessionState::AwaitingResponse
+        } else {
+            SessionState::Streaming
+        }
+    }
+
+    /// Stops the action and notifies its close listener.
+    fn end(self) {
+        let _ = self.close_sender.send(());
+        self.action.abort();
+    }
+}
+
+/// How long an operation lock is held at most. Locks are released as soon
+/// as their instruction ends; this only bounds a lock whose instruction
+/// never does.
+const OPERATION_LOCK_TTL: TimeDelta = TimeDelta::minutes(10);
+
+/// Exclusive lock a mutating instruction holds on its robot while it runs.
+#[derive(Debug, Clone, Object)]
+pub struct OperationLock {
+    /// Name of the user or token that sent the instruction.
+    pub holder: String,
+    pub instruction: String,
+    pub acquired_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    #[oai(skip)]
+    id: Uuid,
+}
+
+impl fmt::Display for OperationLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} holds the lock for {} until {}",
+            self.holder,
+            self.instruction,
+            self.expires_at.to_rfc3339()
+        )
+    }
+}
+
+/// Releases an operation lock when dropped, unless it expired and was
+/// taken over in the meantime.
+struct LockGuard<'a> {
+    connection: &'a Connection,
+    id: Uuid,
+}
+
+impl Drop for LockGuard<'_> {
+    fn drop(&mut self) {
+        let mut lock = self
+            .connection
+            .operation_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if lock.as_ref().is_some_and(|lock| lock.id == self.id) {
+            *lock = None;
+        }
+    }
+}
+
 pub struct Connection {
-    pub sessions: Arc<DashMap<Uuid, (Action, oneshot::Sender<()>)>>,
+    pub sessions: Arc<DashMap<Uuid, Session>>,
     pub robot_id: String,
     pub writer: mpsc::Sender<Message>,
+    /// Bot version reported when connecting, if any.
+    pub version: Option<String>,
+    pub connected_since: DateTime<Utc>,
+    /// Time of the last message received, in milliseconds since the epoch.
+    last_seen: AtomicI64,
+    /// Whether the robot's enrollment is approved, which is required to
+    /// send it instructions.
+    approved: AtomicBool,
+    disconnect: Notify,
+    operation_lock: Mutex<Option<OperationLock>>,
+}
+
+/// Why an instruction did not produce a response.
+#[derive(Debug)]
+pub enum InstructionError {
+    /// The robot's enrollment is not approved.
+    NotApproved,
+    /// The session ended without an answer, e.g. because the robot
+    /// disconnected.
+    NoResponse,
+    /// The robot answered with an error payload.
+    Robot(ProtocolError),
+    /// The response does not have the expected shape.
+    InvalidResponse(serde_json::Error),
+    /// The session was cancelled before the robot answered.
+    Cancelled,
+    /// Another mutating instruction holds the robot's operation lock.
+    Locked(OperationLock),
+}
+
+impl fmt::Display for InstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstructionError::NotApproved => {
+                f.write_str("robot is not approved")
+            }
+            InstructionError::NoResponse => {
+                f.write_str("session closed without a response")
+            }
+            InstructionError::Robot(err) => {
+                write!(f, "robot reported an error: {err}")
+            }
+            InstructionError::InvalidResponse(err) => {
+                write!(f, "invalid response: {err}")
+            }
+            InstructionError::Cancelled => f.write_str("instruction cancelled"),
+            InstructionError::Locked(lock) => {
+                write!(f, "robot is locked: {lock}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InstructionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InstructionError::Robot(err) => Some(err),
+            InstructionError::InvalidResponse(err) => Some(err),
+            InstructionError::NotApproved
+            | InstructionError::NoResponse
+            | InstructionError::Cancelled
+            | InstructionError::Locked(_) => None,
+        }
+    }
+}
+
+/// Session ID of a message that failed to parse, if it has a readable one.
+fn session_id_of(msg: &str) -> Option<Uuid> {
+    serde_json::from_str::<serde_json::Value>(msg)
+        .ok()?
+        .get("session_id")?
+        .as_str()?
+        .parse()
+        .ok()
+}
+
+/// Closes an instruction session whose caller stopped waiting for the
+/// response, e.g. because it timed out or its job was cancelled.
+struct PendingSession<'a> {
+    connection: &'a Connection,
+    session_id: Uuid,
+}
+
+impl Drop for PendingSession<'_> {
+    fn drop(&mut self) {
+        self.connection.close_session(self.session_id);
+    }
+}
+
+/// Audit entry and metrics of an instruction, recorded when dropped. An
+/// instruction whose caller stops waiting is recorded as abandoned.
+struct InstructionAudit {
+    entry: Option<NewAuditEntry>,
+    started: Instant,
+    /// Whether the entry goes to the audit log; metrics are always kept.
+    audited: bool,
+}
+
+impl InstructionAudit {
+    fn finish(mut self, outcome: AuditOutcome, message: Option<String>) {
+        if let Some(entry) = &mut self.entry {
+            entry.outcome = outcome;
+            entry.message = message;
+        }
+    }
+}
+
+/// Outcome and message to audit for an instruction's response.
+/// Instructions report their own result in a `status` field, and explain an
+/// `error` status in `message`.
+fn reported_outcome(
+    response: &serde_json::Value,
+) -> (AuditOutcome, Option<String>) {
+    let field = |name| {
+        response
+            .get(name)
+            .and_then(serde_json::Value::as_str)
+            .map(ToString::to_string)
+    };
+    let status = field("status");
+    if status.as_deref() == Some("error") {
+        (AuditOutcome::Failure, field("message").or(status))
+    } else {
+        (AuditOutcome::Success, status)
+    }
+}
+
+impl Drop for InstructionAudit {
+    fn drop(&mut self) {
+        if let Some(mut entry) = self.entry.take() {
+            entry.duration = self.started.elapsed();
+            METRICS
+                .instructions
+                .with_label_values(&[
+                    entry.action.as_str(),
+                    entry.outcome.as_str(),
+                ])
+                .inc();
+            METRICS
+                .instruction_duration
+                .with_label_values(&[entry.action.as_str()])
+                .observe(entry.duration.as_secs_f64());
+            if self.audited {
+                audit::record(entry);
+            }
+        }
+    }
 }
 
 impl Connection {
-    pub fn new(robot_id: String, writer: mpsc::Sender<Message>) -> Self {
+    pub fn new(
+        robot_id: String,
+        writer: mpsc::Sender<Message>,
+        version: Option<String>,
+        approved: bool,
+    ) -> Self {
+        let now = Utc::now();
         Connection {
             sessions: Arc::new(DashMap::new()),
             robot_id,
             writer,
+            version,
+            connected_since: now,
+            last_seen: AtomicI64::new(now.timestamp_millis()),
+            approved: AtomicBool::new(approved),
+            disconnect: Notify::new(),
+            operation_lock: Mutex::new(None),
+        }
+    }
+
+    pub fn is_approved(&self) -> bool {
+        self.approved.load(Ordering::Relaxed)
+    }
+
+    pub fn set_approved(&self, approved: bool) {
+        self.approved.store(approved, Ordering::Relaxed);
+    }
+
+    /// Asks the websocket writer to close the connection.
+    pub fn disconnect(&self) {
+        self.disconnect.notify_one();
+    }
+
+    /// Completes once [`Connection::disconnect`] has been called.
+    pub async fn disconnect_requested(&self) {
+        self.disconnect.notified().await;
+    }
+
+    /// The robot's operation lock, if a mutating instruction holds it.
+    pub fn operation_lock(&self) -> Option<OperationLock> {
+        self.operation_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+            .filter(|lock| lock.expires_at > Utc::now())
+    }
+
+    /// Takes the operation lock for `instruction`, failing at once with the
+    /// current lock if it is held and has not expired.
+    fn try_lock(
+        &self,
+        instruction: &Instruction,
+        actor: &Actor,
+    ) -> Result<LockGuard<'_>, OperationLock> {
+        let mut lock = self
+            .operation_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let now = Utc::now();
+        if let Some(held) = lock.as_ref().filter(|lock| lock.expires_at > now) {
+            return Err(held.clone());
         }
+        let id = Uuid::new_v4();
+        *lock = Some(OperationLock {
+            holder: actor.name.clone(),
+            instruction: instruction.kind().to_string(),
+            acquired_at: now,
+            expires_at: now + OPERATION_LOCK_TTL,
+            id,
+        });
+        Ok(LockGuard {
+            connection: self,
+            id,
+        })
+    }
+
+    pub fn last_seen(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(self.last_seen.load(Ordering::Relaxed))
+            .unwrap_or(self.connected_since)
     }
 
     pub async fn recv(&self, msg: &str) -> anyhow::Result<()> {
-        let message: Message = serde_json::from_str(msg)?;
+        self.last_seen
+            .store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+        let message: Message = match serde_json::from_str(msg) {
+            Ok(message) => message,
+            Err(err) => {
+                self.send_error(
+                    session_id_of(msg).unwrap_or_default(),
+                    ProtocolError::new(
+                        ErrorCode::MalformedMessage,
+                        err.to_string(),
+                    ),
+                );
+                return Err(err.into());
+            }
+        };
         let session_id = message.session_id;
         let payload = message.payload;
         self.process_session(session_id, payload).await
     }
 
+    /// Sends an instruction and waits for the robot's response. The
+    /// instruction is recorded in the audit log as performed by `actor`,
+    /// unless it is a read made by the service itself.
+    /// Mutating instructions hold the robot's operation lock until they
+    /// end, and fail with [`InstructionError::Locked`] while another one
+    /// holds it.
     pub async fn send_instruction<T: DeserializeOwned>(
         &self,
         instruction: Instruction,
-    ) -> anyhow::Result<T> {
+        actor: &Actor,
+    ) -> Result<T, InstructionError> {
+        self.send_instruction_then(instruction, actor, async |response| {
+            response
+        })
+        .await
+    }
+
+    /// Like [`Connection::send_instruction`], but passes the response to
+    /// `then` before releasing the operation lock, so that the service can
+    /// record what the instruction changed before another one may start.
+    pub async fn send_instruction_then<T: DeserializeOwned, U>(
+        &self,
+        instruction: Instruction,
+        actor: &Actor,
+        then: impl AsyncFnOnce(T) -> U,
+    ) -> Result<U, InstructionError> {
+        if !self.is_approved() {
+            return Err(InstructionError::NotApproved);
+        }
+        let audit = InstructionAudit {
+            entry: Some(NewAuditEntry {
+                category: AuditCategory::Instruction,
+                actor: actor.clone(),
+                action: instruction.kind().to_string(),
+                robot_uuid: Some(self.robot_id.clone()),
+                parameters: Some(audit::redact(instruction.parameters())),
+                outcome: AuditOutcome::Abandoned,
+                message: None,
+                duration: Duration::ZERO,
+            }),
+            started: Instant::now(),
+            // Reads the service makes on its own, such as the periodic
+            // network refresh, would flood the audit log.
+            audited: instruction.is_mutating() || !actor.is_system(),
+        };
+        let _lock = if instruction.is_mutating() {
+            match self.try_lock(&instruction, actor) {
+                Ok(guard) => Some(guard),
+                Err(lock) => {
+                    let err = InstructionError::Locked(lock);
+                    audit.finish(AuditOutcome::Failure, Some(err.to_string()));
+                    return Err(err);
+                }
+            }
+        } else {
+            None
+        };
         let (resp_tx, resp_rx) = oneshot::channel();
+        let (cancel_tx, cancel_rx) = oneshot::channel();
         let sessions = self.sessions.clone();
         let session_id = Uuid::new_v4();
+        let name = instruction.kind();
         let session = instruction.into_session_compatible(session_id, resp_tx)(
             self.writer.clone(),
             move || {
                 sessions.remove(&session_id);
             },
         );
-        self.sessions
-            .insert(session_id, (session.action, session.close_listener));
-        let response = resp_rx.await?;
-        Ok(serde_json::from_value(response)?)
+        self.sessions.insert(
+            session_id,
+            Session {
+                action: session.action,
+                close_sender: session.close_listener,
+                kind: SessionKind::Instruction,
+                name,
+                started_at: Utc::now(),
+                canceller: Some(cancel_tx),
+            },
+        );
+        let pending = PendingSession {
+            connection: self,
+            session_id,
+        };
+        // A cancellation wins over a response that arrives at the same
+        // time, so that a cancelled instruction is always reported as such.
+        let response = tokio::select! {
+            biased;
+            // The canceller is also dropped when the session ends without
+            // being cancelled, which must not count as a cancellation.
+            Ok(()) = cancel_rx => {
+                audit.finish(
+                    AuditOutcome::Abandoned,
+                    Some(InstructionError::Cancelled.to_string()),
+                );
+                return Err(InstructionError::Cancelled);
+            }
+            response = resp_rx => response,
+        };
+        let response = match response {
+            Ok(Ok(response)) => response,
+            Ok(Err(err)) => {
+                audit.finish(AuditOutcome::Failure, Some(err.to_string()));
+                return Err(InstructionError::Robot(err));
+            }
+            Err(_) => {
+                let err = InstructionError::NoResponse;
+                audit.finish(AuditOutcome::Failure, Some(err.to_string()));
+                return Err(err);
+            }
+        };
+        // The robot answered, so the session completes on its own.
+        std::mem::forget(pending);
+        let (outcome, message) = reported_outcome(&response);
+        match serde_json::from_value(response) {
+            Ok(response) => {
+                audit.finish(outcome, message);
+                Ok(then(response).await)
+            }
+            Err(err) => {
+                let err = InstructionError::InvalidResponse(err);
+                audit.finish(AuditOutcome::Failure, Some(err.to_string()));
+                Err(err)
+            }
+        }
+    }
+
+    /// Closes a session from the server side: stops its action and tells
+    /// the robot to drop it as well.
+    pub fn close_session(&self, session_id: Uuid) {
+        if let Some((_, session)) = self.sessions.remove(&session_id) {
+            session.end();
+            self.send_close(session_id);
+        }
+    }
+
+    /// Closes a session like [`Connection::close_session`] and fails the
+    /// instruction waiting on it with [`InstructionError::Cancelled`].
+    /// Returns whether the session was open.
+    pub fn cancel_session(&self, session_id: Uuid) -> bool {
+        let Some((_, mut session)) = self.sessions.remove(&session_id) else {
+            return false;
+        };
+        log::info!(
+            "Cancelling {} session {session_id} of robot {}",
+            session.name,
+            self.robot_id
+        );
+        if let Some(canceller) = session.canceller.take() {
+            let _ = canceller.send(());
+        }
+        session.end();
+        self.send_close(session_id);
+        true
+    }
+
+    fn send_close(&self, session_id: Uuid) {
+        if let Err(err) = self
+            .writer
+            .try_send(Message::new_close_with_uuid(session_id))
+        {
+            log::warn!(
+                "Failed to send close for session {session_id} to robot {}: {err}",
+                self.robot_id
+            );
+        }
+    }
+
+    /// Tells the robot that a message of the session could not be handled.
+    fn send_error(&self, session_id: Uuid, error: ProtocolError) {
+        log::warn!(
+            "Replying to robot {} in session {session_id} with error {error}",
+            self.robot_id
+        );
+        if let Err(err) = self
+            .writer
+            .try_send(Message::new_error_with_uuid(session_id, error))
+        {
+            log::warn!(
+                "Failed to send error for session {session_id} to robot {}: {err}",
+                self.robot_id
+            );
+        }
+    }
+
+    fn open_event_session(&self, session_id: Uuid, content: serde_json::Value) {
+        let sessions = self.sessions.clone();
+        let started_at = Utc::now();
+        let event_session = match events::create_event_session(
+            &self.robot_id,
+            content,
+            session_id,
+            self.writer.clone(),
+            move || {
+                sessions.remove(&session_id);
+            },
+        ) {
+            Ok(event_session) => event_session,
+            Err(err) => {
+                self.send_error(session_id, err);
+                return;
+            }
+        };
+        self.sessions.insert(
+            session_id,
+            Session {
+                action: event_session.action,
+                close_sender: event_session.close_listener,
+                kind: SessionKind::Event,
+                name: event_session.event.as_str(),
+                started_at,
+                canceller: None,
+            },
+        );
     }
 
     async fn process_session(
@@ -63,38 +607,48 @@ impl Connection {
                 log::error!(
                     "Invalid message payload: Instructions shall be sent by the server."
                 );
+                self.send_error(
+                    session_id,
+                    ProtocolError::new(
+                        ErrorCode::UnexpectedMessage,
+                        "instructions are only sent by the service",
+                    ),
+                );
             }
             MessagePayload::Event { content } => {
                 log::info!("Processing event for session_id: {session_id}");
-                let sessions = self.sessions.clone();
-                let event_session = events::create_event_session(
-                    content,
-                    session_id,
-                    self.writer.clone(),
-                    move || {
-                        sessions.remove(&session_id);
-                    },
-                )?;
-                self.sessions.insert(
-                    session_id,
-                    (event_session.action, event_session.close_listener),
-                );
+                self.open_event_session(session_id, content);
             }
             MessagePayload::Response { content } => {
-                if let Some(action) = self.sessions.get_mut(&session_id) {
-                    action.0.resume(content).await?;
+                if let Some(session) = self.sessions.get_mut(&session_id) {
+                    session.action.resume(content).await?;
                 } else {
                     log::error!(
                         "Received unknown session response for {session_id}"
                     );
+                    self.send_error(
+                        session_id,
+                        ProtocolError::new(
+                            ErrorCode::UnknownSession,
+                            "no session is open with this ID",
+                        ),
+                    );
+                }
+            }
+            // Errors are never answered with errors, so that two peers
+            // cannot keep reporting each other's replies.
+            MessagePayload::Error { content } => {
+                log::warn!(
+                    "Robot {} reported an error in session {session_id}: {content}",
+                    self.robot_id
+                );
+                if let Some(session) = self.sessions.get_mut(&session_id) {
+                    session.action.fail(content).await?;
                 }
             }
             MessagePayload::Close => {
-                if let Some(handle) = self.sessions.remove(&session_id) {
-                    let (_, (action, close_sender)) = handle;
-                    // Abort the running task and notify close
-                    let _ = close_sender.send(());
-                    action.abort();
+                if let Some((_, session)) = self.sessions.remove(&session_id) {
+                    session.end();
                 } else {
                     log::warn!(
                         "Received close for unknown session {session_id}"
@@ -102,7 +656,44 @@ impl Connection {
                 }
                 return Ok(());
             }
-            MessagePayload::Unknown(_) => {}
+            MessagePayload::Unknown(payload) => {
+                let kind = payload.get("type").and_then(|kind| kind.as_str());
+                // An error whose content cannot be decoded still ends the
+                // session, and is not answered either.
+                if kind == Some("error") {
+                    log::warn!(
+                        "Robot {} reported an undecodable error in session {session_id}: {payload}",
+                        self.robot_id
+                    );
+                    if let Some(session) = self.sessions.get_mut(&session_id) {
+                        session
+                            .action
+                            .fail(ProtocolError::new(
+                                ErrorCode::InvalidContent,
+                                "the robot reported an error that could not be decoded",
+                            ))
+                            .await?;
+                    }
+                    return Ok(());
+                }
+                let error = match kind {
+                    Some(kind) if MessagePayload::TYPES.contains(&kind) => {
+                        ProtocolError::new(
+                            ErrorCode::InvalidContent,
+                            format!("invalid content for `{kind}` payload"),
+                        )
+                    }
+                    Some(kind) => ProtocolError::new(
+                        ErrorCode::UnknownMessageType,
+                        format!("unknown payload type `{kind}`"),
+                    ),
+                    None => ProtocolError::new(
+                        ErrorCode::MalformedMessage,
+                        "payload has no type",
+                    ),
+                };
+                self.send_error(session_id, error);
+            }
         }
 
         Ok(())
@@ -4,19 +4,31 @@ use uuid::Uuid;
 
 use crate::service::{
     action::{Action, InitAction, Streaming},
-    message::Message,
+    message::{ErrorCode, Message, ProtocolError},
 };
 
 pub mod heartbeat;
+pub mod network_changed;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Event {
     Heartbeat,
+    NetworkChanged,
     #[serde(other)]
     Unknown,
 }
 
+impl Event {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Event::Heartbeat => "heartbeat",
+            Event::NetworkChanged => "network_changed",
+            Event::Unknown => "unknown",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventMessage {
     pub event: Event,
@@ -24,29 +36,58 @@ pub struct EventMessage {
 }
 
 pub struct EventSession {
+    pub event: Event,
     pub action: Action,
     pub close_listener: oneshot::Sender<()>,
 }
 
 pub fn create_event_session(
+    robot_id: &str,
     event_raw: serde_json::Value,
     session_id: Uuid,
     output_receiver: mpsc::Sender<Message>,
     on_complete: impl FnOnce() + Send + 'static,
-) -> anyhow::Result<EventSession> {
+) -> Result<EventSession, ProtocolError> {
     log::info!("Creating event session for session_id: {session_id}");
-    let event_message: EventMessage = serde_json::from_value(event_raw)?;
+    let event_message: EventMessage = serde_json::from_value(event_raw)
+        .map_err(|err| {
+            ProtocolError::new(ErrorCode::InvalidContent, err.to_string())
+        })?;
     // Channel for streaming outputs from the action: sender goes into the action,
     // receiver is returned for external consumers to read.
     let (action, closer) =
         match event_message.event {
             Event::Heartbeat => Streaming(heartbeat::heartbeat_task)
                 .init_action(session_id, output_receiver, on_complete),
+            Event::NetworkChanged => {
+                let robot_id = robot_id.to_string();
+                let detail = event_message.detail;
+                Streaming(
+                    move |session_id: Uuid,
+                          receiver: mpsc::Receiver<serde_json::Value>,
+                          sender: mpsc::Sender<Message>,
+                          close_listener: oneshot::Receiver<()>| {
+                        network_changed::network_changed_task(
+                            robot_id.clone(),
+                            detail.clone(),
+                            session_id,
+                            receiver,
+                            sender,
+                            close_listener,
+                        )
+                    },
+                )
+                .init_action(session_id, output_receiver, on_complete)
+            }
             Event::Unknown => {
-                anyhow::bail!("Unknown event type");
+                return Err(ProtocolError::new(
+                    ErrorCode::UnknownEvent,
+                    "unknown event type",
+                ));
             }
         };
     Ok(EventSession {
+        event: event_message.event,
         action,
         close_listener: closer,
     })
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    database::{network::NetworkInfo, with_database},
+    service::{
+        fleet_events::{self, FleetEvent, NetworkChangedEvent},
+        message::{ErrorCode, Message, ProtocolError},
+    },
+};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct NetworkChangedResponse {}
+
+/// Handles a `network_changed` session.
+///
+/// The opening event carries the new network info as its detail; the bot
+/// may keep the session open and push further changes as responses. Invalid
+/// network info is answered with an `invalid_content` error, which ends the
+/// session.
+pub async fn network_changed_task(
+    robot_uuid: String,
+    initial_detail: serde_json::Value,
+    session_id: uuid::Uuid,
+    mut receiver: mpsc::Receiver<serde_json::Value>,
+    sender: mpsc::Sender<Message>,
+    mut close_listener: oneshot::Receiver<()>,
+) -> anyhow::Result<()> {
+    let mut detail = Some(initial_detail);
+    loop {
+        if let Some(detail) = detail.take() {
+            let info: NetworkInfo = match serde_json::from_value(detail) {
+                Ok(info) => info,
+                Err(err) => {
+                    log::warn!(
+                        "Robot {robot_uuid} sent invalid network info: {err}"
+                    );
+                    // The error ends the session on both sides.
+                    sender
+                        .send(Message::new_error_with_uuid(
+                            session_id,
+                            ProtocolError::new(
+                                ErrorCode::InvalidContent,
+                                format!("invalid network info: {err}"),
+                            ),
+                        ))
+                        .await?;
+                    break;
+                }
+            };
+            with_database(|db| db.write_network_info(&robot_uuid, &info))?
+                .await?;
+            log::info!("Network info of robot {robot_uuid} changed");
+            fleet_events::publish(FleetEvent::NetworkChanged(
+                NetworkChangedEvent {
+                    robot_uuid: robot_uuid.clone(),
+                    info,
+                    timestamp: chrono::Utc::now(),
+                },
+            ));
+            sender
+                .send(Message::new_response_with_uuid(
+                    session_id,
+                    NetworkChangedResponse {},
+                )?)
+                .await?;
+        }
+        tokio::select! {
+            Some(next) = receiver.recv() => {
+                detail = Some(next);
+            }
+            _ = &mut close_listener => {
+                log::info!("Network change task received close signal.");
+                break;
+            }
+            else => break,
+        }
+    }
+    Ok(())
+}
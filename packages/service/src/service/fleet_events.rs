@@ -0,0 +1,44 @@
+//! Fleet-wide notifications published by the service.
+//!
+//! Events are fanned out to every subscriber through a broadcast channel.
+//! Publishing never blocks and succeeds even when nobody is listening;
+//! subscribers that fall behind skip the events they missed.
+
+use std::sync::LazyLock;
+
+use chrono::{DateTime, Utc};
+use poem_openapi::{Object, Union};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::database::network::NetworkInfo;
+
+const FLEET_EVENT_CAPACITY: usize = 64;
+
+/// A robot reported a change of its network configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct NetworkChangedEvent {
+    pub robot_uuid: String,
+    pub info: NetworkInfo,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Union)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[oai(discriminator_name = "type")]
+pub enum FleetEvent {
+    #[oai(mapping = "network_changed")]
+    NetworkChanged(NetworkChangedEvent),
+}
+
+static FLEET_EVENTS: LazyLock<broadcast::Sender<FleetEvent>> =
+    LazyLock::new(|| broadcast::channel(FLEET_EVENT_CAPACITY).0);
+
+pub fn publish(event: FleetEvent) {
+    // An error only means there are no subscribers right now.
+    let _ = FLEET_EVENTS.send(event);
+}
+
+pub fn subscribe() -> broadcast::Receiver<FleetEvent> {
+    FLEET_EVENTS.subscribe()
+}
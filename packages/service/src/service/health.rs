@@ -0,0 +1,172 @@
+//! Checks behind the liveness and readiness endpoints.
+//!
+//! Liveness covers what restarting the service can fix: a database that
+//! stopped answering, e.g. because the connection pool is exhausted, and
+//! background tasks that ended. Readiness additionally requires the
+//! directories the service writes to to be writable.
+
+use std::{
+    path::Path,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use poem_openapi::{Enum, Object};
+use serde::{Deserialize, Serialize};
+use tokio::{task::JoinHandle, time::timeout};
+
+use crate::{
+    constant::env::{ENV_NAME_LOG_DIR, ENV_NAME_STORAGE_DIR},
+    database::{Database, with_database},
+};
+
+/// Time the database has to answer a health check.
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Background tasks by name. Tasks that are disabled by configuration are
+/// registered without a handle.
+static TASKS: LazyLock<DashMap<&'static str, Option<JoinHandle<()>>>> =
+    LazyLock::new(DashMap::new);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Failing,
+    /// Turned off by configuration; does not fail the report.
+    Disabled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct HealthCheck {
+    /// `database`, `storage_dir`, `log_dir` or `task:<name>`.
+    pub name: String,
+    pub status: CheckStatus,
+    /// Why the check is failing.
+    pub message: Option<String>,
+    pub duration_ms: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct HealthReport {
+    /// `failing` if any check is.
+    pub status: CheckStatus,
+    pub checks: Vec<HealthCheck>,
+}
+
+impl HealthReport {
+    fn new(checks: Vec<HealthCheck>) -> Self {
+        let status = if checks
+            .iter()
+            .any(|check| check.status == CheckStatus::Failing)
+        {
+            CheckStatus::Failing
+        } else {
+            CheckStatus::Ok
+        };
+        Self { status, checks }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.status == CheckStatus::Ok
+    }
+}
+
+/// Records a background task so that health checks notice when it ends.
+/// `None` marks a task that is disabled.
+pub fn register_task(name: &'static str, handle: Option<JoinHandle<()>>) {
+    TASKS.insert(name, handle);
+}
+
+fn timed_check(
+    name: String,
+    started: Instant,
+    result: anyhow::Result<()>,
+) -> HealthCheck {
+    let (status, message) = match result {
+        Ok(()) => (CheckStatus::Ok, None),
+        Err(err) => (CheckStatus::Failing, Some(format!("{err:#}"))),
+    };
+    HealthCheck {
+        name,
+        status,
+        message,
+        duration_ms: i64::try_from(started.elapsed().as_millis())
+            .unwrap_or(i64::MAX),
+    }
+}
+
+async fn check_database() -> HealthCheck {
+    let started = Instant::now();
+    let result = async {
+        timeout(DATABASE_TIMEOUT, with_database(Database::ping)?)
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "No answer within {} seconds",
+                    DATABASE_TIMEOUT.as_secs()
+                )
+            })??;
+        anyhow::Ok(())
+    }
+    .await;
+    timed_check("database".to_string(), started, result)
+}
+
+/// Checks that a file can be created in the directory named by the
+/// environment variable `var`.
+async fn check_dir_writable(name: &str, var: &str) -> HealthCheck {
+    let started = Instant::now();
+    let result = async {
+        let dir = std::env::var(var)?;
+        let probe =
+            Path::new(&dir).join(format!(".health-{}", uuid::Uuid::new_v4()));
+        tokio::fs::write(&probe, b"").await?;
+        tokio::fs::remove_file(&probe).await?;
+        anyhow::Ok(())
+    }
+    .await;
+    timed_check(name.to_string(), started, result)
+}
+
+fn check_tasks() -> Vec<HealthCheck> {
+    let mut checks: Vec<HealthCheck> = TASKS
+        .iter()
+        .map(|task| {
+            let (status, message) = match task.value() {
+                None => (CheckStatus::Disabled, None),
+                Some(handle) if handle.is_finished() => (
+                    CheckStatus::Failing,
+                    Some("The task has stopped".to_string()),
+                ),
+                Some(_) => (CheckStatus::Ok, None),
+            };
+            HealthCheck {
+                name: format!("task:{}", task.key()),
+                status,
+                message,
+                duration_ms: 0,
+            }
+        })
+        .collect();
+    checks.sort_by(|a, b| a.name.cmp(&b.name));
+    checks
+}
+
+pub async fn liveness() -> HealthReport {
+    let mut checks = vec![check_database().await];
+    checks.extend(check_tasks());
+    HealthReport::new(checks)
+}
+
+pub async fn readiness() -> HealthReport {
+    let mut checks = vec![
+        check_database().await,
+        check_dir_writable("storage_dir", ENV_NAME_STORAGE_DIR).await,
+        check_dir_writable("log_dir", ENV_NAME_LOG_DIR).await,
+    ];
+    checks.extend(check_tasks());
+    HealthReport::new(checks)
+}
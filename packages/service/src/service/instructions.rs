@@ -3,7 +3,7 @@ use tokio::sync::{mpsc, oneshot};
 use uuid::Uuid;
 
 use crate::service::{
-    action::{Action, InitAction},
+    action::{Action, ActionInput, InitAction},
     message::Message,
 };
 
@@ -41,10 +41,41 @@ pub enum Instruction {
 }
 
 impl Instruction {
+    /// Name of the instruction as sent to the robot.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Instruction::SyncRobotName { .. } => "sync_robot_name",
+            Instruction::FetchNetwork {} => "fetch_network",
+            Instruction::UpdateBinary { .. } => "update_binary",
+        }
+    }
+
+    /// Whether the instruction changes the robot, and so must not run
+    /// alongside another such instruction.
+    pub fn is_mutating(&self) -> bool {
+        match self {
+            Instruction::SyncRobotName { .. }
+            | Instruction::UpdateBinary { .. } => true,
+            Instruction::FetchNetwork {} => false,
+        }
+    }
+
+    pub fn parameters(&self) -> serde_json::Value {
+        match self {
+            Instruction::SyncRobotName { robot_name } => {
+                serde_json::json!({ "robot_name": robot_name })
+            }
+            Instruction::FetchNetwork {} => serde_json::json!({}),
+            Instruction::UpdateBinary { artifact_url } => {
+                serde_json::json!({ "artifact_url": artifact_url })
+            }
+        }
+    }
+
     pub fn into_session_compatible<F: FnOnce() + Send + 'static>(
         self,
         session_id: Uuid,
-        resp_tx: oneshot::Sender<serde_json::Value>,
+        resp_tx: oneshot::Sender<ActionInput>,
     ) -> impl FnOnce(mpsc::Sender<Message>, F) -> InstructionSession {
         move |output_receiver: mpsc::Sender<Message>, on_complete: F| match self
         {
@@ -3,12 +3,12 @@ use tokio::sync::oneshot;
 use uuid::Uuid;
 
 use crate::service::{
-    action::{InitAction, PingPong},
+    action::{ActionInput, InitAction, PingPong},
     message::Message,
 };
 
 pub fn fetch_network(
-    resp_tx: oneshot::Sender<serde_json::Value>,
+    resp_tx: oneshot::Sender<ActionInput>,
 ) -> impl InitAction<(), Message> {
     PingPong {
         constructor: move |session_id: Uuid| {
@@ -20,14 +20,13 @@ pub fn fetch_network(
             }
             .boxed()
         },
-        reader:
-            move |_: Uuid, resp_rx: oneshot::Receiver<serde_json::Value>| {
-                async move {
-                    if let Ok(response) = resp_rx.await {
-                        resp_tx.send(response).ok();
-                    }
+        reader: move |_: Uuid, resp_rx: oneshot::Receiver<ActionInput>| {
+            async move {
+                if let Ok(response) = resp_rx.await {
+                    resp_tx.send(response).ok();
                 }
-                .boxed()
-            },
+            }
+            .boxed()
+        },
     }
 }
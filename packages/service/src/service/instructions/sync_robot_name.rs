@@ -4,7 +4,7 @@ use tokio::sync::oneshot;
 use uuid::Uuid;
 
 use crate::service::{
-    action::OnceShot,
+    action::{ActionInput, OnceShot},
     instructions::{InstructionContent, SyncRobotNameMessage},
     message::Message,
 };
@@ -15,20 +15,23 @@ pub struct SyncRobotNameRequest {
 }
 
 pub fn sync_robot_name(
-    resp_tx: oneshot::Sender<serde_json::Value>,
+    resp_tx: oneshot::Sender<ActionInput>,
     new_robot_name: String,
 ) -> OnceShot<impl FnOnce(Uuid) -> BoxFuture<'static, anyhow::Result<Message>>>
 {
-    let _ = resp_tx.send(serde_json::json!({}));
     OnceShot(move |session_id: Uuid| {
         let robot_name = new_robot_name.clone();
         async move {
-            Ok(Message::new_instruction_with_uuid(
+            let message = Message::new_instruction_with_uuid(
                 session_id,
                 InstructionContent::SyncRobotName {
                     message: SyncRobotNameMessage { robot_name },
                 },
-            ))
+            );
+            // Robots do not answer renames, so the instruction is complete
+            // once it is on its way.
+            let _ = resp_tx.send(Ok(serde_json::json!({})));
+            Ok(message)
         }
         .boxed()
     })
@@ -3,13 +3,13 @@ use tokio::sync::oneshot;
 use uuid::Uuid;
 
 use crate::service::{
-    action::{InitAction, PingPong},
+    action::{ActionInput, InitAction, PingPong},
     instructions::{InstructionContent, UpdateBinaryMessage},
     message::Message,
 };
 
 pub fn update_binary(
-    resp_tx: oneshot::Sender<serde_json::Value>,
+    resp_tx: oneshot::Sender<ActionInput>,
     artifact_url: String,
 ) -> impl InitAction<(), Message> {
     PingPong {
@@ -24,14 +24,13 @@ pub fn update_binary(
             }
             .boxed()
         },
-        reader:
-            move |_: Uuid, resp_rx: oneshot::Receiver<serde_json::Value>| {
-                async move {
-                    if let Ok(response) = resp_rx.await {
-                        resp_tx.send(response).ok();
-                    }
+        reader: move |_: Uuid, resp_rx: oneshot::Receiver<ActionInput>| {
+            async move {
+                if let Ok(response) = resp_rx.await {
+                    resp_tx.send(response).ok();
                 }
-                .boxed()
-            },
+            }
+            .boxed()
+        },
     }
 }
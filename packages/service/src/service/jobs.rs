@@ -0,0 +1,55 @@
+//! Cancellation handles of the asynchronous jobs running in this process.
+//!
+//! Job state itself lives in the database; this registry only lets a
+//! cancellation request reach the task executing the job.
+
+use std::sync::LazyLock;
+
+use dashmap::DashMap;
+use tokio::sync::watch;
+
+static JOBS: LazyLock<DashMap<String, watch::Sender<bool>>> =
+    LazyLock::new(DashMap::new);
+
+/// Handle held by the task executing a job. The job stays cancellable for
+/// as long as the handle is alive.
+pub struct JobContext {
+    pub id: String,
+    cancelled: watch::Receiver<bool>,
+}
+
+impl JobContext {
+    pub fn register(id: String) -> Self {
+        let (sender, cancelled) = watch::channel(false);
+        JOBS.insert(id.clone(), sender);
+        Self { id, cancelled }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancelled.borrow()
+    }
+
+    /// Completes once the job is cancelled.
+    pub async fn cancelled(&self) {
+        let mut cancelled = self.cancelled.clone();
+        if cancelled.wait_for(|cancelled| *cancelled).await.is_err() {
+            // The job can no longer be cancelled.
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+impl Drop for JobContext {
+    fn drop(&mut self) {
+        JOBS.remove(&self.id);
+    }
+}
+
+/// Signals the task executing job `id` to stop. Returns `false` if the job
+/// is not running in this process.
+pub fn cancel(id: &str) -> bool {
+    JOBS.get(id).is_some_and(|sender| {
+        sender.send_replace(true);
+        true
+    })
+}
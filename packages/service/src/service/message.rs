@@ -1,3 +1,5 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use uuid::Uuid;
@@ -52,6 +54,22 @@ impl Message {
             payload: MessagePayload::Instruction { content },
         }
     }
+
+    pub fn new_error_with_uuid(session_id: Uuid, error: ProtocolError) -> Self {
+        Self {
+            session_id,
+            local_timestamp: chrono::Utc::now(),
+            payload: MessagePayload::Error { content: error },
+        }
+    }
+
+    pub fn new_close_with_uuid(session_id: Uuid) -> Self {
+        Self {
+            session_id,
+            local_timestamp: chrono::Utc::now(),
+            payload: MessagePayload::Close,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,7 +84,82 @@ pub enum MessagePayload {
     Response {
         content: serde_json::Value,
     },
+    /// The sender could not handle a message of the session, or failed to
+    /// carry out its instruction.
+    Error {
+        content: ProtocolError,
+    },
     Close,
     #[serde(untagged)]
     Unknown(serde_json::Value),
 }
+
+impl MessagePayload {
+    /// Payload types the protocol defines.
+    pub const TYPES: [&str; 5] =
+        ["instruction", "event", "response", "error", "close"];
+}
+
+/// Reason of an `error` payload. Both sides may send every code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The message is not JSON or lacks required fields.
+    MalformedMessage,
+    /// The payload `type` is not one the receiver knows.
+    UnknownMessageType,
+    /// The payload is valid but not one the receiver accepts, such as an
+    /// instruction sent by a robot.
+    UnexpectedMessage,
+    /// No session with the message's ID is open on the receiver.
+    UnknownSession,
+    UnknownInstruction,
+    UnknownEvent,
+    /// The content of the payload could not be decoded.
+    InvalidContent,
+    /// The instruction was understood but could not be carried out.
+    InstructionFailed,
+    /// A code added by a newer peer.
+    #[serde(other)]
+    Other,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::MalformedMessage => "malformed_message",
+            ErrorCode::UnknownMessageType => "unknown_message_type",
+            ErrorCode::UnexpectedMessage => "unexpected_message",
+            ErrorCode::UnknownSession => "unknown_session",
+            ErrorCode::UnknownInstruction => "unknown_instruction",
+            ErrorCode::UnknownEvent => "unknown_event",
+            ErrorCode::InvalidContent => "invalid_content",
+            ErrorCode::InstructionFailed => "instruction_failed",
+            ErrorCode::Other => "other",
+        }
+    }
+}
+
+/// Content of an `error` payload.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtocolError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ProtocolError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code.as_str(), self.message)
+    }
+}
+
+impl std::error::Error for ProtocolError {}
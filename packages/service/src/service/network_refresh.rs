@@ -0,0 +1,236 @@
+//! Background refresh of robot network info.
+//!
+//! Each robot is refreshed shortly after it connects and then once per
+//! configured interval for as long as it stays connected. Refreshes are
+//! delayed by a random jitter so that a fleet reconnecting at once does not
+//! answer in lockstep, and a semaphore caps how many run concurrently. A
+//! connection whose previous refresh is still queued or running is skipped,
+//! so slow rounds do not pile up. Refreshes are not recorded in the audit
+//! log.
+
+use std::{
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use dashmap::DashSet;
+use rand::Rng;
+use tokio::{sync::Semaphore, task::JoinHandle, time::timeout};
+
+use crate::{
+    constant::env::{
+        DEFAULT_NETWORK_REFRESH_CONCURRENCY,
+        DEFAULT_NETWORK_REFRESH_INTERVAL_SECS,
+        DEFAULT_NETWORK_REFRESH_JITTER_SECS,
+        DEFAULT_NETWORK_REFRESH_TIMEOUT_SECS,
+        ENV_NAME_NETWORK_REFRESH_CONCURRENCY,
+        ENV_NAME_NETWORK_REFRESH_INTERVAL_SECS,
+        ENV_NAME_NETWORK_REFRESH_JITTER_SECS,
+        ENV_NAME_NETWORK_REFRESH_TIMEOUT_SECS,
+    },
+    database::{audit::Actor, network::NetworkInfo, with_database},
+    env::parse_env_or,
+    service::{CONNECTIONS, connection::Connection, instructions::Instruction},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct NetworkRefreshConfig {
+    /// Time between two refreshes of the same robot. Zero disables the
+    /// scheduler entirely.
+    pub interval: Duration,
+    /// Upper bound of the random delay added before each refresh.
+    pub jitter: Duration,
+    /// Maximum number of refreshes in flight at the same time.
+    pub concurrency: usize,
+    /// Time allowed for a robot to answer a `fetch_network` instruction.
+    pub timeout: Duration,
+}
+
+impl NetworkRefreshConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        Ok(Self {
+            interval: Duration::from_secs(parse_env_or(
+                ENV_NAME_NETWORK_REFRESH_INTERVAL_SECS,
+                DEFAULT_NETWORK_REFRESH_INTERVAL_SECS,
+            )?),
+            jitter: Duration::from_secs(parse_env_or(
+                ENV_NAME_NETWORK_REFRESH_JITTER_SECS,
+                DEFAULT_NETWORK_REFRESH_JITTER_SECS,
+            )?),
+            concurrency: parse_env_or(
+                ENV_NAME_NETWORK_REFRESH_CONCURRENCY,
+                DEFAULT_NETWORK_REFRESH_CONCURRENCY,
+            )?
+            .max(1),
+            timeout: Duration::from_secs(parse_env_or(
+                ENV_NAME_NETWORK_REFRESH_TIMEOUT_SECS,
+                DEFAULT_NETWORK_REFRESH_TIMEOUT_SECS,
+            )?),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.interval.is_zero()
+    }
+}
+
+pub struct NetworkRefresher {
+    config: NetworkRefreshConfig,
+    permits: Arc<Semaphore>,
+    /// Connections with a refresh scheduled or running, by address. A
+    /// robot that reconnects gets a new connection, so a refresh stuck on
+    /// the old one does not hold back the new one's.
+    in_flight: DashSet<usize>,
+}
+
+/// Removes a connection from [`NetworkRefresher::in_flight`] when its
+/// refresh ends, however it ends.
+struct InFlight<'a> {
+    refresher: &'a NetworkRefresher,
+    connection: usize,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.refresher.in_flight.remove(&self.connection);
+    }
+}
+
+/// Identifies a connection for as long as it is alive. The refresh task
+/// holds the connection, so its address cannot be reused meanwhile.
+fn connection_key(connection: &Arc<Connection>) -> usize {
+    Arc::as_ptr(connection) as usize
+}
+
+pub static NETWORK_REFRESHER: OnceLock<NetworkRefresher> = OnceLock::new();
+
+/// Fetches network info from a connected robot on behalf of `actor` and
+/// stores it.
+pub async fn refresh_network_info(
+    connection: &Connection,
+    actor: &Actor,
+) -> anyhow::Result<NetworkInfo> {
+    let info: NetworkInfo = connection
+        .send_instruction(Instruction::FetchNetwork {}, actor)
+        .await?;
+    with_database(|db| db.write_network_info(&connection.robot_id, &info))?
+        .await?;
+    Ok(info)
+}
+
+impl NetworkRefresher {
+    pub fn new(config: NetworkRefreshConfig) -> Self {
+        Self {
+            config,
+            permits: Arc::new(Semaphore::new(config.concurrency)),
+            in_flight: DashSet::new(),
+        }
+    }
+
+    /// Schedules a single jittered refresh of `connection`, used right
+    /// after a robot connects. Does nothing if a refresh of the connection
+    /// is already scheduled or running.
+    pub fn schedule(&'static self, connection: Arc<Connection>) {
+        if !self.config.enabled() {
+            return;
+        }
+        let key = connection_key(&connection);
+        if !self.in_flight.insert(key) {
+            log::debug!(
+                "Skipping network refresh for robot {}: the previous one is still in flight",
+                connection.robot_id
+            );
+            return;
+        }
+        let in_flight = InFlight {
+            refresher: self,
+            connection: key,
+        };
+        tokio::spawn(async move {
+            let _in_flight = in_flight;
+            tokio::time::sleep(self.random_jitter()).await;
+            self.refresh(&connection).await;
+        });
+    }
+
+    /// Spawns the periodic refresh loop covering every connected robot.
+    pub fn spawn(&'static self) -> Option<JoinHandle<()>> {
+        if !self.config.enabled() {
+            log::info!("Periodic network refresh is disabled");
+            return None;
+        }
+        log::info!(
+            "Refreshing network info every {} seconds",
+            self.config.interval.as_secs()
+        );
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.config.interval);
+            // The first tick completes immediately; robots connected at
+            // that point are already covered by `schedule`.
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let connections: Vec<Arc<Connection>> = CONNECTIONS
+                    .iter()
+                    .map(|entry| entry.value().clone())
+                    .collect();
+                for connection in connections {
+                    self.schedule(connection);
+                }
+            }
+        }))
+    }
+
+    async fn refresh(&self, connection: &Connection) {
+        let Ok(_permit) = self.permits.acquire().await else {
+            return;
+        };
+        let robot_id = &connection.robot_id;
+        // A robot that reconnected is refreshed through its new connection.
+        if CONNECTIONS
+            .get(robot_id)
+            .is_none_or(|current| !std::ptr::eq(current.as_ref(), connection))
+        {
+            log::debug!(
+                "Skipping network refresh for offline robot {robot_id}"
+            );
+            return;
+        }
+        if !connection.is_approved() {
+            log::debug!(
+                "Skipping network refresh for unapproved robot {robot_id}"
+            );
+            return;
+        }
+        match timeout(
+            self.config.timeout,
+            refresh_network_info(connection, &Actor::system()),
+        )
+        .await
+        {
+            Ok(Ok(_)) => {
+                log::debug!("Refreshed network info for robot {robot_id}");
+            }
+            Ok(Err(err)) => {
+                log::warn!(
+                    "Failed to refresh network info for robot {robot_id}: {err:?}"
+                );
+            }
+            Err(_) => {
+                log::warn!(
+                    "Timed out refreshing network info for robot {robot_id} after {} seconds",
+                    self.config.timeout.as_secs()
+                );
+            }
+        }
+    }
+
+    fn random_jitter(&self) -> Duration {
+        if self.config.jitter.is_zero() {
+            return Duration::ZERO;
+        }
+        self.config
+            .jitter
+            .mul_f64(rand::rng().random_range(0.0..1.0))
+    }
+}
@@ -0,0 +1,309 @@
+//! Pruning of history tables.
+//!
+//! Connection sessions, jobs and audit entries accumulate for as long as
+//! the service runs. A background task deletes the rows that fall outside
+//! the retention policy of their table once per configured interval, then
+//! refreshes the query planner statistics and optionally vacuums the
+//! database to return the freed space. Runs can also be triggered on
+//! demand; only one run happens at a time.
+
+use std::{sync::OnceLock, time::Duration};
+
+use anyhow::bail;
+use chrono::{DateTime, Utc};
+use poem_openapi::{Enum, Object};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::Mutex, task::JoinHandle};
+
+use crate::{
+    constant::env::{
+        DEFAULT_RETENTION_AUDIT_LOG_MAX_AGE_DAYS,
+        DEFAULT_RETENTION_CONNECTION_SESSIONS_MAX_AGE_DAYS,
+        DEFAULT_RETENTION_CONNECTION_SESSIONS_MAX_PER_ROBOT,
+        DEFAULT_RETENTION_INTERVAL_SECS, DEFAULT_RETENTION_JOBS_MAX_AGE_DAYS,
+        DEFAULT_RETENTION_VACUUM, ENV_NAME_RETENTION_AUDIT_LOG_MAX_AGE_DAYS,
+        ENV_NAME_RETENTION_CONNECTION_SESSIONS_MAX_AGE_DAYS,
+        ENV_NAME_RETENTION_CONNECTION_SESSIONS_MAX_PER_ROBOT,
+        ENV_NAME_RETENTION_INTERVAL_SECS, ENV_NAME_RETENTION_JOBS_MAX_AGE_DAYS,
+        ENV_NAME_RETENTION_VACUUM,
+    },
+    database::with_database,
+    env::parse_env_or,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+pub enum HistoryTable {
+    ConnectionSessions,
+    Jobs,
+    AuditLog,
+}
+
+impl HistoryTable {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HistoryTable::ConnectionSessions => "connection_sessions",
+            HistoryTable::Jobs => "jobs",
+            HistoryTable::AuditLog => "audit_log",
+        }
+    }
+}
+
+/// Which rows of a history table are kept. Unset limits keep every row.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Object)]
+pub struct RetentionPolicy {
+    pub table: HistoryTable,
+    pub max_age_days: Option<u32>,
+    pub max_rows_per_robot: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetentionConfig {
+    /// Time between two scheduled runs. Zero disables them; runs can still
+    /// be triggered on demand.
+    pub interval: Duration,
+    /// Whether runs end by vacuuming the database.
+    pub vacuum: bool,
+    pub policies: Vec<RetentionPolicy>,
+}
+
+/// Reads a retention limit where `0` means unlimited.
+fn parse_limit(var: &str, default: u32) -> anyhow::Result<Option<u32>> {
+    Ok(Some(parse_env_or(var, default)?).filter(|limit| *limit > 0))
+}
+
+impl RetentionConfig {
+    /// Reads the configuration. `audit_log_floor_days` is the age below
+    /// which the database refuses to delete audit entries, which the audit
+    /// log's maximum age may not undercut.
+    pub fn from_env(audit_log_floor_days: u32) -> anyhow::Result<Self> {
+        let audit_log_max_age = parse_limit(
+            ENV_NAME_RETENTION_AUDIT_LOG_MAX_AGE_DAYS,
+            DEFAULT_RETENTION_AUDIT_LOG_MAX_AGE_DAYS,
+        )?;
+        if audit_log_max_age.is_some_and(|days| days < audit_log_floor_days) {
+            bail!(
+                "Environment variable \
+                 `{ENV_NAME_RETENTION_AUDIT_LOG_MAX_AGE_DAYS}` must be at \
+                 least {audit_log_floor_days}, or 0 to keep every entry"
+            );
+        }
+        Ok(Self {
+            interval: Duration::from_secs(parse_env_or(
+                ENV_NAME_RETENTION_INTERVAL_SECS,
+                DEFAULT_RETENTION_INTERVAL_SECS,
+            )?),
+            vacuum: parse_env_or(
+                ENV_NAME_RETENTION_VACUUM,
+                DEFAULT_RETENTION_VACUUM,
+            )?,
+            policies: vec![
+                RetentionPolicy {
+                    table: HistoryTable::ConnectionSessions,
+                    max_age_days: parse_limit(
+                        ENV_NAME_RETENTION_CONNECTION_SESSIONS_MAX_AGE_DAYS,
+                        DEFAULT_RETENTION_CONNECTION_SESSIONS_MAX_AGE_DAYS,
+                    )?,
+                    max_rows_per_robot: parse_limit(
+                        ENV_NAME_RETENTION_CONNECTION_SESSIONS_MAX_PER_ROBOT,
+                        DEFAULT_RETENTION_CONNECTION_SESSIONS_MAX_PER_ROBOT,
+                    )?,
+                },
+                RetentionPolicy {
+                    table: HistoryTable::Jobs,
+                    max_age_days: parse_limit(
+                        ENV_NAME_RETENTION_JOBS_MAX_AGE_DAYS,
+                        DEFAULT_RETENTION_JOBS_MAX_AGE_DAYS,
+                    )?,
+                    max_rows_per_robot: None,
+                },
+                RetentionPolicy {
+                    table: HistoryTable::AuditLog,
+                    max_age_days: audit_log_max_age,
+                    max_rows_per_robot: None,
+                },
+            ],
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.interval.is_zero()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Object)]
+pub struct PrunedRows {
+    pub table: HistoryTable,
+    pub rows: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct RetentionRun {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// Rows deleted from each table the run got to.
+    pub pruned: Vec<PrunedRows>,
+    pub vacuumed: bool,
+    /// Why the run stopped early.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Object)]
+pub struct RetentionStatus {
+    /// Zero when runs only happen on demand.
+    pub interval_secs: u64,
+    pub vacuum: bool,
+    pub policies: Vec<RetentionPolicy>,
+    pub running: bool,
+    pub last_run: Option<RetentionRun>,
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+pub struct RetentionService {
+    config: RetentionConfig,
+    running: Mutex<()>,
+    last_run: Mutex<Option<RetentionRun>>,
+    next_run_at: Mutex<Option<DateTime<Utc>>>,
+}
+
+pub static RETENTION_SERVICE: OnceLock<RetentionService> = OnceLock::new();
+
+async fn apply_policy(policy: &RetentionPolicy) -> anyhow::Result<u64> {
+    let mut rows = 0;
+    match policy.table {
+        HistoryTable::ConnectionSessions => {
+            if let Some(days) = policy.max_age_days {
+                rows += with_database(|db| {
+                    db.prune_connection_sessions_by_age(days)
+                })?
+                .await?;
+            }
+            if let Some(max) = policy.max_rows_per_robot {
+                rows += with_database(|db| {
+                    db.prune_connection_sessions_per_robot(max)
+                })?
+                .await?;
+            }
+        }
+        HistoryTable::Jobs => {
+            if let Some(days) = policy.max_age_days {
+                rows += with_database(|db| db.prune_jobs(days))?.await?;
+            }
+        }
+        HistoryTable::AuditLog => {
+            if let Some(days) = policy.max_age_days {
+                rows += with_database(|db| db.prune_audit_log(days))?.await?;
+            }
+        }
+    }
+    Ok(rows)
+}
+
+impl RetentionService {
+    pub fn new(config: RetentionConfig) -> Self {
+        Self {
+            config,
+            running: Mutex::new(()),
+            last_run: Mutex::new(None),
+            next_run_at: Mutex::new(None),
+        }
+    }
+
+    /// Spawns the loop pruning history once per configured interval.
+    pub fn spawn(&'static self) -> Option<JoinHandle<()>> {
+        if !self.config.enabled() {
+            log::info!("Scheduled history pruning is disabled");
+            return None;
+        }
+        log::info!(
+            "Pruning history every {} seconds",
+            self.config.interval.as_secs()
+        );
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.config.interval);
+            // The first tick completes immediately; restarting the service
+            // should not trigger a vacuum.
+            ticker.tick().await;
+            loop {
+                self.schedule_next().await;
+                ticker.tick().await;
+                let run = self.run().await;
+                match &run.error {
+                    None => log::info!(
+                        "Pruned history: {}",
+                        run.pruned
+                            .iter()
+                            .map(|pruned| format!(
+                                "{} rows from {}",
+                                pruned.rows,
+                                pruned.table.as_str()
+                            ))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    Some(err) => log::warn!("Failed to prune history: {err}"),
+                }
+            }
+        }))
+    }
+
+    async fn schedule_next(&self) {
+        let next = chrono::Duration::from_std(self.config.interval)
+            .ok()
+            .and_then(|interval| Utc::now().checked_add_signed(interval));
+        *self.next_run_at.lock().await = next;
+    }
+
+    /// Prunes every table, waiting for a run already in progress to end
+    /// first.
+    pub async fn run(&self) -> RetentionRun {
+        let _running = self.running.lock().await;
+        self.run_locked().await
+    }
+
+    /// Prunes every table unless a run is already in progress.
+    pub async fn try_run(&self) -> Option<RetentionRun> {
+        let _running = self.running.try_lock().ok()?;
+        Some(self.run_locked().await)
+    }
+
+    async fn run_locked(&self) -> RetentionRun {
+        let started_at = Utc::now();
+        let mut pruned = Vec::new();
+        let mut vacuumed = false;
+        let result = async {
+            for policy in &self.config.policies {
+                let rows = apply_policy(policy).await?;
+                pruned.push(PrunedRows {
+                    table: policy.table,
+                    rows,
+                });
+            }
+            with_database(|db| db.optimize(self.config.vacuum))?.await?;
+            vacuumed = self.config.vacuum;
+            anyhow::Ok(())
+        }
+        .await;
+        let run = RetentionRun {
+            started_at,
+            finished_at: Utc::now(),
+            pruned,
+            vacuumed,
+            error: result.err().map(|err| format!("{err:#}")),
+        };
+        *self.last_run.lock().await = Some(run.clone());
+        run
+    }
+
+    pub async fn status(&self) -> RetentionStatus {
+        RetentionStatus {
+            interval_secs: self.config.interval.as_secs(),
+            vacuum: self.config.vacuum,
+            policies: self.config.policies.clone(),
+            running: self.running.try_lock().is_err(),
+            last_run: self.last_run.lock().await.clone(),
+            next_run_at: *self.next_run_at.lock().await,
+        }
+    }
+}
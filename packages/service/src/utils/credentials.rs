@@ -0,0 +1,57 @@
+//! Password hashing and opaque bearer tokens for operator authentication.
+//!
+//! Tokens are random and only their SHA-256 digest is stored, so a leaked
+//! database does not grant access.
+
+use std::fmt::Write;
+
+use argon2::{
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+    password_hash::SaltString,
+};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+/// Minimum accepted password length, in characters.
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+const TOKEN_BYTES: usize = 32;
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// Hashes a password into a PHC string. This is deliberately slow and
+/// should be run off the async executor.
+pub fn hash_password(password: &str) -> anyhow::Result<String> {
+    let salt_bytes: [u8; 16] = rand::rng().random();
+    let salt = SaltString::encode_b64(&salt_bytes)
+        .map_err(|err| anyhow::anyhow!("Failed to encode salt: {err}"))?;
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|err| anyhow::anyhow!("Failed to hash password: {err}"))?
+        .to_string())
+}
+
+/// Checks a password against a PHC string produced by [`hash_password`].
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// Generates a random token, prefixed so that its kind is recognisable.
+pub fn generate_token(prefix: &str) -> String {
+    let bytes: [u8; TOKEN_BYTES] = rand::rng().random();
+    format!("{prefix}{}", to_hex(&bytes))
+}
+
+/// Digest under which a token is stored and looked up.
+pub fn hash_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
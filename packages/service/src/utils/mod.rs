@@ -1 +1,3 @@
+pub mod credentials;
+pub mod network;
 pub mod serde;
@@ -0,0 +1,50 @@
+use std::net::IpAddr;
+
+use crate::{
+    constant::env::{
+        DEFAULT_NETWORK_IGNORED_INTERFACES, ENV_NAME_NETWORK_IGNORED_INTERFACES,
+    },
+    env::parse_env_or,
+};
+
+/// Interfaces whose addresses are expected to repeat across robots and are
+/// not useful to reach them, such as loopback and container bridges.
+/// Interfaces are matched by name prefix.
+pub struct IgnoredInterfaces(Vec<String>);
+
+impl IgnoredInterfaces {
+    pub fn from_env() -> anyhow::Result<Self> {
+        Ok(Self::parse(&parse_env_or(
+            ENV_NAME_NETWORK_IGNORED_INTERFACES,
+            DEFAULT_NETWORK_IGNORED_INTERFACES.to_string(),
+        )?))
+    }
+
+    pub fn parse(list: &str) -> Self {
+        Self(
+            list.split(',')
+                .map(str::trim)
+                .filter(|prefix| !prefix.is_empty())
+                .map(ToString::to_string)
+                .collect(),
+        )
+    }
+
+    pub fn contains(&self, interface: &str) -> bool {
+        self.0
+            .iter()
+            .any(|prefix| interface.starts_with(prefix.as_str()))
+    }
+}
+
+/// Whether `ip` can be used to reach a robot from another host on the
+/// network, i.e. it is not loopback, link-local, unspecified or multicast.
+pub fn is_reachable_ip(ip: IpAddr) -> bool {
+    if ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() {
+        return false;
+    }
+    match ip {
+        IpAddr::V4(ip) => !ip.is_link_local(),
+        IpAddr::V6(ip) => !ip.is_unicast_link_local(),
+    }
+}
@@ -1,5 +1,5 @@
 import * as z from 'zod';
-import { getEndpoint } from '$lib/api/api';
+import { ensureLoggedIn, getEndpoint } from '$lib/api/api';
 import { STATS_ROBOT_ENDPOINT } from '$lib/api/stats/robot_uuid';
 import { STATS_ROBOT_NETWORK_ENDPOINT } from '$lib/api/stats/robot_network';
 import { STATS_ONLINE_ROBOTS_ENDPOINT } from '$lib/api/stats/online_robots';
@@ -24,9 +24,11 @@ export async function actionSetRobotName(
       'Content-Type': 'application/json',
     },
     body: JSON.stringify(body),
+    credentials: 'include',
     signal: AbortSignal.timeout(5000),
   });
 
+  ensureLoggedIn(response);
   if (!response.ok) {
     throw new Error(`Error setting robot name: ${response.status} ${response.statusText}`);
   }
@@ -1,5 +1,5 @@
 import * as z from 'zod';
-import { getEndpoint } from '$lib/api/api';
+import { errorDetail, ensureLoggedIn, getEndpoint } from '$lib/api/api';
 
 export const ACTION_UPDATE_BINARY_ENDPOINT = '/action/update_binary';
 export const ACTION_UPDATE_BINARY_ALL_ENDPOINT = '/action/update_binary_all';
@@ -46,11 +46,13 @@ export async function actionUpdateBinary(
       'Content-Type': 'application/json',
     },
     body: JSON.stringify(body),
+    credentials: 'include',
     signal: AbortSignal.timeout(30000),
   });
 
+  ensureLoggedIn(response);
   if (!response.ok) {
-    const detail = await response.text().catch(() => '');
+    const detail = await errorDetail(response);
     throw new Error(
       `Error updating binary: ${response.status} ${response.statusText}${detail ? ` - ${detail}` : ''}`,
     );
@@ -72,11 +74,13 @@ export async function actionUpdateBinaryAll(
       'Content-Type': 'application/json',
     },
     body: JSON.stringify(body),
+    credentials: 'include',
     signal: AbortSignal.timeout(60000),
   });
 
+  ensureLoggedIn(response);
   if (!response.ok) {
-    const detail = await response.text().catch(() => '');
+    const detail = await errorDetail(response);
     throw new Error(
       `Error updating all binaries: ${response.status} ${response.statusText}${detail ? ` - ${detail}` : ''}`,
     );
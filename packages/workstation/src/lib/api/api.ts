@@ -1,4 +1,46 @@
+import { browser } from '$app/environment';
+import { goto } from '$app/navigation';
+import { currentUser } from '$lib/stores/auth';
+
+export const LOGIN_PATH = '/login';
+
 export function getEndpoint(path: string): string {
   const baseUrl = 'http://localhost:3000/api';
   return `${baseUrl}${path}`;
 }
+
+/** Explanation of a failed response, read from its problem details if any. */
+export async function errorDetail(response: Response): Promise<string> {
+  const body = await response.text().catch(() => '');
+  try {
+    const problem = JSON.parse(body);
+    if (typeof problem.detail === 'string') {
+      return problem.code ? `${problem.detail} (${problem.code})` : problem.detail;
+    }
+  } catch {
+    // Not problem details; show the body as it is.
+  }
+  return body;
+}
+
+/** Thrown for requests made without a valid login session. */
+export class UnauthorizedError extends Error {
+  constructor() {
+    super('Your login session has expired, please log in again');
+  }
+}
+
+/**
+ * Sends the user to the login page, and back to the current page afterwards, if `response` says
+ * they are not logged in.
+ */
+export function ensureLoggedIn(response: Response): void {
+  if (response.status !== 401) return;
+
+  currentUser.set(null);
+  if (browser && window.location.pathname !== LOGIN_PATH) {
+    const from = window.location.pathname + window.location.search;
+    goto(`${LOGIN_PATH}?redirect=${encodeURIComponent(from)}`);
+  }
+  throw new UnauthorizedError();
+}
@@ -0,0 +1,85 @@
+import * as z from 'zod';
+import { errorDetail, getEndpoint } from '$lib/api/api';
+import { currentUser } from '$lib/stores/auth';
+
+export const AUTH_LOGIN_ENDPOINT = '/auth/login';
+export const AUTH_LOGOUT_ENDPOINT = '/auth/logout';
+export const AUTH_ME_ENDPOINT = '/auth/me';
+
+export const AuthLoginRequest = z.object({
+  username: z.string().min(1),
+  password: z.string().min(1),
+});
+export type AuthLoginRequest = z.infer<typeof AuthLoginRequest>;
+
+export const AuthUser = z.object({
+  id: z.number(),
+  username: z.string(),
+  role: z.string(),
+  scope_tags: z.array(z.string()),
+});
+export type AuthUser = z.infer<typeof AuthUser>;
+
+/** Logs in, which sets the session cookie used by every other request. */
+export async function authLogin(
+  trackedFetch: typeof fetch,
+  request: AuthLoginRequest,
+): Promise<AuthUser> {
+  const body = AuthLoginRequest.parse(request);
+
+  const response = await trackedFetch(getEndpoint(AUTH_LOGIN_ENDPOINT), {
+    method: 'POST',
+    headers: {
+      'Content-Type': 'application/json',
+    },
+    body: JSON.stringify(body),
+    credentials: 'include',
+    signal: AbortSignal.timeout(10000),
+  });
+
+  if (!response.ok) {
+    const detail = await errorDetail(response);
+    throw new Error(detail || `Error logging in: ${response.status} ${response.statusText}`);
+  }
+
+  const user = AuthUser.parse(await response.json());
+  currentUser.set(user);
+  return user;
+}
+
+export async function authLogout(trackedFetch: typeof fetch): Promise<void> {
+  const response = await trackedFetch(getEndpoint(AUTH_LOGOUT_ENDPOINT), {
+    method: 'POST',
+    credentials: 'include',
+    signal: AbortSignal.timeout(5000),
+  });
+
+  if (!response.ok) {
+    throw new Error(`Error logging out: ${response.status} ${response.statusText}`);
+  }
+  currentUser.set(null);
+}
+
+/** Looks up the logged in operator, or returns `null` if there is none. */
+export async function fetchAuthMe(trackedFetch: typeof fetch): Promise<AuthUser | null> {
+  const response = await trackedFetch(getEndpoint(AUTH_ME_ENDPOINT), {
+    method: 'GET',
+    headers: {
+      'Content-Type': 'application/json',
+    },
+    credentials: 'include',
+    signal: AbortSignal.timeout(5000),
+  });
+
+  if (response.status === 401) {
+    currentUser.set(null);
+    return null;
+  }
+  if (!response.ok) {
+    throw new Error(`Error fetching current user: ${response.status} ${response.statusText}`);
+  }
+
+  const user = AuthUser.parse(await response.json());
+  currentUser.set(user);
+  return user;
+}
@@ -1,5 +1,5 @@
 import * as z from 'zod';
-import { getEndpoint } from '../api';
+import { ensureLoggedIn, getEndpoint } from '../api';
 
 export const STATS_ONLINE_ROBOTS_ENDPOINT = '/stats/online_robots';
 
@@ -15,9 +15,11 @@ export async function fetchStatsOnlineRobots(
     headers: {
       'Content-Type': 'application/json',
     },
+    credentials: 'include',
     signal: AbortSignal.timeout(5000),
   });
 
+  ensureLoggedIn(response);
   if (!response.ok) {
     throw new Error(`Error fetching stats robots: ${response.status} ${response.statusText}`);
   }
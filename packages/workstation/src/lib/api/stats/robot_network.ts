@@ -1,5 +1,5 @@
 import * as z from 'zod';
-import { getEndpoint } from '../api';
+import { ensureLoggedIn, getEndpoint } from '../api';
 
 export const STATS_ROBOT_NETWORK_ENDPOINT = (robotUuid: string) =>
   `/stats/robot/${robotUuid}/network`;
@@ -35,9 +35,11 @@ export async function fetchStatsRobotNetwork(
     headers: {
       'Content-Type': 'application/json',
     },
+    credentials: 'include',
     signal: AbortSignal.timeout(5000),
   });
 
+  ensureLoggedIn(response);
   if (!response.ok) {
     throw new Error(
       `Error fetching stats robot network ${robotUuid}: ${response.status} ${response.statusText}`,
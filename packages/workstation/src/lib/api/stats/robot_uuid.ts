@@ -1,5 +1,5 @@
 import * as z from 'zod';
-import { getEndpoint } from '../api';
+import { ensureLoggedIn, getEndpoint } from '../api';
 
 export const STATS_ROBOT_ENDPOINT = (robotUuid: string) => `/stats/robot/${robotUuid}`;
 
@@ -20,9 +20,11 @@ export async function fetchStatsRobot(
     headers: {
       'Content-Type': 'application/json',
     },
+    credentials: 'include',
     signal: AbortSignal.timeout(5000),
   });
 
+  ensureLoggedIn(response);
   if (!response.ok) {
     throw new Error(
       `Error fetching stats robot ${robotUuid}: ${response.status} ${response.statusText}`,
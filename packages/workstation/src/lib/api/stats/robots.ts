@@ -1,5 +1,5 @@
 import * as z from 'zod';
-import { getEndpoint } from '../api';
+import { ensureLoggedIn, getEndpoint } from '../api';
 
 export const STATS_ROBOTS_ENDPOINT = '/stats/robots';
 
@@ -13,9 +13,11 @@ export async function fetchStatsRobots(trackedFetch: typeof fetch): Promise<Stat
     headers: {
       'Content-Type': 'application/json',
     },
+    credentials: 'include',
     signal: AbortSignal.timeout(5000),
   });
 
+  ensureLoggedIn(response);
   if (!response.ok) {
     throw new Error(`Error fetching stats robots: ${response.status} ${response.statusText}`);
   }
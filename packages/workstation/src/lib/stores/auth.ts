@@ -0,0 +1,5 @@
+import { writable, type Writable } from 'svelte/store';
+import type { AuthUser } from '$lib/api/auth/session';
+
+/** The logged in operator, or `null` if there is no valid login session. */
+export const currentUser: Writable<AuthUser | null> = writable(null);